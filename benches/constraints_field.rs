@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use oid4vp::presentation_exchange::ConstraintsField;
+use serde_json::json;
+
+fn field() -> ConstraintsField {
+    serde_json::from_value(json!({
+        "path": ["$.vc.credentialSubject.degree.type", "$.credentialSubject.degree.type"],
+        "filter": { "type": "string", "pattern": "^(Bachelor|Master)Degree$" }
+    }))
+    .unwrap()
+}
+
+/// Compare validating with a field whose paths and filter are already parsed against a field that
+/// parses them on first use.
+fn validate(c: &mut Criterion) {
+    let credential = json!({
+        "credentialSubject": { "degree": { "type": "BachelorDegree" } }
+    });
+
+    let cached = field();
+    c.bench_function("validate cached", |b| {
+        b.iter(|| cached.validate(&credential).unwrap())
+    });
+    c.bench_function("validate uncached", |b| {
+        b.iter_batched(
+            field,
+            |field| field.validate(&credential).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, validate);
+criterion_main!(benches);
@@ -1,4 +1,7 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::OnceLock,
+};
 
 use anyhow::{anyhow, bail, Context, Error, Result};
 use serde::{Deserialize, Serialize};
@@ -9,15 +12,15 @@ use crate::wallet::Wallet;
 
 use self::{
     parameters::{
-        ClientId, ClientIdScheme, Nonce, PresentationDefinition, PresentationDefinitionUri,
-        RedirectUri, ResponseMode, ResponseType, ResponseUri,
+        ClientId, ClientIdScheme, ClientMetadata, DcqlQuery, Nonce, PresentationDefinition,
+        PresentationDefinitionUri, RedirectUri, ResponseMode, ResponseType, ResponseUri,
     },
     verification::verify_request,
 };
 
 use super::{
     object::{ParsingErrorContext, UntypedObject},
-    util::{base_request, AsyncHttpClient},
+    util::AsyncHttpClient,
 };
 
 pub mod parameters;
@@ -31,9 +34,11 @@ pub struct AuthorizationRequestObject(
     ClientIdScheme,
     ResponseMode,
     ResponseType,
-    PresentationDefinitionIndirection,
+    Option<PresentationDefinitionIndirection>,
     Url,
     Nonce,
+    OnceLock<PresentationDefinition>,
+    OnceLock<ClientMetadata>,
 );
 
 /// An Authorization Request.
@@ -68,47 +73,23 @@ impl AuthorizationRequest {
     pub async fn validate<W: Wallet + ?Sized>(
         self,
         wallet: &W,
-    ) -> Result<AuthorizationRequestObject> {
+    ) -> Result<AuthorizationRequestObject, crate::Error> {
         let jwt = match self.request_indirection {
             RequestIndirection::ByValue(jwt) => jwt,
-            RequestIndirection::ByReference(url) => {
-                let request = base_request()
-                    .method("GET")
-                    .uri(url.to_string())
-                    .body(vec![])
-                    .context("failed to build authorization request request")?;
-
-                let response = wallet
-                    .http_client()
-                    .execute(request)
-                    .await
-                    .context(format!(
-                        "failed to make authorization request request at {url}"
-                    ))?;
-
-                let status = response.status();
-                let Ok(body) = String::from_utf8(response.into_body()) else {
-                    bail!("failed to parse authorization request response as UTF-8 from {url} (status: {status})")
-                };
-
-                if !status.is_success() {
-                    bail!(
-                        "authorization request request was unsuccessful (status: {status}): {body}"
-                    )
-                }
-
-                body
-            }
+            RequestIndirection::ByReference(url) => fetch_request_object(wallet, &url)
+                .await
+                .map_err(crate::Error::Resolution)?,
         };
         let aro = verify_request(wallet, jwt)
             .await
-            .context("unable to validate Authorization Request")?;
+            .context("unable to validate Authorization Request")
+            .map_err(crate::Error::InvalidRequest)?;
         if self.client_id.as_str() != aro.client_id().0.as_str() {
-            bail!(
+            return Err(crate::Error::InvalidRequest(anyhow!(
                 "Authorization Request and Request Object have different client ids: '{}' vs. '{}'",
                 self.client_id,
                 aro.client_id().0
-            );
+            )));
         }
         Ok(aro)
     }
@@ -195,6 +176,26 @@ impl AuthorizationRequest {
     }
 }
 
+/// Fetch a request object passed by reference.
+async fn fetch_request_object<W: Wallet + ?Sized>(wallet: &W, url: &Url) -> Result<String> {
+    let response = wallet.http_client().get(url).await.context(format!(
+        "failed to make authorization request request at {url}"
+    ))?;
+
+    let status = response.status();
+    let Ok(body) = String::from_utf8(response.into_body()) else {
+        bail!(
+            "failed to parse authorization request response as UTF-8 from {url} (status: {status})"
+        )
+    };
+
+    if !status.is_success() {
+        bail!("authorization request request was unsuccessful (status: {status}): {body}")
+    }
+
+    Ok(body)
+}
+
 impl AuthorizationRequestObject {
     pub fn client_id(&self) -> &ClientId {
         &self.1
@@ -204,20 +205,28 @@ impl AuthorizationRequestObject {
         &self.2
     }
 
-    pub async fn resolve_presentation_definition<H: AsyncHttpClient>(
+    /// Resolve the presentation definition, passed by value or by reference.
+    ///
+    /// A presentation definition passed by reference is fetched from the
+    /// `presentation_definition_uri` on the first call, and cached for subsequent calls.
+    ///
+    /// Returns an error if the request uses a [DcqlQuery] instead of a presentation definition.
+    pub async fn resolve_presentation_definition<H: AsyncHttpClient + Sync>(
         &self,
         http_client: &H,
     ) -> Result<PresentationDefinition> {
-        match &self.5 {
+        let Some(pd_indirection) = &self.5 else {
+            bail!("the request does not contain a presentation definition")
+        };
+
+        match pd_indirection {
             PresentationDefinitionIndirection::ByValue(by_value) => Ok(by_value.clone()),
             PresentationDefinitionIndirection::ByReference(by_reference) => {
-                let request = base_request()
-                    .method("GET")
-                    .uri(by_reference.to_string())
-                    .body(vec![])
-                    .context("failed to build presentation definition request")?;
+                if let Some(presentation_definition) = self.8.get() {
+                    return Ok(presentation_definition.clone());
+                }
 
-                let response = http_client.execute(request).await.context(format!(
+                let response = http_client.get(by_reference).await.context(format!(
                     "failed to make presentation definition request at {by_reference}"
                 ))?;
 
@@ -227,12 +236,14 @@ impl AuthorizationRequestObject {
                     bail!("presentation definition request was unsuccessful (status: {status})")
                 }
 
-                serde_json::from_slice::<Json>(response.body())
+                let presentation_definition: PresentationDefinition = serde_json::from_slice::<Json>(response.body())
                     .context(format!(
                     "failed to parse presentation definition response as JSON from {by_reference} (status: {status})"
                 ))?
                 .try_into()
-                .context("failed to parse presentation definition from JSON")
+                .context("failed to parse presentation definition from JSON")?;
+
+                Ok(self.8.get_or_init(|| presentation_definition).clone())
             }
         }
     }
@@ -313,18 +324,26 @@ impl TryFrom<UntypedObject> for AuthorizationRequestObject {
         let pd_indirection = match (
             value.get::<PresentationDefinition>(),
             value.get::<PresentationDefinitionUri>(),
+            value.get::<DcqlQuery>(),
         ) {
-            (None, None) => bail!(
-                "one of 'presentation_definition' and 'presentation_definition_uri' are required"
+            (None, None, None) => bail!(
+                "one of 'presentation_definition', 'presentation_definition_uri' and 'dcql_query' are required"
             ),
-            (Some(_), Some(_)) => {
+            (Some(_), Some(_), _) => {
                 bail!("'presentation_definition' and 'presentation_definition_uri' are mutually exclusive")
             }
-            (Some(by_value), None) => {
-                PresentationDefinitionIndirection::ByValue(by_value.parsing_error()?)
+            (Some(_), _, Some(_)) | (_, Some(_), Some(_)) => {
+                bail!("'dcql_query' and a presentation definition are mutually exclusive")
             }
-            (None, Some(by_reference)) => {
-                PresentationDefinitionIndirection::ByReference(by_reference.parsing_error()?.0)
+            (Some(by_value), None, None) => Some(PresentationDefinitionIndirection::ByValue(
+                by_value.parsing_error()?,
+            )),
+            (None, Some(by_reference), None) => Some(PresentationDefinitionIndirection::ByReference(
+                by_reference.parsing_error()?.0,
+            )),
+            (None, None, Some(dcql_query)) => {
+                dcql_query.parsing_error()?;
+                None
             }
         };
 
@@ -339,6 +358,8 @@ impl TryFrom<UntypedObject> for AuthorizationRequestObject {
             pd_indirection,
             return_uri,
             nonce,
+            OnceLock::new(),
+            OnceLock::new(),
         ))
     }
 }
@@ -356,3 +377,145 @@ impl DerefMut for AuthorizationRequestObject {
         &mut self.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use http::{Request, Response};
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct PresentationDefinitionServer(AtomicUsize);
+
+    #[async_trait]
+    impl AsyncHttpClient for PresentationDefinitionServer {
+        async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+            assert_eq!(
+                request.uri(),
+                "https://verifier.example.com/presentation-definition"
+            );
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let body = json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{ "id": "vc" }]
+            });
+            Ok(Response::builder()
+                .status(200)
+                .body(serde_json::to_vec(&body)?)?)
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_presentation_definition_uri() {
+        let request: AuthorizationRequestObject = serde_json::from_value(json!({
+            "client_id": "did:example:verifier",
+            "client_id_scheme": "did",
+            "response_mode": "direct_post",
+            "response_type": "vp_token",
+            "response_uri": "https://verifier.example.com/response",
+            "nonce": "random123",
+            "presentation_definition_uri": "https://verifier.example.com/presentation-definition"
+        }))
+        .unwrap();
+
+        let http_client = PresentationDefinitionServer::default();
+
+        for _ in 0..2 {
+            let presentation_definition = request
+                .resolve_presentation_definition(&http_client)
+                .await
+                .unwrap();
+            assert_eq!(
+                presentation_definition.parsed().id,
+                "0b4dd017-efa6-4a05-a269-9790fa3c22c2"
+            );
+        }
+
+        assert_eq!(http_client.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[derive(Default)]
+    struct ClientMetadataServer(AtomicUsize);
+
+    #[async_trait]
+    impl AsyncHttpClient for ClientMetadataServer {
+        async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+            assert_eq!(
+                request.uri(),
+                "https://verifier.example.com/client-metadata"
+            );
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let body = json!({ "client_name": "Verifier" });
+            Ok(Response::builder()
+                .status(200)
+                .body(serde_json::to_vec(&body)?)?)
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_client_metadata_uri() {
+        let request: AuthorizationRequestObject = serde_json::from_value(json!({
+            "client_id": "did:example:verifier",
+            "client_id_scheme": "did",
+            "response_mode": "direct_post",
+            "response_type": "vp_token",
+            "response_uri": "https://verifier.example.com/response",
+            "nonce": "random123",
+            "presentation_definition": {
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{ "id": "vc" }]
+            },
+            "client_metadata_uri": "https://verifier.example.com/client-metadata"
+        }))
+        .unwrap();
+
+        let http_client = ClientMetadataServer::default();
+
+        for _ in 0..2 {
+            let ClientMetadata(client_metadata) = ClientMetadata::resolve(&request, &http_client)
+                .await
+                .unwrap();
+            assert_eq!(client_metadata.0["client_name"], "Verifier");
+        }
+
+        assert_eq!(http_client.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn client_id_scheme_validation() {
+        let client_id = |id: &str| ClientId(id.into());
+        let response_uri: Url = "https://verifier.example.com/response".parse().unwrap();
+
+        client_id("did:example:verifier")
+            .validate_for_scheme(&ClientIdScheme::Did, None)
+            .unwrap();
+        assert!(client_id("https://verifier.example.com")
+            .validate_for_scheme(&ClientIdScheme::Did, None)
+            .is_err());
+
+        client_id("https://verifier.example.com/callback")
+            .validate_for_scheme(&ClientIdScheme::RedirectUri, Some(&response_uri))
+            .unwrap();
+        assert!(client_id("https://attacker.example.com/callback")
+            .validate_for_scheme(&ClientIdScheme::RedirectUri, Some(&response_uri))
+            .is_err());
+
+        client_id("https://verifier.example.com")
+            .validate_for_scheme(&ClientIdScheme::EntityId, None)
+            .unwrap();
+        assert!(client_id("http://verifier.example.com")
+            .validate_for_scheme(&ClientIdScheme::EntityId, None)
+            .is_err());
+
+        client_id("verifier.example.com")
+            .validate_for_scheme(&ClientIdScheme::X509SanDns, None)
+            .unwrap();
+        assert!(client_id("https://verifier.example.com")
+            .validate_for_scheme(&ClientIdScheme::X509SanDns, None)
+            .is_err());
+    }
+}
@@ -1,10 +1,16 @@
 use std::fmt;
 
 use crate::core::{
+    metadata::parameters::verifier::{
+        AuthorizationEncryptedResponseAlg, AuthorizationEncryptedResponseEnc,
+        EncryptedResponseEncValuesSupported, JWKs, VpFormats,
+    },
     object::{ParsingErrorContext, TypedParameter, UntypedObject},
-    util::{base_request, AsyncHttpClient},
+    util::AsyncHttpClient,
 };
+use crate::presentation_exchange::ClaimFormatMap;
 use anyhow::{bail, Context, Error, Ok};
+use base64::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as Json;
 use url::Url;
@@ -38,6 +44,58 @@ impl TypedParameter for ClientId {
     const KEY: &'static str = "client_id";
 }
 
+impl ClientId {
+    /// Check that the client identifier is valid for its `client_id_scheme`.
+    ///
+    /// - `did`: the identifier must be a DID.
+    /// - `redirect_uri`: the identifier must be a URL with the same host as the `return_uri`
+    ///   (the `response_uri` or `redirect_uri` of the request), if provided.
+    /// - `entity_id`: the identifier must be an HTTPS URL.
+    /// - `x509_san_uri`: the identifier must be a URL.
+    /// - `x509_san_dns`: the identifier must be a DNS name.
+    ///
+    /// Other schemes are not checked.
+    pub fn validate_for_scheme(
+        &self,
+        scheme: &ClientIdScheme,
+        return_uri: Option<&Url>,
+    ) -> Result<(), Error> {
+        let id = &self.0;
+        let url = || Url::parse(id).context(format!("client_id '{id}' is not a URL"));
+
+        match scheme {
+            ClientIdScheme::Did => {
+                let is_did = id
+                    .strip_prefix("did:")
+                    .and_then(|did| did.split_once(':'))
+                    .is_some_and(|(method, method_id)| !method.is_empty() && !method_id.is_empty());
+                if !is_did {
+                    bail!("client_id '{id}' is not a DID, as required by the '{scheme}' scheme")
+                }
+            }
+            ClientIdScheme::RedirectUri => {
+                let host = url()?.host_str().map(str::to_owned);
+                if let Some(return_uri) = return_uri.filter(|uri| uri.host_str() != host.as_deref())
+                {
+                    bail!("client_id '{id}' does not have the same host as the return URI '{return_uri}'")
+                }
+            }
+            ClientIdScheme::EntityId if url()?.scheme() != "https" => {
+                bail!("client_id '{id}' is not an HTTPS URL, as required by the '{scheme}' scheme")
+            }
+            ClientIdScheme::X509SanUri => {
+                url()?;
+            }
+            ClientIdScheme::X509SanDns if id.is_empty() || id.contains(['/', ':']) => {
+                bail!("client_id '{id}' is not a DNS name, as required by the '{scheme}' scheme")
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
 impl TryFrom<Json> for ClientId {
     type Error = Error;
 
@@ -126,11 +184,57 @@ impl TryFrom<Json> for ClientMetadata {
 }
 
 impl ClientMetadata {
+    /// The `vp_formats` accepted by the verifier, if present.
+    pub fn vp_formats(&self) -> Result<Option<ClaimFormatMap>, Error> {
+        self.0
+            .get::<VpFormats>()
+            .transpose()?
+            .map(|VpFormats(formats)| {
+                serde_json::from_value(Json::Object(formats)).context("invalid 'vp_formats'")
+            })
+            .transpose()
+    }
+
+    /// The `jwks` of the verifier, such as its encryption keys, if present.
+    pub fn jwks(&self) -> Result<Option<JWKs>, Error> {
+        self.0.get().transpose()
+    }
+
+    /// The `authorization_encrypted_response_alg`, if present.
+    pub fn authorization_encrypted_response_alg(&self) -> Result<Option<String>, Error> {
+        Ok(self
+            .0
+            .get::<AuthorizationEncryptedResponseAlg>()
+            .transpose()?
+            .map(|alg| alg.0))
+    }
+
+    /// The `authorization_encrypted_response_enc`, if present.
+    pub fn authorization_encrypted_response_enc(&self) -> Result<Option<String>, Error> {
+        Ok(self
+            .0
+            .get::<AuthorizationEncryptedResponseEnc>()
+            .transpose()?
+            .map(|enc| enc.0))
+    }
+
+    /// The `encrypted_response_enc_values_supported`, if present.
+    pub fn encrypted_response_enc_values_supported(&self) -> Result<Option<Vec<String>>, Error> {
+        Ok(self
+            .0
+            .get::<EncryptedResponseEncValuesSupported>()
+            .transpose()?
+            .map(|encs| encs.0))
+    }
+
     /// Resolves the client metadata from the Authorization Request Object.
     ///
+    /// Client metadata passed by reference is fetched from the `client_metadata_uri` on the first
+    /// call, and cached in the request object for subsequent calls.
+    ///
     /// If the client metadata is not passed by reference or value if the Authorization Request Object,
     /// then this function will return an error.
-    pub async fn resolve<H: AsyncHttpClient>(
+    pub async fn resolve<H: AsyncHttpClient + Sync>(
         request: &AuthorizationRequestObject,
         http_client: &H,
     ) -> Result<Self, Error> {
@@ -139,15 +243,13 @@ impl ClientMetadata {
         }
 
         if let Some(metadata_uri) = request.get::<ClientMetadataUri>() {
-            let uri = metadata_uri.parsing_error()?.0;
-            let request = base_request()
-                .method("GET")
-                .uri(uri.to_string())
-                .body(vec![])
-                .context("failed to build client metadata request")?;
+            if let Some(metadata) = request.9.get() {
+                return Ok(metadata.clone());
+            }
 
+            let uri = metadata_uri.parsing_error()?.0;
             let response = http_client
-                .execute(request)
+                .get(&uri)
                 .await
                 .context(format!("failed to make client metadata request at {uri}"))?;
 
@@ -157,12 +259,14 @@ impl ClientMetadata {
                 bail!("client metadata request was unsuccessful (status: {status})")
             }
 
-            return serde_json::from_slice::<Json>(response.body())
+            let metadata: ClientMetadata = serde_json::from_slice::<Json>(response.body())
                 .context(format!(
                 "failed to parse client metadata response as JSON from {uri} (status: {status})"
             ))?
                 .try_into()
-                .context("failed to parse client metadata from JSON");
+                .context("failed to parse client metadata from JSON")?;
+
+            return Ok(request.9.get_or_init(|| metadata).clone());
         }
 
         tracing::warn!("the client metadata was not passed by reference or value");
@@ -195,6 +299,17 @@ impl TryFrom<Json> for ClientMetadataUri {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Nonce(pub String);
 
+impl Nonce {
+    /// The minimum length of a nonce issued by the verifier, as 22 base64url characters encode
+    /// 128 bits.
+    pub const MIN_LENGTH: usize = 22;
+
+    /// A nonce of 256 random bits, base64url-encoded.
+    pub fn random() -> Self {
+        Self(BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>()))
+    }
+}
+
 impl TypedParameter for Nonce {
     const KEY: &'static str = "nonce";
 }
@@ -475,6 +590,51 @@ impl From<PresentationDefinition> for Json {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct DcqlQuery {
+    raw: Json,
+    parsed: crate::core::dcql_query::DcqlQuery,
+}
+
+impl DcqlQuery {
+    pub fn into_parsed(self) -> crate::core::dcql_query::DcqlQuery {
+        self.parsed
+    }
+
+    pub fn parsed(&self) -> &crate::core::dcql_query::DcqlQuery {
+        &self.parsed
+    }
+}
+
+impl TryFrom<crate::core::dcql_query::DcqlQuery> for DcqlQuery {
+    type Error = Error;
+
+    fn try_from(parsed: crate::core::dcql_query::DcqlQuery) -> Result<Self, Self::Error> {
+        let raw = serde_json::to_value(parsed.clone())?;
+        Ok(Self { raw, parsed })
+    }
+}
+
+impl TypedParameter for DcqlQuery {
+    const KEY: &'static str = "dcql_query";
+}
+
+impl TryFrom<Json> for DcqlQuery {
+    type Error = Error;
+
+    fn try_from(value: Json) -> Result<Self, Self::Error> {
+        let parsed: crate::core::dcql_query::DcqlQuery = serde_json::from_value(value.clone())?;
+        parsed.validate_structure()?;
+        Ok(Self { raw: value, parsed })
+    }
+}
+
+impl From<DcqlQuery> for Json {
+    fn from(value: DcqlQuery) -> Self {
+        value.raw
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PresentationDefinitionUri(pub Url);
 
@@ -1,7 +1,10 @@
 use crate::{
     core::{
         metadata::parameters::{
-            verifier::{AuthorizationEncryptedResponseAlg, AuthorizationEncryptedResponseEnc},
+            verifier::{
+                AuthorizationEncryptedResponseAlg, AuthorizationEncryptedResponseEnc,
+                EncryptedResponseEncValuesSupported,
+            },
             wallet::{
                 AuthorizationEncryptionAlgValuesSupported,
                 AuthorizationEncryptionEncValuesSupported, ClientIdSchemesSupported,
@@ -20,12 +23,14 @@ use super::{
 };
 
 pub mod did;
+pub mod redirect_uri;
 pub mod verifier;
 pub mod x509_san;
 
 /// Verifies Authorization Request Objects.
 #[allow(unused_variables)]
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait RequestVerifier {
     /// Performs verification on Authorization Request Objects when `client_id_scheme` is `did`.
     async fn did(
@@ -55,12 +60,14 @@ pub trait RequestVerifier {
     }
 
     /// Performs verification on Authorization Request Objects when `client_id_scheme` is `redirect_uri`.
+    ///
+    /// Defaults to [redirect_uri::validate].
     async fn redirect_uri(
         &self,
         decoded_request: &AuthorizationRequestObject,
         request_jwt: String,
     ) -> Result<(), Error> {
-        bail!("'redirect_uri' client verification not implemented")
+        redirect_uri::validate(decoded_request, request_jwt)
     }
 
     /// Performs verification on Authorization Request Objects when `client_id_scheme` is `verifier_attestation`.
@@ -155,9 +162,15 @@ pub(crate) async fn validate_request_against_metadata<W: Wallet + ?Sized>(
         let alg = client_metadata
             .get::<AuthorizationEncryptedResponseAlg>()
             .parsing_error()?;
-        let enc = client_metadata
-            .get::<AuthorizationEncryptedResponseEnc>()
-            .parsing_error()?;
+        let encs = match client_metadata.get::<AuthorizationEncryptedResponseEnc>() {
+            Some(enc) => vec![enc.parsing_error()?.0],
+            None => {
+                client_metadata
+                    .get::<EncryptedResponseEncValuesSupported>()
+                    .parsing_error()?
+                    .0
+            }
+        };
 
         if let Some(supported_algs) =
             wallet_metadata.get::<AuthorizationEncryptionAlgValuesSupported>()
@@ -173,11 +186,12 @@ pub(crate) async fn validate_request_against_metadata<W: Wallet + ?Sized>(
         if let Some(supported_encs) =
             wallet_metadata.get::<AuthorizationEncryptionEncValuesSupported>()
         {
-            if !supported_encs?.0.contains(&enc.0) {
+            let supported_encs = supported_encs?.0;
+            if !encs.iter().any(|enc| supported_encs.contains(enc)) {
                 bail!(
                     "unsupported {} '{}'",
                     AuthorizationEncryptedResponseEnc::KEY,
-                    enc.0
+                    encs.join("', '")
                 )
             }
         }
@@ -185,3 +199,228 @@ pub(crate) async fn validate_request_against_metadata<W: Wallet + ?Sized>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use base64::prelude::*;
+    use did_method_key::DIDKey;
+    use http::{Request, Response};
+    use serde_json::json;
+    use ssi::did::DIDMethod;
+
+    use crate::{
+        core::{metadata::WalletMetadata, util::AsyncHttpClient},
+        verifier::{
+            client::{Client, DIDClient},
+            request_signer::P256Signer,
+        },
+    };
+
+    use super::*;
+
+    const VERIFIER_DID: &str = "did:key:zDnaeaDj3YpPR4JXos2kCCNPS86hdELeN5PZh97KGkoFzUtGn";
+
+    struct NoHttpClient;
+
+    #[async_trait]
+    impl AsyncHttpClient for NoHttpClient {
+        async fn execute(&self, _: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+            bail!("unexpected request")
+        }
+    }
+
+    struct TestWallet {
+        metadata: WalletMetadata,
+        trusted_dids: Vec<String>,
+    }
+
+    impl TestWallet {
+        fn new(trusted_dids: Vec<String>) -> Self {
+            let metadata = serde_json::from_value(json!({
+                "authorization_endpoint": "openid4vp:",
+                "client_id_schemes_supported": ["did", "redirect_uri"],
+                "request_object_signing_alg_values_supported": ["ES256"],
+                "vp_formats_supported": {}
+            }))
+            .unwrap();
+            Self {
+                metadata,
+                trusted_dids,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RequestVerifier for TestWallet {
+        async fn did(
+            &self,
+            decoded_request: &AuthorizationRequestObject,
+            request_jwt: String,
+        ) -> Result<()> {
+            did::verify_with_resolver(
+                &self.metadata,
+                decoded_request,
+                request_jwt,
+                Some(&self.trusted_dids),
+                DIDKey.to_resolver(),
+            )
+            .await
+        }
+    }
+
+    impl Wallet for TestWallet {
+        type HttpClient = NoHttpClient;
+
+        fn metadata(&self) -> &WalletMetadata {
+            &self.metadata
+        }
+
+        fn http_client(&self) -> &Self::HttpClient {
+            &NoHttpClient
+        }
+    }
+
+    fn request_object(client_id: &str, client_id_scheme: &str) -> AuthorizationRequestObject {
+        serde_json::from_value::<UntypedObject>(json!({
+            "client_id": client_id,
+            "client_id_scheme": client_id_scheme,
+            "client_metadata": {},
+            "response_mode": "direct_post",
+            "response_type": "vp_token",
+            "response_uri": "https://verifier.example.com/response",
+            "nonce": "random123",
+            "presentation_definition": {
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{ "id": "vc" }]
+            }
+        }))
+        .unwrap()
+        .try_into()
+        .unwrap()
+    }
+
+    async fn did_request_jwt() -> String {
+        let signer = Arc::new(
+            P256Signer::new(
+                p256::SecretKey::from_jwk_str(include_str!(
+                    "../../../../tests/examples/verifier.jwk"
+                ))
+                .unwrap()
+                .into(),
+            )
+            .unwrap(),
+        );
+        let vm = format!(
+            "{VERIFIER_DID}#{}",
+            VERIFIER_DID.trim_start_matches("did:key:")
+        );
+        DIDClient::new(vm, signer, DIDKey.to_resolver())
+            .await
+            .unwrap()
+            .generate_request_object_jwt(&request_object(VERIFIER_DID, "did"))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn did_scheme() {
+        let wallet = TestWallet::new(vec![VERIFIER_DID.to_owned()]);
+        verify_request(&wallet, did_request_jwt().await)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn did_scheme_untrusted() {
+        let wallet = TestWallet::new(vec![]);
+        verify_request(&wallet, did_request_jwt().await)
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn verify_request_jwt() {
+        let wallet = TestWallet::new(vec![]);
+        let request_object = request_object(VERIFIER_DID, "did");
+        wallet
+            .verify_request_jwt(
+                &request_object,
+                did_request_jwt().await,
+                DIDKey.to_resolver(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_request_jwt_tampered_signature() {
+        let wallet = TestWallet::new(vec![]);
+        let request_object = request_object(VERIFIER_DID, "did");
+        let (headers, body, signature) = ssi::jws::split_jws(&did_request_jwt().await)
+            .map(|(h, b, s)| (h.to_owned(), b.to_owned(), s.to_owned()))
+            .unwrap();
+        let mut signature = BASE64_URL_SAFE_NO_PAD.decode(signature).unwrap();
+        signature[0] ^= 1;
+        let jwt = format!(
+            "{headers}.{body}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(signature)
+        );
+        wallet
+            .verify_request_jwt(&request_object, jwt, DIDKey.to_resolver())
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn verify_request_jwt_unknown_kid() {
+        let wallet = TestWallet::new(vec![]);
+        let request_object = request_object(VERIFIER_DID, "did");
+        let jwt = did_request_jwt().await;
+        let (_, body, signature) = ssi::jws::split_jws(&jwt).unwrap();
+        let headers = BASE64_URL_SAFE_NO_PAD.encode(
+            json!({
+                "alg": "ES256",
+                "kid": format!("{VERIFIER_DID}#unknown"),
+                "typ": "JWT",
+            })
+            .to_string(),
+        );
+        let jwt = format!("{headers}.{body}.{signature}");
+        let error = wallet
+            .verify_request_jwt(&request_object, jwt, DIDKey.to_resolver())
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unable to resolve verification method"));
+    }
+
+    #[tokio::test]
+    async fn redirect_uri_scheme() {
+        let wallet = TestWallet::new(vec![]);
+        let request_object =
+            request_object("https://verifier.example.com/response", "redirect_uri");
+        let jwt = ssi::jwt::encode_unsigned(&UntypedObject::from(request_object)).unwrap();
+        verify_request(&wallet, jwt).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn redirect_uri_scheme_mismatch() {
+        let wallet = TestWallet::new(vec![]);
+        let request_object = request_object("https://other.example.com/response", "redirect_uri");
+        let jwt = ssi::jwt::encode_unsigned(&UntypedObject::from(request_object)).unwrap();
+        verify_request(&wallet, jwt).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn redirect_uri_scheme_signed() {
+        let wallet = TestWallet::new(vec![]);
+        let request_object =
+            request_object("https://verifier.example.com/response", "redirect_uri");
+        let jwt = ssi::jwt::encode_unsigned(&UntypedObject::from(request_object)).unwrap();
+        let jwt = format!("{jwt}c2lnbmF0dXJl");
+        verify_request(&wallet, jwt).await.unwrap_err();
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{bail, Context, Result};
+use base64::prelude::*;
+use serde_json::{Map, Value as Json};
+use url::Url;
+
+use crate::core::authorization_request::AuthorizationRequestObject;
+
+/// Default implementation of request validation for `client_id_scheme` `redirect_uri`.
+///
+/// The request object must not be signed, and the `client_id` must be the URI that the response
+/// will be returned to.
+pub fn validate(request_object: &AuthorizationRequestObject, request_jwt: String) -> Result<()> {
+    let (headers_b64, _, sig_b64) = ssi::jws::split_jws(&request_jwt)?;
+
+    let headers_json_bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(headers_b64)
+        .context("jwt headers were not valid base64url")?;
+
+    let mut headers = serde_json::from_slice::<Map<String, Json>>(&headers_json_bytes)
+        .context("jwt headers were not valid json")?;
+
+    let Json::String(alg) = headers
+        .remove("alg")
+        .context("'alg' was missing from jwt headers")?
+    else {
+        bail!("'alg' header was not a string")
+    };
+
+    if alg != "none" || !sig_b64.is_empty() {
+        bail!("requests with the 'redirect_uri' client_id_scheme must not be signed")
+    }
+
+    let client_id = &request_object.client_id().0;
+    let client_id_uri: Url = client_id
+        .parse()
+        .context(format!("'client_id' ({client_id}) is not a valid URI"))?;
+
+    if &client_id_uri != request_object.return_uri() {
+        bail!(
+            "'client_id' ({client_id}) does not match the response URI ({})",
+            request_object.return_uri()
+        )
+    }
+
+    Ok(())
+}
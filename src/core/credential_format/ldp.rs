@@ -0,0 +1,218 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value as Json;
+use ssi::{
+    did_resolve::DIDResolver,
+    jsonld::ContextLoader,
+    vc::{Credential, LinkedDataProofOptions, Presentation, ProofPurpose},
+};
+
+/// Verify the Data Integrity proof of an `ldp_vp` presentation, and of each credential in it.
+///
+/// The presentation proof must be an `authentication` proof of the holder, bound to the
+/// authorization request with the `nonce` as its `challenge` and the `client_id` as its `domain`.
+/// Each credential must be an `ldp_vc` credential, see [verify_credential].
+pub async fn verify_presentation(
+    presentation: &Json,
+    nonce: &str,
+    client_id: &str,
+    resolver: &dyn DIDResolver,
+) -> Result<()> {
+    let parsed: Presentation = serde_json::from_value(presentation.clone())
+        .context("unable to parse Data Integrity presentation")?;
+
+    let options = LinkedDataProofOptions {
+        proof_purpose: Some(ProofPurpose::Authentication),
+        challenge: Some(nonce.to_owned()),
+        domain: Some(client_id.to_owned()),
+        ..Default::default()
+    };
+
+    let result = parsed
+        .verify(Some(options), resolver, &mut ContextLoader::default())
+        .await;
+
+    if !result.errors.is_empty() {
+        bail!(
+            "presentation proof could not be verified: {}",
+            result.errors.join(", ")
+        )
+    }
+
+    let credentials = match presentation.get("verifiableCredential") {
+        Some(Json::Array(credentials)) => credentials.iter().collect(),
+        Some(credential) => vec![credential],
+        None => vec![],
+    };
+    for (i, credential) in credentials.into_iter().enumerate() {
+        verify_credential(credential, resolver)
+            .await
+            .context(format!("invalid credential {i}"))?;
+    }
+
+    Ok(())
+}
+
+/// Verify the Data Integrity proof of an `ldp_vc` credential, which must be an `assertionMethod`
+/// proof of its issuer.
+pub async fn verify_credential(credential: &Json, resolver: &dyn DIDResolver) -> Result<()> {
+    let credential: Credential = serde_json::from_value(credential.clone())
+        .context("unable to parse Data Integrity credential")?;
+
+    let options = LinkedDataProofOptions {
+        proof_purpose: Some(ProofPurpose::AssertionMethod),
+        ..Default::default()
+    };
+
+    let result = credential
+        .verify(Some(options), resolver, &mut ContextLoader::default())
+        .await;
+
+    if !result.errors.is_empty() {
+        bail!(
+            "credential proof could not be verified: {}",
+            result.errors.join(", ")
+        )
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use did_method_key::DIDKey;
+    use serde_json::json;
+    use ssi::{
+        did::{DIDMethod, Source},
+        jwk::JWK,
+        vc::{Proof, URI},
+    };
+
+    use super::*;
+
+    /// A key from the test examples, identified by the verification method of its `did:key`.
+    fn key(jwk: &str) -> (String, JWK) {
+        let mut key: JWK = serde_json::from_str(jwk).unwrap();
+        let did = DIDKey.generate(&Source::Key(&key)).unwrap();
+        key.key_id = Some(format!("{did}#{}", did.strip_prefix("did:key:").unwrap()));
+        (did, key)
+    }
+
+    fn proof_options(key: &JWK, proof_purpose: ProofPurpose) -> LinkedDataProofOptions {
+        LinkedDataProofOptions {
+            verification_method: key.key_id.clone().map(URI::String),
+            proof_purpose: Some(proof_purpose),
+            challenge: Some("n-0S6_WzA2Mj8sKq3bT5yA".to_owned()),
+            domain: Some("did:example:verifier".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// A presentation by the subject of a credential issued by the issuer, whose proof is
+    /// generated with `credential_key`.
+    async fn presentation(credential_key: Option<&JWK>) -> Json {
+        let (issuer, _) = key(include_str!("../../../tests/examples/issuer.jwk"));
+        let (holder, holder_key) = key(include_str!("../../../tests/examples/subject.jwk"));
+
+        let mut credential: Credential = serde_json::from_value(json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiableCredential"],
+            "issuer": issuer,
+            "issuanceDate": "2024-01-01T00:00:00Z",
+            "credentialSubject": { "id": holder }
+        }))
+        .unwrap();
+        if let Some(credential_key) = credential_key {
+            let proof: Proof = credential
+                .generate_proof(
+                    credential_key,
+                    &proof_options(credential_key, ProofPurpose::AssertionMethod),
+                    DIDKey.to_resolver(),
+                    &mut ContextLoader::default(),
+                )
+                .await
+                .unwrap();
+            credential.add_proof(proof);
+        }
+
+        let mut presentation: Presentation = serde_json::from_value(json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiablePresentation"],
+            "holder": holder,
+            "verifiableCredential": [credential]
+        }))
+        .unwrap();
+        let proof = presentation
+            .generate_proof(
+                &holder_key,
+                &proof_options(&holder_key, ProofPurpose::Authentication),
+                DIDKey.to_resolver(),
+                &mut ContextLoader::default(),
+            )
+            .await
+            .unwrap();
+        presentation.add_proof(proof);
+        serde_json::to_value(presentation).unwrap()
+    }
+
+    #[tokio::test]
+    async fn valid_presentation() {
+        let (_, issuer_key) = key(include_str!("../../../tests/examples/issuer.jwk"));
+        let presentation = presentation(Some(&issuer_key)).await;
+
+        verify_presentation(
+            &presentation,
+            "n-0S6_WzA2Mj8sKq3bT5yA",
+            "did:example:verifier",
+            DIDKey.to_resolver(),
+        )
+        .await
+        .unwrap();
+
+        verify_presentation(
+            &presentation,
+            "n-0S6_WzA2Mj8sKq3bT5yB",
+            "did:example:verifier",
+            DIDKey.to_resolver(),
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn invalid_credential_proof() {
+        let (_, holder_key) = key(include_str!("../../../tests/examples/subject.jwk"));
+
+        // A credential without a proof, and one that is not signed by its issuer.
+        for credential_key in [None, Some(&holder_key)] {
+            let error = verify_presentation(
+                &presentation(credential_key).await,
+                "n-0S6_WzA2Mj8sKq3bT5yA",
+                "did:example:verifier",
+                DIDKey.to_resolver(),
+            )
+            .await
+            .unwrap_err();
+            assert!(
+                format!("{error:#}").contains("credential proof could not be verified"),
+                "{error:#}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn presentation_without_proof() {
+        let presentation = json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiablePresentation"]
+        });
+
+        verify_presentation(
+            &presentation,
+            "random123",
+            "did:example:verifier",
+            DIDKey.to_resolver(),
+        )
+        .await
+        .unwrap_err();
+    }
+}
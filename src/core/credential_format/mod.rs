@@ -1,3 +1,7 @@
+pub mod ldp;
+pub mod mso_mdoc;
+pub mod sd_jwt;
+
 /// A credential format that can be transmitted using OID4VP.
 pub trait CredentialFormat {
     /// The ID of the credential format.
@@ -15,3 +19,31 @@ pub struct JwtVc;
 impl CredentialFormat for JwtVc {
     const ID: &'static str = "jwt_vc";
 }
+
+/// An SD-JWT VC, with the media type used by earlier drafts.
+pub struct VcSdJwt;
+
+impl CredentialFormat for VcSdJwt {
+    const ID: &'static str = "vc+sd-jwt";
+}
+
+/// An SD-JWT VC.
+pub struct DcSdJwt;
+
+impl CredentialFormat for DcSdJwt {
+    const ID: &'static str = "dc+sd-jwt";
+}
+
+/// A W3C verifiable credential secured with a Data Integrity proof.
+pub struct LdpVc;
+
+impl CredentialFormat for LdpVc {
+    const ID: &'static str = "ldp_vc";
+}
+
+/// A W3C verifiable presentation secured with a Data Integrity proof.
+pub struct LdpVp;
+
+impl CredentialFormat for LdpVp {
+    const ID: &'static str = "ldp_vp";
+}
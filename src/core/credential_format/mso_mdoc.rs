@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use base64::prelude::*;
+use serde_cbor::Value as Cbor;
+use serde_json::{Map, Value as Json};
+use sha2::{Digest, Sha256};
+
+use super::sd_jwt::escape;
+
+/// An ISO 18013-5 `DeviceResponse`, as presented for the `mso_mdoc` format.
+///
+/// Only the issuer-signed data elements are decoded, and each is checked against its digest in the
+/// mobile security object (MSO). The issuer signature of the MSO (`issuerAuth`) and the device
+/// signature are not verified, so the data elements are not authenticated. A decoded document is
+/// only suitable for matching credentials that a wallet holds, such as with
+/// [DcqlQuery::select](crate::core::dcql_query::DcqlQuery::select). mdoc presentations in an
+/// authorization response are rejected as unverifiable by
+/// [PresentationDefinition::validate_authorization_response](crate::presentation_exchange::PresentationDefinition::validate_authorization_response),
+/// [DcqlQuery::validate_vp_token](crate::core::dcql_query::DcqlQuery::validate_vp_token) and the
+/// [Verifier](crate::verifier::Verifier).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceResponse {
+    pub version: String,
+    pub documents: Vec<Document>,
+    pub status: u64,
+}
+
+/// A document of a [DeviceResponse].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    /// The document type, such as `org.iso.18013.5.1.mDL`.
+    pub doc_type: String,
+    /// The issuer-signed data elements, by namespace and element identifier.
+    pub namespaces: BTreeMap<String, BTreeMap<String, Json>>,
+}
+
+impl DeviceResponse {
+    /// Decode a base64url-encoded `DeviceResponse`, as found in a `vp_token`.
+    pub fn from_base64url(device_response: &str) -> Result<Self> {
+        let bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(device_response)
+            .context("DeviceResponse was not valid base64url")?;
+        Self::from_cbor(&bytes)
+    }
+
+    /// Decode a CBOR-encoded `DeviceResponse`.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        let device_response: Cbor =
+            serde_cbor::from_slice(bytes).context("DeviceResponse was not valid CBOR")?;
+
+        let version = match get(&device_response, "version") {
+            Some(Cbor::Text(version)) => version.clone(),
+            _ => bail!("DeviceResponse 'version' was missing or not a string"),
+        };
+
+        let status = match get(&device_response, "status") {
+            Some(Cbor::Integer(status)) => u64::try_from(*status)
+                .context("DeviceResponse 'status' was not a valid status code")?,
+            _ => bail!("DeviceResponse 'status' was missing or not an integer"),
+        };
+
+        let documents = match get(&device_response, "documents") {
+            None => vec![],
+            Some(Cbor::Array(documents)) => documents
+                .iter()
+                .enumerate()
+                .map(|(i, document)| {
+                    Document::from_cbor(document).context(format!("invalid document {i}"))
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => bail!("DeviceResponse 'documents' was not an array"),
+        };
+
+        Ok(Self {
+            version,
+            documents,
+            status,
+        })
+    }
+}
+
+impl Document {
+    fn from_cbor(document: &Cbor) -> Result<Self> {
+        let doc_type = match get(document, "docType") {
+            Some(Cbor::Text(doc_type)) => doc_type.clone(),
+            _ => bail!("'docType' was missing or not a string"),
+        };
+
+        let issuer_signed = get(document, "issuerSigned").context("'issuerSigned' was missing")?;
+        let mso = decode_mso(issuer_signed).context("invalid 'issuerAuth'")?;
+
+        match get(&mso, "docType") {
+            Some(Cbor::Text(mso_doc_type)) if *mso_doc_type == doc_type => (),
+            _ => bail!("MSO 'docType' does not match the document type '{doc_type}'"),
+        }
+        match get(&mso, "digestAlgorithm") {
+            Some(Cbor::Text(algorithm)) if algorithm == "SHA-256" => (),
+            _ => bail!("MSO 'digestAlgorithm' was missing or not supported"),
+        }
+        let value_digests = get(&mso, "valueDigests").context("MSO 'valueDigests' was missing")?;
+
+        let mut namespaces = BTreeMap::new();
+        match get(issuer_signed, "nameSpaces") {
+            None => (),
+            Some(Cbor::Map(issuer_namespaces)) => {
+                for (namespace, items) in issuer_namespaces {
+                    let Cbor::Text(namespace) = namespace else {
+                        bail!("namespace was not a string")
+                    };
+                    let Cbor::Array(items) = items else {
+                        bail!("namespace '{namespace}' was not an array")
+                    };
+
+                    let digests = get(value_digests, namespace)
+                        .context(format!("MSO has no digests for namespace '{namespace}'"))?;
+
+                    let elements = items
+                        .iter()
+                        .map(|item| decode_issuer_signed_item(item, digests))
+                        .collect::<Result<_>>()
+                        .context(format!("invalid item in namespace '{namespace}'"))?;
+                    namespaces.insert(namespace.clone(), elements);
+                }
+            }
+            Some(_) => bail!("'nameSpaces' was not a map"),
+        }
+
+        Ok(Self {
+            doc_type,
+            namespaces,
+        })
+    }
+
+    /// The issuer-signed data elements as a JSON object of namespaces, for matching with paths
+    /// such as `$['org.iso.18013.5.1']['family_name']`.
+    pub fn claims(&self) -> Json {
+        Json::Object(
+            self.namespaces
+                .iter()
+                .map(|(namespace, elements)| {
+                    let elements: Map<String, Json> = elements
+                        .iter()
+                        .map(|(identifier, value)| (identifier.clone(), value.clone()))
+                        .collect();
+                    (namespace.clone(), Json::Object(elements))
+                })
+                .collect(),
+        )
+    }
+
+    /// JSON pointers to each data element in [Document::claims], all of which are selectively
+    /// disclosed.
+    pub fn claim_pointers(&self) -> Vec<String> {
+        self.namespaces
+            .iter()
+            .flat_map(|(namespace, elements)| {
+                elements
+                    .keys()
+                    .map(move |identifier| format!("/{}/{}", escape(namespace), escape(identifier)))
+            })
+            .collect()
+    }
+}
+
+/// Decode the `MobileSecurityObject` from the `issuerAuth` COSE_Sign1 of an `IssuerSigned`.
+fn decode_mso(issuer_signed: &Cbor) -> Result<Cbor> {
+    let Some(Cbor::Array(issuer_auth)) = get(issuer_signed, "issuerAuth") else {
+        bail!("'issuerAuth' was missing or not a COSE_Sign1")
+    };
+    let [_protected, _unprotected, Cbor::Bytes(payload), _signature] = issuer_auth.as_slice()
+    else {
+        bail!("'issuerAuth' was not a COSE_Sign1 with a payload")
+    };
+
+    // The payload is `MobileSecurityObjectBytes`, embedded CBOR tagged 24.
+    let mso_bytes: Cbor =
+        serde_cbor::from_slice(payload).context("'issuerAuth' payload was not valid CBOR")?;
+    let mso_bytes = match mso_bytes {
+        Cbor::Tag(24, bytes) => *bytes,
+        bytes => bytes,
+    };
+    let Cbor::Bytes(mso_bytes) = mso_bytes else {
+        bail!("MobileSecurityObjectBytes was not a byte string")
+    };
+
+    serde_cbor::from_slice(&mso_bytes).context("MobileSecurityObject was not valid CBOR")
+}
+
+/// The SHA-256 digest of an `IssuerSignedItemBytes`, i.e. the item bytes tagged 24.
+fn issuer_signed_item_digest(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    hasher.update([0xd8, 0x18]);
+    hasher.update(serde_cbor::to_vec(&Cbor::Bytes(bytes.to_vec()))?);
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Decode an `IssuerSignedItemBytes` into its element identifier and value, after checking it
+/// against its digest in the `valueDigests` of its namespace.
+fn decode_issuer_signed_item(item_bytes: &Cbor, digests: &Cbor) -> Result<(String, Json)> {
+    // The item is embedded CBOR, tagged 24 if tags are preserved.
+    let bytes = match item_bytes {
+        Cbor::Tag(24, bytes) => bytes.as_ref(),
+        bytes => bytes,
+    };
+    let Cbor::Bytes(bytes) = bytes else {
+        bail!("IssuerSignedItemBytes was not a byte string")
+    };
+
+    let item: Cbor =
+        serde_cbor::from_slice(bytes).context("IssuerSignedItem was not valid CBOR")?;
+
+    let identifier = match get(&item, "elementIdentifier") {
+        Some(Cbor::Text(identifier)) => identifier.clone(),
+        _ => bail!("'elementIdentifier' was missing or not a string"),
+    };
+
+    let Some(Cbor::Integer(digest_id)) = get(&item, "digestID") else {
+        bail!("'digestID' of '{identifier}' was missing or not an integer")
+    };
+    let Cbor::Map(digests) = digests else {
+        bail!("MSO digests were not a map")
+    };
+    match digests.get(&Cbor::Integer(*digest_id)) {
+        Some(Cbor::Bytes(digest)) if *digest == issuer_signed_item_digest(bytes)? => (),
+        Some(_) => bail!("'{identifier}' does not match its digest in the MSO"),
+        None => bail!("MSO has no digest for '{identifier}'"),
+    }
+
+    let value = get(&item, "elementValue").context("'elementValue' was missing")?;
+
+    Ok((identifier, cbor_to_json(value)?))
+}
+
+fn get<'a>(map: &'a Cbor, key: &str) -> Option<&'a Cbor> {
+    match map {
+        Cbor::Map(map) => map.get(&Cbor::Text(key.to_owned())),
+        _ => None,
+    }
+}
+
+/// Convert a CBOR data element value to JSON.
+///
+/// Byte strings are base64url-encoded, and tags are discarded.
+fn cbor_to_json(value: &Cbor) -> Result<Json> {
+    Ok(match value {
+        Cbor::Null => Json::Null,
+        Cbor::Bool(b) => Json::Bool(*b),
+        Cbor::Integer(i) => match i64::try_from(*i) {
+            Ok(i) => i.into(),
+            Err(_) => u64::try_from(*i).context("integer is out of range")?.into(),
+        },
+        Cbor::Float(f) => serde_json::Number::from_f64(*f)
+            .map(Json::Number)
+            .context("float is not finite")?,
+        Cbor::Bytes(bytes) => Json::String(BASE64_URL_SAFE_NO_PAD.encode(bytes)),
+        Cbor::Text(text) => Json::String(text.clone()),
+        Cbor::Array(array) => Json::Array(array.iter().map(cbor_to_json).collect::<Result<_>>()?),
+        Cbor::Map(map) => Json::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let Cbor::Text(key) = key else {
+                        bail!("map key was not a string")
+                    };
+                    Ok((key.clone(), cbor_to_json(value)?))
+                })
+                .collect::<Result<_>>()?,
+        ),
+        Cbor::Tag(_, value) => cbor_to_json(value)?,
+        _ => bail!("unsupported CBOR value"),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use serde_json::json;
+
+    use crate::core::dcql_query::{DcqlQuery, HeldCredential};
+
+    use super::*;
+
+    fn text(text: &str) -> Cbor {
+        Cbor::Text(text.to_owned())
+    }
+
+    fn issuer_signed_item(digest_id: i128, identifier: &str, value: Cbor) -> Vec<u8> {
+        let item = Cbor::Map(BTreeMap::from([
+            (text("digestID"), Cbor::Integer(digest_id)),
+            (text("random"), Cbor::Bytes(vec![0; 16])),
+            (text("elementIdentifier"), text(identifier)),
+            (text("elementValue"), value),
+        ]));
+        serde_cbor::to_vec(&item).unwrap()
+    }
+
+    fn tagged(bytes: Vec<u8>) -> Cbor {
+        Cbor::Tag(24, Box::new(Cbor::Bytes(bytes)))
+    }
+
+    /// A base64url-encoded `DeviceResponse` containing an mDL, with an unsigned `issuerAuth`.
+    pub(crate) fn device_response() -> String {
+        device_response_with(|_| ())
+    }
+
+    /// A base64url-encoded `DeviceResponse` containing an mDL, with the item bytes modified after
+    /// their digests are taken.
+    fn device_response_with(tamper: impl FnOnce(&mut Vec<Vec<u8>>)) -> String {
+        let mut items = vec![
+            issuer_signed_item(0, "family_name", text("Doe")),
+            issuer_signed_item(1, "age_over_18", Cbor::Bool(true)),
+        ];
+
+        let digests = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                (
+                    Cbor::Integer(i as i128),
+                    Cbor::Bytes(issuer_signed_item_digest(item).unwrap()),
+                )
+            })
+            .collect();
+        let mso = Cbor::Map(BTreeMap::from([
+            (text("version"), text("1.0")),
+            (text("digestAlgorithm"), text("SHA-256")),
+            (text("docType"), text("org.iso.18013.5.1.mDL")),
+            (
+                text("valueDigests"),
+                Cbor::Map(BTreeMap::from([(
+                    text("org.iso.18013.5.1"),
+                    Cbor::Map(digests),
+                )])),
+            ),
+        ]));
+        let payload = serde_cbor::to_vec(&tagged(serde_cbor::to_vec(&mso).unwrap())).unwrap();
+        let issuer_auth = Cbor::Array(vec![
+            Cbor::Bytes(vec![]),
+            Cbor::Map(BTreeMap::new()),
+            Cbor::Bytes(payload),
+            Cbor::Bytes(vec![]),
+        ]);
+
+        tamper(&mut items);
+
+        let document = Cbor::Map(BTreeMap::from([
+            (text("docType"), text("org.iso.18013.5.1.mDL")),
+            (
+                text("issuerSigned"),
+                Cbor::Map(BTreeMap::from([
+                    (
+                        text("nameSpaces"),
+                        Cbor::Map(BTreeMap::from([(
+                            text("org.iso.18013.5.1"),
+                            Cbor::Array(items.into_iter().map(tagged).collect()),
+                        )])),
+                    ),
+                    (text("issuerAuth"), issuer_auth),
+                ])),
+            ),
+            (text("deviceSigned"), Cbor::Map(BTreeMap::new())),
+        ]));
+
+        let device_response = Cbor::Map(BTreeMap::from([
+            (text("version"), text("1.0")),
+            (text("documents"), Cbor::Array(vec![document])),
+            (text("status"), Cbor::Integer(0)),
+        ]));
+
+        BASE64_URL_SAFE_NO_PAD.encode(serde_cbor::to_vec(&device_response).unwrap())
+    }
+
+    #[test]
+    fn decode_device_response() {
+        let device_response = DeviceResponse::from_base64url(&device_response()).unwrap();
+        assert_eq!(device_response.version, "1.0");
+        assert_eq!(device_response.status, 0);
+
+        let [document] = device_response.documents.as_slice() else {
+            panic!("expected one document")
+        };
+        assert_eq!(document.doc_type, "org.iso.18013.5.1.mDL");
+        assert_eq!(
+            document.claims(),
+            json!({
+                "org.iso.18013.5.1": {
+                    "family_name": "Doe",
+                    "age_over_18": true
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn issuer_signed_item_digests() {
+        let substituted = device_response_with(|items| {
+            items[0] = issuer_signed_item(0, "family_name", text("Roe"));
+        });
+        let error = DeviceResponse::from_base64url(&substituted).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("'family_name' does not match its digest in the MSO"),
+            "{error:#}"
+        );
+
+        let added = device_response_with(|items| {
+            items.push(issuer_signed_item(2, "given_name", text("John")));
+        });
+        let error = DeviceResponse::from_base64url(&added).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("MSO has no digest for 'given_name'"),
+            "{error:#}"
+        );
+    }
+
+    #[test]
+    fn dcql_namespaced_element() {
+        let device_response = DeviceResponse::from_base64url(&device_response()).unwrap();
+        let document = &device_response.documents[0];
+
+        let credential = HeldCredential {
+            format: "mso_mdoc".into(),
+            doctype: Some(document.doc_type.clone()),
+            vct: None,
+            claims: document.claims(),
+        };
+
+        let query = |element: &str| {
+            DcqlQuery::from_json(
+                &json!({
+                    "credentials": [
+                        {
+                            "id": "mdl",
+                            "format": "mso_mdoc",
+                            "meta": { "doctype_value": "org.iso.18013.5.1.mDL" },
+                            "claims": [
+                                { "path": ["org.iso.18013.5.1", element] }
+                            ]
+                        }
+                    ]
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+
+        let credentials = [credential];
+        assert_eq!(query("family_name").select(&credentials).len(), 1);
+        assert!(query("given_name").select(&credentials).is_empty());
+    }
+}
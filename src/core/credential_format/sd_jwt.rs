@@ -0,0 +1,684 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use base64::prelude::*;
+use serde_json::{json, Map, Value as Json};
+use sha2::{Digest, Sha256};
+use ssi::{
+    did_resolve::{resolve_key, DIDResolver},
+    jwk::JWK,
+};
+
+use crate::{core::response::id_token::header_kid, presentation_exchange::InputDescriptor};
+
+/// The only supported `_sd_alg`.
+const SD_ALG: &str = "sha-256";
+
+/// The `typ` header of a key binding JWT.
+pub const KEY_BINDING_JWT_TYP: &str = "kb+jwt";
+
+/// How far the `iat` of a key binding JWT may be from the current time, in seconds.
+const KEY_BINDING_JWT_LEEWAY: u64 = 300;
+
+/// An SD-JWT presentation, in the form `<issuer-jwt>~<disclosure>~...~<key-binding-jwt>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdJwtPresentation {
+    /// The JWT signed by the issuer, containing digests of the disclosable claims.
+    pub issuer_jwt: String,
+    /// The disclosures selected by the holder.
+    pub disclosures: Vec<String>,
+    /// The key binding JWT, if any.
+    pub key_binding_jwt: Option<String>,
+}
+
+/// A signature of an SD-JWT presentation, of the issuer-signed JWT or the key binding JWT, is not
+/// valid.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidSignature(pub String);
+
+enum Disclosure {
+    Property(String, Json),
+    ArrayElement(Json),
+}
+
+impl SdJwtPresentation {
+    /// Split an SD-JWT presentation into its parts.
+    pub fn parse(presentation: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = presentation.split('~').collect();
+        if parts.len() < 2 {
+            bail!("SD-JWT presentation must contain a '~' separator")
+        }
+
+        let key_binding_jwt = parts
+            .pop()
+            .filter(|key_binding_jwt| !key_binding_jwt.is_empty())
+            .map(str::to_owned);
+        let issuer_jwt = parts.remove(0).to_owned();
+
+        Ok(Self {
+            issuer_jwt,
+            disclosures: parts.into_iter().map(str::to_owned).collect(),
+            key_binding_jwt,
+        })
+    }
+
+    /// Decode the claims of the issuer-signed JWT, replacing digests with the disclosed claims.
+    ///
+    /// Each disclosure must be referenced by a digest in the issuer-signed claims, and digests
+    /// that were not disclosed are removed. Signatures are not verified.
+    pub fn disclosed_claims(&self) -> Result<Json> {
+        self.disclosed_claims_and_pointers()
+            .map(|(claims, _)| claims)
+    }
+
+    /// Like [SdJwtPresentation::disclosed_claims], also returning JSON pointers to the claims
+    /// that were selectively disclosed.
+    pub fn disclosed_claims_and_pointers(&self) -> Result<(Json, Vec<String>)> {
+        let (claims, disclosed) = self.disclose()?;
+        Ok((
+            claims,
+            disclosed.into_iter().map(|(pointer, _)| pointer).collect(),
+        ))
+    }
+
+    /// Select the disclosures to present for an input descriptor: those of the claims matched by
+    /// its constraint fields, and of the claims that contain or are contained by them.
+    ///
+    /// The result satisfies a `limit_disclosure` of `required`. Fails if the disclosed claims do
+    /// not satisfy the constraint fields. The key binding JWT is removed, as it is bound to the
+    /// disclosures that are presented: the holder must sign a new one over the result, see
+    /// [SdJwtPresentation::key_binding_claims].
+    pub fn select_disclosures(&self, input_descriptor: &InputDescriptor) -> Result<Self> {
+        let (claims, disclosed) = self.disclose()?;
+        let requested = input_descriptor.validate_constraints_fields(&claims)?;
+
+        let covers =
+            |outer: &str, inner: &str| inner == outer || inner.starts_with(&format!("{outer}/"));
+        let digests: Vec<String> = disclosed
+            .into_iter()
+            .filter(|(pointer, _)| {
+                requested
+                    .iter()
+                    .any(|requested| covers(pointer, requested) || covers(requested, pointer))
+            })
+            .map(|(_, digest)| digest)
+            .collect();
+
+        Ok(Self {
+            issuer_jwt: self.issuer_jwt.clone(),
+            disclosures: self
+                .disclosures
+                .iter()
+                .filter(|disclosure| digests.contains(&disclosure_digest(disclosure)))
+                .cloned()
+                .collect(),
+            key_binding_jwt: None,
+        })
+    }
+
+    /// The key that the key binding JWT must be signed with: the `jwk` of the `cnf` claim of the
+    /// issuer-signed JWT, if it has one.
+    ///
+    /// The signature of the issuer-signed JWT is not verified, so this is only suitable for a
+    /// holder's own credential. A verifier uses [SdJwtPresentation::verify_key_binding] instead.
+    pub fn holder_key(&self) -> Result<Option<JWK>> {
+        let claims: Json = ssi::jwt::decode_unverified(&self.issuer_jwt)
+            .context("unable to decode issuer-signed JWT")?;
+        cnf_key(&claims)
+    }
+
+    /// The key of the issuer, resolved with the `resolver` from the `kid` header of the
+    /// issuer-signed JWT, which must be a verification method of the DID in its `iss` claim.
+    ///
+    /// Issuers that are not identified by a DID are not supported.
+    pub async fn issuer_key(&self, resolver: &dyn DIDResolver) -> Result<JWK> {
+        let claims: Json = ssi::jwt::decode_unverified(&self.issuer_jwt)
+            .context("unable to decode issuer-signed JWT")?;
+        let Some(Json::String(issuer)) = claims.get("iss") else {
+            bail!("issuer-signed JWT 'iss' was missing or not a string")
+        };
+
+        let kid = header_kid(&self.issuer_jwt)?;
+        let (did, _f) = kid.split_once('#').context(format!(
+            "expected a DID verification method in 'kid' header, received '{kid}'"
+        ))?;
+        if did != issuer {
+            bail!("DID from 'kid' ({did}) is not the issuer ({issuer})")
+        }
+
+        resolve_key(&kid, resolver)
+            .await
+            .context("unable to resolve verification method from 'kid' header")
+    }
+
+    /// Verify the issuer-signed JWT with the key of [SdJwtPresentation::issuer_key], resolved
+    /// with the `resolver`, and then the key binding JWT, returning its claims, as
+    /// [SdJwtPresentation::verify_key_binding] does.
+    pub async fn verify(&self, resolver: &dyn DIDResolver) -> Result<Json> {
+        let issuer_key = self
+            .issuer_key(resolver)
+            .await
+            .map_err(|e| InvalidSignature(format!("{e:#}")))?;
+        self.verify_key_binding(&issuer_key)
+    }
+
+    /// The `sd_hash` that binds a key binding JWT to this presentation: the base64url-encoded
+    /// SHA-256 digest of the issuer-signed JWT and the disclosures, each followed by `~`.
+    pub fn sd_hash(&self) -> String {
+        let mut presentation = format!("{}~", self.issuer_jwt);
+        for disclosure in &self.disclosures {
+            presentation.push_str(disclosure);
+            presentation.push('~');
+        }
+        BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(presentation.as_bytes()))
+    }
+
+    /// The claims of a key binding JWT over this presentation, issued now for the `nonce` and
+    /// `audience` of an authorization request. It must be signed by the holder with the key of
+    /// [SdJwtPresentation::holder_key] and a `typ` of [KEY_BINDING_JWT_TYP].
+    pub fn key_binding_claims(&self, nonce: &str, audience: &str) -> Json {
+        json!({
+            "nonce": nonce,
+            "aud": audience,
+            "iat": now(),
+            "sd_hash": self.sd_hash()
+        })
+    }
+
+    /// Verify the signature of the issuer-signed JWT with the `issuer_key`, and then the key
+    /// binding JWT against the `cnf` key of the issuer-signed JWT, returning its claims.
+    ///
+    /// Checks the signature and `typ` of the key binding JWT, that the `sd_hash` matches the
+    /// issuer-signed JWT and disclosures presented, and that the `iat` is within five minutes of
+    /// now. Fails with [InvalidSignature] if either signature is not valid. The `nonce` and `aud`
+    /// claims are left for the caller to check against the authorization request.
+    pub fn verify_key_binding(&self, issuer_key: &JWK) -> Result<Json> {
+        let issuer_claims: Json =
+            ssi::jwt::decode_verify(&self.issuer_jwt, issuer_key).map_err(|e| {
+                InvalidSignature(format!("issuer-signed JWT signature is not valid: {e}"))
+            })?;
+        let key = cnf_key(&issuer_claims)?
+            .context("issuer-signed JWT has no 'cnf' key to bind the presentation to")?;
+        let key_binding_jwt = self
+            .key_binding_jwt
+            .as_deref()
+            .context("presentation has no key binding JWT")?;
+
+        let (header, _, _) = ssi::jws::split_jws(key_binding_jwt)?;
+        let header: Map<String, Json> = BASE64_URL_SAFE_NO_PAD
+            .decode(header)
+            .ok()
+            .and_then(|header| serde_json::from_slice(&header).ok())
+            .context("key binding JWT header was not valid base64url-encoded JSON")?;
+        if header.get("typ").and_then(Json::as_str) != Some(KEY_BINDING_JWT_TYP) {
+            bail!("key binding JWT 'typ' is not '{KEY_BINDING_JWT_TYP}'")
+        }
+
+        let claims: Json = ssi::jwt::decode_verify(key_binding_jwt, &key).map_err(|e| {
+            InvalidSignature(format!(
+                "key binding JWT signature is not valid for the 'cnf' key: {e}"
+            ))
+        })?;
+
+        if claims.get("sd_hash").and_then(Json::as_str) != Some(&self.sd_hash()) {
+            bail!("key binding JWT 'sd_hash' does not match the presentation")
+        }
+
+        let iat = claims
+            .get("iat")
+            .and_then(Json::as_u64)
+            .context("key binding JWT has no 'iat'")?;
+        if iat.abs_diff(now()) > KEY_BINDING_JWT_LEEWAY {
+            bail!("key binding JWT 'iat' is more than {KEY_BINDING_JWT_LEEWAY} seconds from now")
+        }
+
+        Ok(claims)
+    }
+
+    /// Decode the claims of the issuer-signed JWT with the disclosures, also returning the JSON
+    /// pointer and digest of each selectively disclosed claim.
+    fn disclose(&self) -> Result<(Json, Vec<(String, String)>)> {
+        let mut claims: Json = ssi::jwt::decode_unverified(&self.issuer_jwt)
+            .context("unable to decode issuer-signed JWT")?;
+
+        let Json::Object(object) = &mut claims else {
+            bail!("issuer-signed JWT claims were not a JSON object")
+        };
+
+        match object.remove("_sd_alg") {
+            None => (),
+            Some(Json::String(alg)) if alg == SD_ALG => (),
+            Some(alg) => bail!("unsupported '_sd_alg' {alg}"),
+        }
+
+        let mut disclosures = HashMap::new();
+        for disclosure in &self.disclosures {
+            let digest = disclosure_digest(disclosure);
+            if disclosures
+                .insert(digest.clone(), decode_disclosure(disclosure)?)
+                .is_some()
+            {
+                bail!("disclosure with digest '{digest}' was presented more than once")
+            }
+        }
+
+        let mut disclosed = vec![];
+        disclose(
+            &mut claims,
+            "",
+            &mut disclosures,
+            &mut disclosed,
+            &mut HashSet::new(),
+        )?;
+
+        if let Some(digest) = disclosures.keys().next() {
+            bail!("disclosure with digest '{digest}' is not referenced by the issuer-signed JWT")
+        }
+
+        Ok((claims, disclosed))
+    }
+}
+
+/// Serialize the presentation in the form `<issuer-jwt>~<disclosure>~...~<key-binding-jwt>`.
+impl fmt::Display for SdJwtPresentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}~", self.issuer_jwt)?;
+        for disclosure in &self.disclosures {
+            write!(f, "{disclosure}~")?;
+        }
+        if let Some(key_binding_jwt) = &self.key_binding_jwt {
+            write!(f, "{key_binding_jwt}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The base64url-encoded SHA-256 digest of a disclosure, as referenced in the issuer-signed JWT.
+pub fn disclosure_digest(disclosure: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(disclosure.as_bytes()))
+}
+
+fn decode_disclosure(disclosure: &str) -> Result<Disclosure> {
+    let bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(disclosure)
+        .context("disclosure was not valid base64url")?;
+
+    let array: Vec<Json> =
+        serde_json::from_slice(&bytes).context("disclosure was not a JSON array")?;
+
+    match array.as_slice() {
+        [_salt, Json::String(name), value] => Ok(Disclosure::Property(name.clone(), value.clone())),
+        [_salt, value] => Ok(Disclosure::ArrayElement(value.clone())),
+        _ => bail!("disclosure must have two or three elements"),
+    }
+}
+
+/// Replace the digests in `value`, at the JSON `pointer`, with the matching disclosures, removing
+/// them from `disclosures` and recording where they were disclosed, with their digest, in
+/// `pointers`. Each digest, whether disclosed or not, is recorded in `seen`, as a digest must
+/// not appear more than once in the issuer-signed JWT.
+fn disclose(
+    value: &mut Json,
+    pointer: &str,
+    disclosures: &mut HashMap<String, Disclosure>,
+    pointers: &mut Vec<(String, String)>,
+    seen: &mut HashSet<String>,
+) -> Result<()> {
+    match value {
+        Json::Object(object) => {
+            if let Some(digests) = object.remove("_sd") {
+                let Json::Array(digests) = digests else {
+                    bail!("'_sd' was not an array")
+                };
+
+                for digest in digests {
+                    let Json::String(digest) = digest else {
+                        bail!("'_sd' digest was not a string")
+                    };
+                    if !seen.insert(digest.clone()) {
+                        bail!("digest '{digest}' appears more than once in the issuer-signed JWT")
+                    }
+
+                    match disclosures.remove(&digest) {
+                        Some(Disclosure::Property(name, value)) => {
+                            if name == "_sd" || name == "..." || object.contains_key(&name) {
+                                bail!("disclosed claim name '{name}' is not allowed")
+                            }
+                            pointers.push((format!("{pointer}/{}", escape(&name)), digest));
+                            object.insert(name, value);
+                        }
+                        Some(Disclosure::ArrayElement(_)) => {
+                            bail!("array element disclosure '{digest}' was referenced by '_sd'")
+                        }
+                        None => (),
+                    }
+                }
+            }
+
+            for (name, value) in object.iter_mut() {
+                disclose(
+                    value,
+                    &format!("{pointer}/{}", escape(name)),
+                    disclosures,
+                    pointers,
+                    seen,
+                )?;
+            }
+        }
+        Json::Array(array) => {
+            for mut element in std::mem::take(array) {
+                let digest = match &element {
+                    Json::Object(object) if object.len() == 1 => {
+                        object.get("...").and_then(Json::as_str).map(str::to_owned)
+                    }
+                    _ => None,
+                };
+
+                let element_pointer = format!("{pointer}/{}", array.len());
+
+                let Some(digest) = digest else {
+                    disclose(&mut element, &element_pointer, disclosures, pointers, seen)?;
+                    array.push(element);
+                    continue;
+                };
+                if !seen.insert(digest.clone()) {
+                    bail!("digest '{digest}' appears more than once in the issuer-signed JWT")
+                }
+
+                match disclosures.remove(&digest) {
+                    Some(Disclosure::ArrayElement(mut value)) => {
+                        disclose(&mut value, &element_pointer, disclosures, pointers, seen)?;
+                        pointers.push((element_pointer, digest));
+                        array.push(value);
+                    }
+                    Some(Disclosure::Property(..)) => {
+                        bail!("property disclosure '{digest}' was referenced by an array element")
+                    }
+                    None => (),
+                }
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// The `jwk` of the `cnf` claim of issuer-signed JWT claims, if it has one.
+fn cnf_key(claims: &Json) -> Result<Option<JWK>> {
+    let Some(cnf) = claims.get("cnf") else {
+        return Ok(None);
+    };
+    let jwk = cnf.get("jwk").context("'cnf' claim has no 'jwk'")?;
+    serde_json::from_value(jwk.clone())
+        .map(Some)
+        .context("'cnf' claim has an invalid 'jwk'")
+}
+
+/// Escape a JSON pointer reference token.
+pub(crate) fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn encode_disclosure(disclosure: Json) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(disclosure.to_string())
+    }
+
+    /// Sign key binding JWT claims with `key`.
+    pub(crate) fn sign_key_binding(claims: &Json, key: &JWK) -> String {
+        let header = json!({ "alg": "ES256", "typ": KEY_BINDING_JWT_TYP });
+        let signing_input = format!(
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(header.to_string()),
+            BASE64_URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature =
+            ssi::jws::sign_bytes(ssi::jwk::Algorithm::ES256, signing_input.as_bytes(), key)
+                .unwrap();
+        format!(
+            "{signing_input}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    #[test]
+    fn disclosed_claims() {
+        let given_name = encode_disclosure(json!(["salt1", "given_name", "Alice"]));
+        let family_name = encode_disclosure(json!(["salt2", "family_name", "Smith"]));
+        let nationality = encode_disclosure(json!(["salt3", "DE"]));
+
+        let issuer_jwt = ssi::jwt::encode_unsigned(&json!({
+            "iss": "https://issuer.example.com",
+            "vct": "https://credentials.example.com/identity_credential",
+            "_sd_alg": "sha-256",
+            "_sd": [disclosure_digest(&given_name), disclosure_digest(&family_name)],
+            "nationalities": [{ "...": disclosure_digest(&nationality) }, "FR"]
+        }))
+        .unwrap();
+
+        let presentation =
+            SdJwtPresentation::parse(&format!("{issuer_jwt}~{given_name}~{nationality}~")).unwrap();
+        assert_eq!(presentation.disclosures.len(), 2);
+        assert_eq!(presentation.key_binding_jwt, None);
+
+        let (_, pointers) = presentation.disclosed_claims_and_pointers().unwrap();
+        assert_eq!(pointers, ["/given_name", "/nationalities/0"]);
+
+        assert_eq!(
+            presentation.disclosed_claims().unwrap(),
+            json!({
+                "iss": "https://issuer.example.com",
+                "vct": "https://credentials.example.com/identity_credential",
+                "given_name": "Alice",
+                "nationalities": ["DE", "FR"]
+            })
+        );
+    }
+
+    #[test]
+    fn select_disclosures() {
+        let disclosures: Vec<String> = [
+            ("given_name", json!("Alice")),
+            ("family_name", json!("Smith")),
+            ("birthdate", json!("1990-01-01")),
+            ("email", json!("alice@example.com")),
+            ("age_over_18", json!(true)),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, value))| encode_disclosure(json!([format!("salt{i}"), name, value])))
+        .collect();
+
+        let issuer_jwt = ssi::jwt::encode_unsigned(&json!({
+            "iss": "https://issuer.example.com",
+            "_sd": disclosures.iter().map(|d| disclosure_digest(d)).collect::<Vec<_>>()
+        }))
+        .unwrap();
+        let credential =
+            SdJwtPresentation::parse(&format!("{issuer_jwt}~{}~", disclosures.join("~"))).unwrap();
+
+        let input_descriptor: InputDescriptor = serde_json::from_value(json!({
+            "id": "pid",
+            "constraints": {
+                "limit_disclosure": "required",
+                "fields": [
+                    { "path": ["$.family_name"] },
+                    { "path": ["$.age_over_18"], "filter": { "const": true } }
+                ]
+            }
+        }))
+        .unwrap();
+
+        let presentation = credential.select_disclosures(&input_descriptor).unwrap();
+        assert_eq!(
+            presentation.disclosures,
+            [disclosures[1].clone(), disclosures[4].clone()]
+        );
+
+        let presentation = SdJwtPresentation::parse(&presentation.to_string()).unwrap();
+        assert_eq!(
+            presentation.disclosed_claims().unwrap(),
+            json!({
+                "iss": "https://issuer.example.com",
+                "family_name": "Smith",
+                "age_over_18": true
+            })
+        );
+    }
+
+    #[test]
+    fn key_binding() {
+        let key: JWK =
+            serde_json::from_str(include_str!("../../../tests/examples/subject.jwk")).unwrap();
+        let issuer_key: JWK =
+            serde_json::from_str(include_str!("../../../tests/examples/issuer.jwk")).unwrap();
+        let given_name = encode_disclosure(json!(["salt1", "given_name", "Alice"]));
+        let family_name = encode_disclosure(json!(["salt2", "family_name", "Smith"]));
+        let issuer_jwt = ssi::jwt::encode_sign(
+            ssi::jwk::Algorithm::ES256,
+            &json!({
+                "iss": "https://issuer.example.com",
+                "_sd": [disclosure_digest(&given_name), disclosure_digest(&family_name)],
+                "cnf": { "jwk": key.to_public() }
+            }),
+            &issuer_key,
+        )
+        .unwrap();
+        let issuer_key = issuer_key.to_public();
+
+        let mut presentation =
+            SdJwtPresentation::parse(&format!("{issuer_jwt}~{given_name}~{family_name}~")).unwrap();
+        assert_eq!(presentation.holder_key().unwrap(), Some(key.to_public()));
+        let error = presentation.verify_key_binding(&issuer_key).unwrap_err();
+        assert!(
+            error.to_string().contains("no key binding JWT"),
+            "{error:#}"
+        );
+
+        let claims = presentation.key_binding_claims("nonce", "did:example:verifier");
+        presentation.key_binding_jwt = Some(sign_key_binding(&claims, &key));
+        let presentation = SdJwtPresentation::parse(&presentation.to_string()).unwrap();
+        assert_eq!(
+            presentation.verify_key_binding(&issuer_key).unwrap(),
+            claims
+        );
+
+        // The issuer-signed JWT must be signed with the issuer key.
+        let error = presentation
+            .verify_key_binding(&key.to_public())
+            .unwrap_err();
+        assert!(error.is::<InvalidSignature>(), "{error:#}");
+
+        // Removing a disclosure changes the `sd_hash`.
+        let mut stripped = presentation.clone();
+        stripped.disclosures.pop();
+        let error = stripped.verify_key_binding(&issuer_key).unwrap_err();
+        assert!(error.to_string().contains("'sd_hash'"), "{error:#}");
+
+        // The key binding JWT must be signed with the `cnf` key.
+        let other: JWK =
+            serde_json::from_str(include_str!("../../../tests/examples/verifier.jwk")).unwrap();
+        let mut forged = presentation.clone();
+        forged.key_binding_jwt = Some(sign_key_binding(&claims, &other));
+        let error = forged.verify_key_binding(&issuer_key).unwrap_err();
+        assert!(error.is::<InvalidSignature>(), "{error:#}");
+
+        let mut stale = presentation;
+        let mut claims = claims;
+        claims["iat"] = json!(now() - 2 * KEY_BINDING_JWT_LEEWAY);
+        stale.key_binding_jwt = Some(sign_key_binding(&claims, &key));
+        let error = stale.verify_key_binding(&issuer_key).unwrap_err();
+        assert!(error.to_string().contains("'iat'"), "{error:#}");
+    }
+
+    #[tokio::test]
+    async fn issuer_key() {
+        use did_method_key::DIDKey;
+        use ssi::did::{DIDMethod, Source};
+
+        let issuer_key: JWK =
+            serde_json::from_str(include_str!("../../../tests/examples/issuer.jwk")).unwrap();
+        let issuer = DIDKey.generate(&Source::Key(&issuer_key)).unwrap();
+        let sign = |iss: &str| {
+            let key = JWK {
+                key_id: Some(format!(
+                    "{issuer}#{}",
+                    issuer.strip_prefix("did:key:").unwrap()
+                )),
+                ..issuer_key.clone()
+            };
+            let issuer_jwt =
+                ssi::jwt::encode_sign(ssi::jwk::Algorithm::ES256, &json!({ "iss": iss }), &key)
+                    .unwrap();
+            SdJwtPresentation::parse(&format!("{issuer_jwt}~")).unwrap()
+        };
+
+        let key = sign(&issuer).issuer_key(&DIDKey).await.unwrap();
+        assert_eq!(key, issuer_key.to_public());
+
+        let error = sign("did:example:issuer")
+            .issuer_key(&DIDKey)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("is not the issuer"), "{error:#}");
+    }
+
+    #[test]
+    fn repeated_digest() {
+        let given_name = encode_disclosure(json!(["salt1", "given_name", "Alice"]));
+        let nationality = encode_disclosure(json!(["salt2", "DE"]));
+
+        for claims in [
+            json!({ "_sd": [disclosure_digest(&given_name), disclosure_digest(&given_name)] }),
+            json!({
+                "_sd": [disclosure_digest(&nationality)],
+                "nationalities": [{ "...": disclosure_digest(&nationality) }]
+            }),
+        ] {
+            let issuer_jwt = ssi::jwt::encode_unsigned(&claims).unwrap();
+            // Whether or not the digest is disclosed.
+            for disclosures in ["", &format!("{given_name}~")] {
+                let error = SdJwtPresentation::parse(&format!("{issuer_jwt}~{disclosures}"))
+                    .unwrap()
+                    .disclosed_claims()
+                    .unwrap_err();
+                assert!(error.to_string().contains("more than once"), "{error:#}");
+            }
+        }
+    }
+
+    #[test]
+    fn unreferenced_disclosure() {
+        let given_name = encode_disclosure(json!(["salt1", "given_name", "Alice"]));
+        let issuer_jwt = ssi::jwt::encode_unsigned(&json!({
+            "iss": "https://issuer.example.com",
+            "_sd": []
+        }))
+        .unwrap();
+
+        let error = SdJwtPresentation::parse(&format!("{issuer_jwt}~{given_name}~"))
+            .unwrap()
+            .disclosed_claims()
+            .unwrap_err();
+        assert!(error.to_string().contains("is not referenced"));
+    }
+}
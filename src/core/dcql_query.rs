@@ -0,0 +1,609 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as Json};
+
+use crate::utils::NonEmptyVec;
+
+use super::{
+    credential_format::{
+        sd_jwt::SdJwtPresentation, CredentialFormat, DcSdJwt, LdpVc, MsoMdoc, VcSdJwt,
+    },
+    response::parameters::VpToken,
+};
+
+/// A Digital Credentials Query Language (DCQL) query, an alternative to a presentation
+/// definition for requesting credentials from a wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DcqlQuery {
+    pub credentials: NonEmptyVec<CredentialQuery>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_sets: Option<NonEmptyVec<CredentialSetQuery>>,
+}
+
+/// A request for a single credential, see [DcqlQuery].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CredentialQuery {
+    /// Identifies the credential in the response.
+    pub id: String,
+    /// The format of the requested credential, such as `mso_mdoc`.
+    pub format: String,
+    /// Format-specific metadata constraints, such as the `doctype_value` of an `mso_mdoc`
+    /// credential.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Map<String, Json>>,
+    /// The requested claims. If absent, no specific claims are requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims: Option<NonEmptyVec<ClaimsQuery>>,
+    /// Combinations of claim ids that satisfy the request, in order of preference.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_sets: Option<NonEmptyVec<Vec<String>>>,
+}
+
+/// A request for a single claim of a credential, see [CredentialQuery].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaimsQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The path to the claim within the credential.
+    pub path: NonEmptyVec<ClaimPathElement>,
+    /// The values that the claim is expected to have, any of which is acceptable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub values: Option<NonEmptyVec<Json>>,
+}
+
+/// An element of a claims path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ClaimPathElement {
+    /// Select the value of a key in an object.
+    Key(String),
+    /// Select an element of an array.
+    Index(usize),
+    /// Select all elements of an array, represented as `null`.
+    Wildcard,
+}
+
+/// Combinations of credentials that satisfy a [DcqlQuery].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CredentialSetQuery {
+    /// Each option is a list of credential query ids, in order of preference.
+    pub options: NonEmptyVec<Vec<String>>,
+    /// Whether the wallet must return one of the options. Defaults to `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<Json>,
+}
+
+/// A credential held by a wallet, as seen by a [DcqlQuery].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeldCredential {
+    /// The format of the credential, such as `mso_mdoc`.
+    pub format: String,
+    /// The document type of an `mso_mdoc` credential.
+    pub doctype: Option<String>,
+    /// The type (`vct`) of an SD-JWT VC.
+    pub vct: Option<String>,
+    /// The claims of the credential.
+    pub claims: Json,
+}
+
+impl DcqlQuery {
+    /// Parse a DCQL query from JSON, and validate its structure.
+    ///
+    /// See [DcqlQuery::validate_structure].
+    pub fn from_json(json: &str) -> Result<Self> {
+        let query: Self = serde_json::from_str(json).context("unable to parse DCQL query")?;
+        query.validate_structure()?;
+        Ok(query)
+    }
+
+    /// Validate the structure of this query.
+    ///
+    /// Credential query ids must be non-empty and unique, and credential sets may only refer to
+    /// credential queries in this query.
+    pub fn validate_structure(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for (i, credential) in self.credentials.iter().enumerate() {
+            if credential.id.is_empty() {
+                bail!("credential query {i} must have a non-empty 'id'")
+            }
+            if !ids.insert(credential.id.as_str()) {
+                bail!("credential query id '{}' is not unique", credential.id)
+            }
+            credential
+                .validate_structure()
+                .context(format!("invalid credential query '{}'", credential.id))?;
+        }
+
+        let options = self
+            .credential_sets
+            .iter()
+            .flat_map(|sets| sets.iter())
+            .flat_map(|set| set.options.iter())
+            .flatten();
+        for id in options {
+            if !ids.contains(id.as_str()) {
+                bail!("credential set refers to unknown credential query '{id}'")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether credential claims satisfy the requested claims of any of the credential queries.
+    ///
+    /// See [CredentialQuery::matches_claims].
+    pub fn matches(&self, credential_claims: &Json) -> bool {
+        self.credentials
+            .iter()
+            .any(|query| query.matches_claims(credential_claims))
+    }
+
+    /// Select the held credentials that match each credential query.
+    ///
+    /// ## Returns
+    /// The matching credentials, keyed by credential query id. Credential queries without a
+    /// match are omitted.
+    pub fn select<'a>(
+        &self,
+        credentials: &'a [HeldCredential],
+    ) -> BTreeMap<String, Vec<&'a HeldCredential>> {
+        self.credentials
+            .iter()
+            .filter_map(|query| {
+                let matches: Vec<_> = credentials
+                    .iter()
+                    .filter(|credential| query.matches(credential))
+                    .collect();
+                (!matches.is_empty()).then(|| (query.id.clone(), matches))
+            })
+            .collect()
+    }
+
+    /// Check that the presentations of a `vp_token` satisfy this query.
+    ///
+    /// The `vp_token` must map credential query ids to presentations, and must contain a
+    /// presentation for every credential query, or if the query has `credential_sets`, for one
+    /// option of every required credential set. Each presentation must have the format,
+    /// metadata and claims requested by its credential query, see [CredentialQuery::matches].
+    ///
+    /// The signatures of the presentations are not verified, and `mso_mdoc` presentations are
+    /// rejected as unverifiable, see
+    /// [DeviceResponse](super::credential_format::mso_mdoc::DeviceResponse).
+    pub fn validate_vp_token(&self, vp_token: &VpToken) -> Result<()> {
+        let VpToken::ByQueryId(presentations) = vp_token else {
+            bail!(
+                "the 'vp_token' of a response to a DCQL query must be keyed by credential query id"
+            )
+        };
+
+        let submitted = |id: &String| presentations.contains_key(id);
+        match &self.credential_sets {
+            None => {
+                if let Some(query) = self.credentials.iter().find(|query| !submitted(&query.id)) {
+                    bail!(
+                        "credential query '{}' is missing from the 'vp_token'",
+                        query.id
+                    )
+                }
+            }
+            Some(credential_sets) => {
+                for (i, credential_set) in credential_sets.iter().enumerate() {
+                    if credential_set.required != Some(false)
+                        && !credential_set
+                            .options
+                            .iter()
+                            .any(|option| option.iter().all(submitted))
+                    {
+                        bail!("credential set {i} is not satisfied by the 'vp_token'")
+                    }
+                }
+            }
+        }
+
+        for (id, presentation) in presentations {
+            let query = self
+                .credentials
+                .iter()
+                .find(|query| query.id == *id)
+                .context(format!(
+                    "'vp_token' refers to unknown credential query '{id}'"
+                ))?;
+            let credential = query
+                .presented_credential(presentation)
+                .context(format!("unable to decode the presentation for '{id}'"))?;
+            if !query.matches(&credential) {
+                bail!("the presentation for '{id}' does not satisfy its credential query")
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CredentialQuery {
+    /// Decode a presentation of the requested format into the credential it presents.
+    ///
+    /// `jwt_vc_json` and `ldp_vc` presentations must present a single credential. `mso_mdoc`
+    /// presentations are rejected, as they cannot be verified, see
+    /// [DeviceResponse](super::credential_format::mso_mdoc::DeviceResponse).
+    fn presented_credential(&self, presentation: &str) -> Result<HeldCredential> {
+        let format = self.format.clone();
+        if format == MsoMdoc::ID {
+            bail!("mdoc presentations cannot be verified, as the issuer signature of mdocs is not verified")
+        }
+
+        let claims = if [VcSdJwt::ID, DcSdJwt::ID].contains(&format.as_str()) {
+            SdJwtPresentation::parse(presentation)?.disclosed_claims()?
+        } else if format == LdpVc::ID {
+            let presentation: Json = serde_json::from_str(presentation)
+                .context("presentation is not a JSON-LD object")?;
+            single_credential(&presentation)?.clone()
+        } else if format.starts_with("jwt_vc") {
+            let claims: Json = ssi::jwt::decode_unverified(presentation)
+                .context("presentation is not a valid JWT")?;
+            match single_credential(claims.get("vp").context("presentation has no 'vp' claim")?)? {
+                Json::String(jwt) => {
+                    let mut claims: Json = ssi::jwt::decode_unverified(jwt)
+                        .context("credential is not a valid JWT")?;
+                    claims
+                        .get_mut("vc")
+                        .map(Json::take)
+                        .context("credential has no 'vc' claim")?
+                }
+                credential => credential.clone(),
+            }
+        } else {
+            bail!("presentations of format '{format}' are not supported")
+        };
+
+        let vct = claims.get("vct").and_then(Json::as_str).map(str::to_owned);
+        Ok(HeldCredential {
+            format,
+            doctype: None,
+            vct,
+            claims,
+        })
+    }
+
+    fn validate_structure(&self) -> Result<()> {
+        let mut claim_ids = HashSet::new();
+        for claim in self.claims.iter().flat_map(|claims| claims.iter()) {
+            if let Some(id) = &claim.id {
+                if !claim_ids.insert(id.as_str()) {
+                    bail!("claims query id '{id}' is not unique")
+                }
+            }
+        }
+
+        let claim_set_ids = self
+            .claim_sets
+            .iter()
+            .flat_map(|claim_sets| claim_sets.iter())
+            .flatten();
+        for id in claim_set_ids {
+            if !claim_ids.contains(id.as_str()) {
+                bail!("claim set refers to unknown claims query '{id}'")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a held credential has the requested format, metadata and claims.
+    pub fn matches(&self, credential: &HeldCredential) -> bool {
+        self.matches_meta(credential) && self.matches_claims(&credential.claims)
+    }
+
+    /// Whether credential claims satisfy the requested claims.
+    ///
+    /// Without `claim_sets` every claims query must match, otherwise every claims query of at
+    /// least one claim set must match.
+    pub fn matches_claims(&self, credential_claims: &Json) -> bool {
+        let Some(claims) = &self.claims else {
+            return true;
+        };
+
+        let Some(claim_sets) = &self.claim_sets else {
+            return claims.iter().all(|claim| claim.matches(credential_claims));
+        };
+
+        claim_sets.iter().any(|claim_set| {
+            claim_set.iter().all(|id| {
+                claims
+                    .iter()
+                    .filter(|claim| claim.id.as_ref() == Some(id))
+                    .all(|claim| claim.matches(credential_claims))
+            })
+        })
+    }
+
+    fn matches_meta(&self, credential: &HeldCredential) -> bool {
+        if self.format != credential.format {
+            return false;
+        }
+
+        let Some(meta) = &self.meta else {
+            return true;
+        };
+
+        if self.format == MsoMdoc::ID {
+            if let Some(doctype) = meta.get("doctype_value") {
+                return credential.doctype.as_deref() == doctype.as_str();
+            }
+        }
+
+        if let Some(Json::Array(vct_values)) = meta.get("vct_values") {
+            return credential
+                .vct
+                .as_ref()
+                .is_some_and(|vct| vct_values.iter().any(|value| value.as_str() == Some(vct)));
+        }
+
+        true
+    }
+}
+
+impl ClaimsQuery {
+    /// Resolve the `path` against credential claims.
+    ///
+    /// Each path element selects from the values selected by the previous element, starting
+    /// from the root of the claims. An element that does not apply to a value, such as a key
+    /// of an array, selects nothing from it.
+    pub fn resolve<'a>(&self, credential_claims: &'a Json) -> Vec<&'a Json> {
+        self.path
+            .iter()
+            .fold(vec![credential_claims], |selected, element| {
+                selected
+                    .into_iter()
+                    .flat_map(|value| match (element, value) {
+                        (ClaimPathElement::Key(key), Json::Object(object)) => {
+                            object.get(key).into_iter().collect()
+                        }
+                        (ClaimPathElement::Index(i), Json::Array(array)) => {
+                            array.get(*i).into_iter().collect()
+                        }
+                        (ClaimPathElement::Wildcard, Json::Array(array)) => array.iter().collect(),
+                        _ => vec![],
+                    })
+                    .collect()
+            })
+    }
+
+    /// Whether the claim is present in the credential claims, with one of the requested
+    /// `values` if any.
+    pub fn matches(&self, credential_claims: &Json) -> bool {
+        let selected = self.resolve(credential_claims);
+        match &self.values {
+            None => !selected.is_empty(),
+            Some(values) => selected.iter().any(|value| values.contains(value)),
+        }
+    }
+}
+
+/// The single credential in the `verifiableCredential` of a presentation.
+fn single_credential(presentation: &Json) -> Result<&Json> {
+    match presentation.get("verifiableCredential") {
+        Some(Json::Array(credentials)) if credentials.len() == 1 => Ok(&credentials[0]),
+        Some(Json::Array(_)) | None => bail!("presentation does not contain a single credential"),
+        Some(credential) => Ok(credential),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn validate_structure() {
+        DcqlQuery::from_json(
+            &json!({
+                "credentials": [
+                    { "id": "pid", "format": "dc+sd-jwt" },
+                    { "id": "mdl", "format": "mso_mdoc" }
+                ],
+                "credential_sets": [
+                    { "options": [["pid"], ["mdl"]] }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let error = DcqlQuery::from_json(
+            &json!({
+                "credentials": [
+                    { "id": "pid", "format": "dc+sd-jwt" },
+                    { "id": "pid", "format": "mso_mdoc" }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("is not unique"));
+
+        let error = DcqlQuery::from_json(
+            &json!({
+                "credentials": [
+                    { "id": "pid", "format": "dc+sd-jwt" }
+                ],
+                "credential_sets": [
+                    { "options": [["mdl"]] }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("unknown credential query 'mdl'"));
+    }
+
+    fn claims() -> Json {
+        json!({
+            "given_name": "Alice",
+            "address": {
+                "country": "DE",
+                "locality": "Berlin"
+            },
+            "nationalities": ["DE", "FR"],
+            "degrees": [
+                { "type": "Bachelor" },
+                { "type": "Master" }
+            ]
+        })
+    }
+
+    fn claims_query(query: Json) -> ClaimsQuery {
+        serde_json::from_value(query).unwrap()
+    }
+
+    #[test]
+    fn nested_object_path() {
+        let claim = claims_query(json!({ "path": ["address", "country"] }));
+        assert_eq!(claim.resolve(&claims()), [&json!("DE")]);
+        assert!(claim.matches(&claims()));
+
+        let claim = claims_query(json!({ "path": ["address", "postal_code"] }));
+        assert!(!claim.matches(&claims()));
+
+        let claim = claims_query(json!({ "path": ["given_name", "first"] }));
+        assert!(!claim.matches(&claims()));
+    }
+
+    #[test]
+    fn array_index_path() {
+        let claim = claims_query(json!({ "path": ["nationalities", 1] }));
+        assert_eq!(claim.resolve(&claims()), [&json!("FR")]);
+
+        let claim = claims_query(json!({ "path": ["nationalities", 2] }));
+        assert!(!claim.matches(&claims()));
+
+        let claim = claims_query(json!({ "path": ["degrees", null, "type"] }));
+        assert_eq!(
+            claim.resolve(&claims()),
+            [&json!("Bachelor"), &json!("Master")]
+        );
+    }
+
+    #[test]
+    fn values_filter() {
+        let claim = claims_query(json!({
+            "path": ["address", "country"],
+            "values": ["DE", "AT"]
+        }));
+        assert!(claim.matches(&claims()));
+
+        let claim = claims_query(json!({
+            "path": ["address", "country"],
+            "values": ["FR"]
+        }));
+        assert!(!claim.matches(&claims()));
+
+        let claim = claims_query(json!({
+            "path": ["degrees", null, "type"],
+            "values": ["Master"]
+        }));
+        assert!(claim.matches(&claims()));
+    }
+
+    #[test]
+    fn query_matches() {
+        let query = DcqlQuery::from_json(
+            &json!({
+                "credentials": [
+                    {
+                        "id": "pid",
+                        "format": "dc+sd-jwt",
+                        "claims": [
+                            { "id": "name", "path": ["given_name"] },
+                            { "id": "postal_code", "path": ["address", "postal_code"] },
+                            { "id": "country", "path": ["address", "country"] }
+                        ],
+                        "claim_sets": [["name", "postal_code"], ["name", "country"]]
+                    }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert!(query.matches(&claims()));
+        assert!(!query.matches(&json!({ "given_name": "Alice" })));
+    }
+
+    #[test]
+    fn claim_path_elements() {
+        let claim: ClaimsQuery = serde_json::from_value(json!({
+            "path": ["addresses", 0, null]
+        }))
+        .unwrap();
+        assert_eq!(
+            claim.path.as_ref(),
+            [
+                ClaimPathElement::Key("addresses".into()),
+                ClaimPathElement::Index(0),
+                ClaimPathElement::Wildcard,
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_vp_token() {
+        let query = DcqlQuery::from_json(
+            &json!({
+                "credentials": [
+                    {
+                        "id": "degree",
+                        "format": "ldp_vc",
+                        "claims": [{
+                            "path": ["credentialSubject", "degree", "type"],
+                            "values": ["BachelorDegree"]
+                        }]
+                    },
+                    { "id": "pid", "format": "dc+sd-jwt" }
+                ],
+                "credential_sets": [{ "options": [["degree"], ["pid"]] }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let presentation = |degree: &str| {
+            json!({
+                "@context": ["https://www.w3.org/2018/credentials/v1"],
+                "type": ["VerifiablePresentation"],
+                "verifiableCredential": {
+                    "credentialSubject": { "degree": { "type": degree } }
+                }
+            })
+            .to_string()
+        };
+        let vp_token =
+            |id: &str, degree| VpToken::ByQueryId([(id.into(), presentation(degree))].into());
+
+        query
+            .validate_vp_token(&vp_token("degree", "BachelorDegree"))
+            .unwrap();
+
+        for (vp_token, expected) in [
+            (
+                vp_token("degree", "MasterDegree"),
+                "does not satisfy its credential query",
+            ),
+            (
+                vp_token("mdl", "BachelorDegree"),
+                "credential set 0 is not satisfied",
+            ),
+            (
+                VpToken::Single(presentation("BachelorDegree")),
+                "must be keyed by credential query id",
+            ),
+        ] {
+            let error = query.validate_vp_token(&vp_token).unwrap_err();
+            assert!(error.to_string().contains(expected), "{error:#}");
+        }
+    }
+}
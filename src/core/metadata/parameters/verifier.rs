@@ -114,11 +114,33 @@ impl From<AuthorizationEncryptedResponseEnc> for Json {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct EncryptedResponseEncValuesSupported(pub Vec<String>);
+
+impl TypedParameter for EncryptedResponseEncValuesSupported {
+    const KEY: &'static str = "encrypted_response_enc_values_supported";
+}
+
+impl TryFrom<Json> for EncryptedResponseEncValuesSupported {
+    type Error = Error;
+
+    fn try_from(value: Json) -> Result<Self, Self::Error> {
+        Ok(Self(serde_json::from_value(value)?))
+    }
+}
+
+impl From<EncryptedResponseEncValuesSupported> for Json {
+    fn from(value: EncryptedResponseEncValuesSupported) -> Json {
+        value.0.into()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use serde_json::json;
 
-    use crate::core::object::UntypedObject;
+    use crate::core::{authorization_request::parameters::ClientMetadata, object::UntypedObject};
+    use crate::presentation_exchange::{ClaimFormatDesignation, ClaimFormatPayload};
 
     use super::*;
 
@@ -139,6 +161,7 @@ mod test {
             },
             "authorization_encrypted_response_alg":"ECDH-ES",
             "authorization_encrypted_response_enc":"A256GCM",
+            "encrypted_response_enc_values_supported":["A256GCM","A128GCM"],
             "require_signed_request_object":true,
             "vp_formats":{ "mso_mdoc":{} }
         }
@@ -153,6 +176,38 @@ mod test {
         assert_eq!(fnd, exp)
     }
 
+    #[test]
+    fn client_metadata_accessors() {
+        let client_metadata = ClientMetadata(metadata());
+
+        let vp_formats = client_metadata.vp_formats().unwrap().unwrap();
+        assert_eq!(
+            vp_formats.0[&ClaimFormatDesignation::MsoMdoc],
+            ClaimFormatPayload::Other(Map::new())
+        );
+        assert_eq!(client_metadata.jwks().unwrap().unwrap().keys.len(), 1);
+        assert_eq!(
+            client_metadata
+                .authorization_encrypted_response_alg()
+                .unwrap()
+                .as_deref(),
+            Some("ECDH-ES")
+        );
+        assert_eq!(
+            client_metadata
+                .encrypted_response_enc_values_supported()
+                .unwrap()
+                .unwrap(),
+            ["A256GCM", "A128GCM"]
+        );
+        assert_eq!(
+            ClientMetadata(UntypedObject::default())
+                .vp_formats()
+                .unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn jwks() {
         let JWKs { keys } = metadata().get().unwrap().unwrap();
@@ -193,4 +248,11 @@ mod test {
         let AuthorizationEncryptedResponseEnc(s) = metadata().get().unwrap().unwrap();
         assert_eq!(s, exp);
     }
+
+    #[test]
+    fn encrypted_response_enc_values_supported() {
+        let exp = vec!["A256GCM".to_string(), "A128GCM".to_string()];
+        let EncryptedResponseEncValuesSupported(v) = metadata().get().unwrap().unwrap();
+        assert_eq!(v, exp);
+    }
 }
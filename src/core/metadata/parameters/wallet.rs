@@ -2,6 +2,7 @@ use crate::core::{
     authorization_request::parameters::{ClientIdScheme, ResponseType},
     object::TypedParameter,
 };
+use crate::presentation_exchange::{ClaimFormatMap, ClaimFormatPayload};
 use anyhow::{bail, Error, Result};
 use serde_json::{Map, Value as Json};
 use url::Url;
@@ -148,6 +149,31 @@ impl TryFrom<Json> for VpFormatsSupported {
     }
 }
 
+impl VpFormatsSupported {
+    /// The supported formats as a [ClaimFormatMap], with the `alg_values_supported` or
+    /// `proof_type_values_supported` of each format as its accepted algorithms or proof types.
+    pub fn claim_format_map(&self) -> ClaimFormatMap {
+        ClaimFormatMap(
+            self.0
+                .iter()
+                .map(|(designation, format)| {
+                    let values = |key: &str| -> Option<Vec<String>> {
+                        serde_json::from_value(format.get(key)?.clone()).ok()
+                    };
+                    let payload = if let Some(alg) = values("alg_values_supported") {
+                        ClaimFormatPayload::Alg(alg)
+                    } else if let Some(proof_type) = values("proof_type_values_supported") {
+                        ClaimFormatPayload::ProofType(proof_type)
+                    } else {
+                        ClaimFormatPayload::Other(format.as_object().cloned().unwrap_or_default())
+                    };
+                    (designation.as_str().into(), payload)
+                })
+                .collect(),
+        )
+    }
+}
+
 impl From<VpFormatsSupported> for Json {
     fn from(value: VpFormatsSupported) -> Json {
         Json::Object(value.0)
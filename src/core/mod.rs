@@ -1,5 +1,6 @@
 pub mod authorization_request;
 pub mod credential_format;
+pub mod dcql_query;
 pub mod metadata;
 pub mod object;
 pub mod response;
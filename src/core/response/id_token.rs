@@ -0,0 +1,138 @@
+use anyhow::{bail, Context, Result};
+use base64::prelude::*;
+use serde_json::{Map, Value as Json};
+use sha2::{Digest, Sha256};
+use ssi::{
+    did_resolve::{resolve_key, DIDResolver},
+    jwk::JWK,
+};
+
+/// Verify the signature of a self-issued ID Token (SIOPv2), returning its subject.
+///
+/// The `iss` must equal the `sub`, which is either a DID, whose verification method in the `kid`
+/// header is resolved with the `resolver`, or the JWK Thumbprint of the `sub_jwk` claim.
+///
+/// The `nonce` and `aud` are not checked.
+pub async fn verify_self_issued(
+    id_token: &str,
+    resolver: Option<&dyn DIDResolver>,
+) -> Result<String> {
+    let claims: Json =
+        ssi::jwt::decode_unverified(id_token).context("unable to decode id_token")?;
+
+    let Some(Json::String(sub)) = claims.get("sub") else {
+        bail!("'sub' was missing or not a string")
+    };
+
+    if claims.get("iss").and_then(Json::as_str) != Some(sub) {
+        bail!("'iss' of a self-issued id_token must be equal to its 'sub'")
+    }
+
+    let jwk: JWK = if sub.starts_with("did:") {
+        let kid = header_kid(id_token)?;
+        let (did, _f) = kid.split_once('#').context(format!(
+            "expected a DID verification method in 'kid' header, received '{kid}'"
+        ))?;
+        if did != sub {
+            bail!("DIDs from 'kid' ({did}) and 'sub' ({sub}) do not match")
+        }
+
+        let resolver = resolver.context("a DID resolver is required to verify a DID subject")?;
+        resolve_key(&kid, resolver)
+            .await
+            .context("unable to resolve verification method from 'kid' header")?
+    } else {
+        let sub_jwk = claims
+            .get("sub_jwk")
+            .context("'sub_jwk' is required when 'sub' is not a DID")?;
+        if jwk_thumbprint(sub_jwk)? != *sub {
+            bail!("'sub' is not the JWK Thumbprint of 'sub_jwk'")
+        }
+        serde_json::from_value(sub_jwk.clone()).context("'sub_jwk' was not a valid JWK")?
+    };
+
+    let _: Json = ssi::jwt::decode_verify(id_token, &jwk)
+        .context("id_token signature could not be verified")?;
+
+    Ok(sub.clone())
+}
+
+/// Decode the headers of a JWT.
+pub(crate) fn jwt_headers(jwt: &str) -> Result<Map<String, Json>> {
+    let (headers_b64, _, _) = ssi::jws::split_jws(jwt)?;
+
+    let headers_json_bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(headers_b64)
+        .context("jwt headers were not valid base64url")?;
+
+    serde_json::from_slice(&headers_json_bytes).context("jwt headers were not valid json")
+}
+
+pub(crate) fn header_kid(jwt: &str) -> Result<String> {
+    let mut headers = jwt_headers(jwt)?;
+
+    let Json::String(kid) = headers
+        .remove("kid")
+        .context("'kid' was missing from jwt headers")?
+    else {
+        bail!("'kid' header was not a string")
+    };
+
+    Ok(kid)
+}
+
+/// The base64url-encoded RFC 7638 JWK Thumbprint of a public key.
+fn jwk_thumbprint(jwk: &Json) -> Result<String> {
+    let member = |name: &str| -> Result<Json> {
+        jwk.get(name)
+            .filter(|value| value.is_string())
+            .cloned()
+            .context(format!("'{name}' was missing from the JWK or not a string"))
+    };
+
+    // The required members, in lexicographic order and without whitespace.
+    let required = match jwk.get("kty").and_then(Json::as_str) {
+        Some("EC") => format!(
+            r#"{{"crv":{},"kty":"EC","x":{},"y":{}}}"#,
+            member("crv")?,
+            member("x")?,
+            member("y")?
+        ),
+        Some("OKP") => format!(
+            r#"{{"crv":{},"kty":"OKP","x":{}}}"#,
+            member("crv")?,
+            member("x")?
+        ),
+        Some("RSA") => format!(
+            r#"{{"e":{},"kty":"RSA","n":{}}}"#,
+            member("e")?,
+            member("n")?
+        ),
+        kty => bail!("unsupported JWK key type {kty:?}"),
+    };
+
+    Ok(BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(required)))
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn rfc7638_thumbprint() {
+        // RFC 7638, Section 3.1.
+        let jwk = json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        });
+        assert_eq!(
+            jwk_thumbprint(&jwk).unwrap(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+}
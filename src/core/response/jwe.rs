@@ -0,0 +1,239 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes128Gcm, Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::prelude::*;
+use p256::{ecdh::EphemeralSecret, PublicKey, SecretKey};
+use serde_json::{json, Map, Value as Json};
+use sha2::{Digest, Sha256};
+
+/// The only supported key management algorithm.
+pub const ALG: &str = "ECDH-ES";
+
+/// Content encryption algorithms that are supported, in order of preference.
+pub const ENC_VALUES_SUPPORTED: [&str; 2] = ["A256GCM", "A128GCM"];
+
+/// The first P-256 key of a JWK set that may be used for `ECDH-ES` encryption.
+pub fn recipient_key(keys: &[Map<String, Json>]) -> Option<&Map<String, Json>> {
+    keys.iter().find(|jwk| {
+        let is = |key: &str, value: &str| jwk.get(key).and_then(Json::as_str) == Some(value);
+        is("kty", "EC")
+            && is("crv", "P-256")
+            && (!jwk.contains_key("use") || is("use", "enc"))
+            && (!jwk.contains_key("alg") || is("alg", ALG))
+    })
+}
+
+/// Encrypt a payload as a compact JWE for the recipient `jwk`, using ECDH-ES key agreement.
+pub fn encrypt(payload: &[u8], jwk: &Map<String, Json>, enc: &str) -> Result<String> {
+    let key_len = key_len(enc)?;
+
+    let recipient = PublicKey::from_jwk_str(&Json::Object(jwk.clone()).to_string())
+        .context("recipient JWK is not a P-256 public key")?;
+
+    let ephemeral = EphemeralSecret::random(&mut OsRng);
+    let epk: Json = serde_json::from_str(&ephemeral.public_key().to_jwk_string())?;
+
+    let mut header = json!({
+        "alg": ALG,
+        "enc": enc,
+        "epk": epk,
+    });
+    if let Some(kid) = jwk.get("kid") {
+        header["kid"] = kid.clone();
+    }
+    let header = BASE64_URL_SAFE_NO_PAD.encode(header.to_string());
+
+    let shared_secret = ephemeral.diffie_hellman(&recipient);
+    let cek = concat_kdf(shared_secret.raw_secret_bytes(), enc, &[], &[], key_len);
+
+    let iv = Aes256Gcm::generate_nonce(&mut OsRng);
+    let payload = Payload {
+        msg: payload,
+        aad: header.as_bytes(),
+    };
+    let mut ciphertext = match key_len {
+        16 => Aes128Gcm::new_from_slice(&cek)?.encrypt(&iv, payload),
+        _ => Aes256Gcm::new_from_slice(&cek)?.encrypt(&iv, payload),
+    }
+    .map_err(|_| anyhow!("failed to encrypt payload"))?;
+    let tag = ciphertext.split_off(ciphertext.len() - 16);
+
+    Ok(format!(
+        "{header}..{}.{}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(iv),
+        BASE64_URL_SAFE_NO_PAD.encode(ciphertext),
+        BASE64_URL_SAFE_NO_PAD.encode(tag),
+    ))
+}
+
+/// Decrypt a compact JWE that was encrypted for `key` using ECDH-ES key agreement.
+pub fn decrypt(jwe: &str, key: &SecretKey) -> Result<Vec<u8>> {
+    let [header, encrypted_key, iv, ciphertext, tag]: [&str; 5] = jwe
+        .split('.')
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| anyhow!("JWE must have five parts"))?;
+
+    if !encrypted_key.is_empty() {
+        bail!("JWE encrypted key must be empty for direct key agreement")
+    }
+
+    let decoded_header: Json = serde_json::from_slice(
+        &BASE64_URL_SAFE_NO_PAD
+            .decode(header)
+            .context("JWE header is not valid base64url")?,
+    )
+    .context("JWE header is not valid JSON")?;
+
+    match decoded_header.get("alg").and_then(Json::as_str) {
+        Some(ALG) => (),
+        Some(alg) => bail!("unsupported JWE key management algorithm '{alg}'"),
+        None => bail!("JWE header is missing 'alg'"),
+    }
+
+    let Some(enc) = decoded_header.get("enc").and_then(Json::as_str) else {
+        bail!("JWE header is missing 'enc'")
+    };
+    let key_len = key_len(enc)?;
+
+    let Some(epk) = decoded_header.get("epk") else {
+        bail!("JWE header is missing 'epk'")
+    };
+    let epk =
+        PublicKey::from_jwk_str(&epk.to_string()).context("JWE 'epk' is not a P-256 public key")?;
+
+    let party_info = |name: &str| -> Result<Vec<u8>> {
+        match decoded_header.get(name) {
+            None => Ok(vec![]),
+            Some(Json::String(info)) => BASE64_URL_SAFE_NO_PAD
+                .decode(info)
+                .context(format!("JWE '{name}' is not valid base64url")),
+            Some(_) => bail!("JWE '{name}' is not a string"),
+        }
+    };
+    let apu = party_info("apu")?;
+    let apv = party_info("apv")?;
+
+    let shared_secret = p256::ecdh::diffie_hellman(key.to_nonzero_scalar(), epk.as_affine());
+    let cek = concat_kdf(shared_secret.raw_secret_bytes(), enc, &apu, &apv, key_len);
+
+    let iv = BASE64_URL_SAFE_NO_PAD
+        .decode(iv)
+        .context("JWE initialization vector is not valid base64url")?;
+    if iv.len() != 12 {
+        bail!("JWE initialization vector must be 96 bits")
+    }
+
+    let mut msg = BASE64_URL_SAFE_NO_PAD
+        .decode(ciphertext)
+        .context("JWE ciphertext is not valid base64url")?;
+    msg.extend(
+        BASE64_URL_SAFE_NO_PAD
+            .decode(tag)
+            .context("JWE authentication tag is not valid base64url")?,
+    );
+    let payload = Payload {
+        msg: &msg,
+        aad: header.as_bytes(),
+    };
+
+    match key_len {
+        16 => Aes128Gcm::new_from_slice(&cek)?.decrypt(Nonce::from_slice(&iv), payload),
+        _ => Aes256Gcm::new_from_slice(&cek)?.decrypt(Nonce::from_slice(&iv), payload),
+    }
+    .map_err(|_| anyhow!("failed to decrypt JWE"))
+}
+
+fn key_len(enc: &str) -> Result<usize> {
+    match enc {
+        "A128GCM" => Ok(16),
+        "A256GCM" => Ok(32),
+        _ => bail!("unsupported JWE content encryption algorithm '{enc}'"),
+    }
+}
+
+/// Concat KDF as specified in RFC 7518 Section 4.6.2, with the decoded `apu` and `apv` of the
+/// JWE header, which are empty if absent.
+fn concat_kdf(z: &[u8], enc: &str, apu: &[u8], apv: &[u8], key_len: usize) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(1u32.to_be_bytes());
+    hasher.update(z);
+    for info in [enc.as_bytes(), apu, apv] {
+        hasher.update((info.len() as u32).to_be_bytes());
+        hasher.update(info);
+    }
+    hasher.update(((key_len * 8) as u32).to_be_bytes());
+    hasher.finalize()[..key_len].to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key() -> SecretKey {
+        SecretKey::from_jwk_str(include_str!("../../../tests/examples/encryption.jwk")).unwrap()
+    }
+
+    fn public_jwk() -> Map<String, Json> {
+        serde_json::from_str(&key().public_key().to_jwk_string()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for enc in ENC_VALUES_SUPPORTED {
+            let jwe = encrypt(b"payload", &public_jwk(), enc).unwrap();
+            assert_eq!(decrypt(&jwe, &key()).unwrap(), b"payload");
+        }
+    }
+
+    #[test]
+    fn wrong_key() {
+        let jwe = encrypt(b"payload", &public_jwk(), "A128GCM").unwrap();
+        let other =
+            SecretKey::from_jwk_str(include_str!("../../../tests/examples/verifier.jwk")).unwrap();
+        decrypt(&jwe, &other).unwrap_err();
+    }
+
+    /// The example of RFC 7518 Appendix C.
+    #[test]
+    fn concat_kdf_known_answer() {
+        let alice = PublicKey::from_jwk_str(
+            &json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+                "y": "SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps"
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let bob = SecretKey::from_jwk_str(
+            &json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+                "y": "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+                "d": "VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw"
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let shared_secret = p256::ecdh::diffie_hellman(bob.to_nonzero_scalar(), alice.as_affine());
+        let key = concat_kdf(
+            shared_secret.raw_secret_bytes(),
+            "A128GCM",
+            &BASE64_URL_SAFE_NO_PAD.decode("QWxpY2U").unwrap(),
+            &BASE64_URL_SAFE_NO_PAD.decode("Qm9i").unwrap(),
+            16,
+        );
+        assert_eq!(BASE64_URL_SAFE_NO_PAD.encode(key), "VqqN6vgjbSBcIijNcacQGg");
+    }
+
+    #[test]
+    fn unsupported_enc() {
+        encrypt(b"payload", &public_jwk(), "A128CBC-HS256").unwrap_err();
+    }
+}
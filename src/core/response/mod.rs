@@ -1,14 +1,22 @@
 use std::collections::BTreeMap;
 
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use ssi::did_resolve::{resolve_key, DIDResolver};
 use url::Url;
 
-use self::parameters::{PresentationSubmission, VpToken};
+use self::parameters::{PresentationSubmission, State, VpToken};
 
-use super::object::{ParsingErrorContext, UntypedObject};
+use super::object::{ParsingErrorContext, TypedParameter, UntypedObject};
+#[cfg(feature = "p256")]
+use super::{
+    authorization_request::parameters::ClientMetadata, metadata::parameters::verifier::JWKs,
+};
 
+pub mod id_token;
+#[cfg(feature = "p256")]
+pub mod jwe;
 pub mod parameters;
 
 #[derive(Debug, Clone)]
@@ -37,24 +45,74 @@ impl AuthorizationResponse {
     }
 }
 
+/// An Authorization Response that is not encoded as a JWT.
+///
+/// The first element holds the other parameters of the response, such as the `state` and any
+/// extension parameters. The `vp_token` and `presentation_submission` take precedence over
+/// parameters of the same name in it.
+///
+/// A response to a DCQL query has no `presentation_submission`, and its `vp_token` is keyed by
+/// credential query id, see [VpToken::ByQueryId].
 #[derive(Debug, Clone)]
 pub struct UnencodedAuthorizationResponse(
     pub UntypedObject,
     pub VpToken,
-    pub PresentationSubmission,
+    pub Option<PresentationSubmission>,
 );
 
 impl UnencodedAuthorizationResponse {
+    /// Create a response with no other parameters.
+    pub fn new(vp_token: VpToken, presentation_submission: PresentationSubmission) -> Self {
+        Self(
+            UntypedObject::default(),
+            vp_token,
+            Some(presentation_submission),
+        )
+    }
+
+    /// Create a response to a DCQL query, with no other parameters.
+    pub fn by_query_id(vp_token: BTreeMap<String, String>) -> Self {
+        Self(UntypedObject::default(), VpToken::ByQueryId(vp_token), None)
+    }
+
+    /// Set the `state` of the authorization request.
+    pub fn with_state(self, state: State) -> Self {
+        self.with_extra(state)
+    }
+
+    /// Set another parameter of the response.
+    pub fn with_extra<T: TypedParameter>(mut self, parameter: T) -> Self {
+        self.0.insert(parameter);
+        self
+    }
+
+    pub fn vp_token(&self) -> &VpToken {
+        &self.1
+    }
+
+    /// The `presentation_submission`, absent from a response to a DCQL query.
+    pub fn presentation_submission(&self) -> Option<&PresentationSubmission> {
+        self.2.as_ref()
+    }
+
     /// Encode the Authorization Response as 'application/x-www-form-urlencoded'.
     pub fn into_x_www_form_urlencoded(self) -> Result<String> {
-        let mut inner = self.0;
-        inner.insert(self.1);
-        inner.insert(self.2);
-        serde_urlencoded::to_string(inner.flatten_for_form()?)
+        serde_urlencoded::to_string(UntypedObject::from(self).flatten_for_form()?)
             .context("failed to encode response as 'application/x-www-form-urlencoded'")
     }
 }
 
+impl From<UnencodedAuthorizationResponse> for UntypedObject {
+    fn from(value: UnencodedAuthorizationResponse) -> Self {
+        let mut inner = value.0;
+        inner.insert(value.1);
+        if let Some(presentation_submission) = value.2 {
+            inner.insert(presentation_submission);
+        }
+        inner
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JwtAuthorizationResponse {
     /// Can be JWT or JWE.
@@ -62,6 +120,119 @@ pub struct JwtAuthorizationResponse {
 }
 
 impl JwtAuthorizationResponse {
+    /// Decode the `response` JWT into an [UnencodedAuthorizationResponse].
+    ///
+    /// The signature of the JWT is not verified, and encrypted (JWE) responses are not supported,
+    /// see [JwtAuthorizationResponse::decrypt].
+    pub fn decode_unverified(&self) -> Result<UnencodedAuthorizationResponse> {
+        ssi::jwt::decode_unverified::<UntypedObject>(&self.response)
+            .context("unable to decode authorization response JWT")?
+            .try_into()
+    }
+
+    /// Whether the `response` is an encrypted JWE rather than a JWT.
+    pub fn is_encrypted(&self) -> bool {
+        self.response.split('.').count() == 5
+    }
+
+    /// Whether the `response` is a signed JWT, rather than a JWE or an unsecured JWT.
+    pub fn is_signed(&self) -> bool {
+        !self.is_encrypted()
+            && id_token::jwt_headers(&self.response)
+                .is_ok_and(|headers| headers.get("alg").is_some_and(|alg| alg != "none"))
+    }
+
+    /// Decode the `response` JWT into an [UnencodedAuthorizationResponse], verifying that it is
+    /// signed by the holder of the presentations.
+    ///
+    /// The holder is the `iss` of the first JWT presentation in the `vp_token`. The signing key
+    /// is resolved with the `resolver` from the `kid` header, which must be a verification method
+    /// of the holder.
+    pub async fn decode_verified(
+        &self,
+        resolver: &dyn DIDResolver,
+    ) -> Result<UnencodedAuthorizationResponse> {
+        let response = self.decode_unverified()?;
+
+        let holder = response
+            .vp_token()
+            .presentations()
+            .into_iter()
+            .find_map(|presentation| {
+                let claims: Value = ssi::jwt::decode_unverified(presentation).ok()?;
+                claims.get("iss")?.as_str().map(str::to_owned)
+            })
+            .context("the vp_token does not contain a JWT presentation with an 'iss'")?;
+
+        let kid = id_token::header_kid(&self.response)?;
+        let (did, _f) = kid.split_once('#').context(format!(
+            "expected a DID verification method in 'kid' header, received '{kid}'"
+        ))?;
+        if did != holder {
+            bail!("DID from 'kid' ({did}) is not the holder ({holder})")
+        }
+
+        let key = resolve_key(&kid, resolver)
+            .await
+            .context("unable to resolve verification method from 'kid' header")?;
+
+        ssi::jwt::decode_verify::<UntypedObject>(&self.response, &key)
+            .context("authorization response signature could not be verified")?
+            .try_into()
+    }
+
+    /// Encrypt an [UnencodedAuthorizationResponse] for the client, for the `direct_post.jwt`
+    /// response mode.
+    ///
+    /// The recipient key is the first P-256 encryption key in the client metadata `jwks`. Only
+    /// the `ECDH-ES` key management algorithm is supported. The content encryption algorithm is
+    /// `authorization_encrypted_response_enc` if present, otherwise the first supported value of
+    /// `encrypted_response_enc_values_supported`.
+    #[cfg(feature = "p256")]
+    pub fn encrypt(
+        response: UnencodedAuthorizationResponse,
+        client_metadata: &ClientMetadata,
+    ) -> Result<Self> {
+        if let Some(alg) = client_metadata.authorization_encrypted_response_alg()? {
+            if alg != jwe::ALG {
+                bail!("unsupported authorization_encrypted_response_alg '{alg}'")
+            }
+        }
+
+        let enc = match client_metadata.authorization_encrypted_response_enc()? {
+            Some(enc) => enc,
+            None => client_metadata
+                .encrypted_response_enc_values_supported()?
+                .context("'encrypted_response_enc_values_supported' is missing")?
+                .into_iter()
+                .find(|enc| jwe::ENC_VALUES_SUPPORTED.contains(&enc.as_str()))
+                .context("no supported 'encrypted_response_enc_values_supported' value")?,
+        };
+
+        let JWKs { keys } = client_metadata
+            .jwks()?
+            .context("client metadata 'jwks' is missing")?;
+        let Some(jwk) = jwe::recipient_key(&keys) else {
+            bail!("client metadata 'jwks' does not contain a P-256 encryption key")
+        };
+
+        let payload = serde_json::to_vec(&UntypedObject::from(response))?;
+
+        Ok(Self {
+            response: jwe::encrypt(&payload, jwk, &enc)?,
+        })
+    }
+
+    /// Decrypt the `response` JWE into an [UnencodedAuthorizationResponse].
+    #[cfg(feature = "p256")]
+    pub fn decrypt(&self, key: &p256::SecretKey) -> Result<UnencodedAuthorizationResponse> {
+        let payload = jwe::decrypt(&self.response, key)
+            .context("unable to decrypt authorization response")?;
+        serde_json::from_slice::<UntypedObject>(&payload)
+            .context("decrypted authorization response is not a JSON object")?
+            .try_into()
+    }
+
     /// Encode the Authorization Response as 'application/x-www-form-urlencoded'.
     pub fn into_x_www_form_urlencoded(self) -> Result<String> {
         serde_urlencoded::to_string(self)
@@ -74,12 +245,73 @@ pub struct PostRedirection {
     pub redirect_uri: Url,
 }
 
+/// The response of the verifier to a `direct_post` submission, with a `redirect_uri` that the
+/// wallet should follow.
+///
+/// The `response_code`, if any, is carried in the fragment of the `redirect_uri`, so that the
+/// frontend of the verifier can use it to retrieve the result of the submission.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "PostRedirection", into = "PostRedirection")]
+pub struct RedirectResponse {
+    pub redirect_uri: Url,
+    pub response_code: Option<String>,
+}
+
+impl RedirectResponse {
+    /// Create a redirect, adding the `response_code`, if any, to the fragment of the
+    /// `redirect_uri`.
+    pub fn new(mut redirect_uri: Url, response_code: Option<String>) -> Self {
+        if let Some(response_code) = &response_code {
+            let fragment = url::form_urlencoded::Serializer::new(
+                redirect_uri.fragment().unwrap_or_default().to_owned(),
+            )
+            .append_pair("response_code", response_code)
+            .finish();
+            redirect_uri.set_fragment(Some(&fragment));
+        }
+        Self {
+            redirect_uri,
+            response_code,
+        }
+    }
+}
+
+impl From<PostRedirection> for RedirectResponse {
+    fn from(PostRedirection { redirect_uri }: PostRedirection) -> Self {
+        let response_code = redirect_uri
+            .fragment()
+            .into_iter()
+            .flat_map(|fragment| url::form_urlencoded::parse(fragment.as_bytes()))
+            .chain(redirect_uri.query_pairs())
+            .find(|(name, _)| name == "response_code")
+            .map(|(_, value)| value.into_owned());
+        Self {
+            redirect_uri,
+            response_code,
+        }
+    }
+}
+
+impl From<RedirectResponse> for PostRedirection {
+    fn from(value: RedirectResponse) -> Self {
+        Self {
+            redirect_uri: value.redirect_uri,
+        }
+    }
+}
+
 impl TryFrom<UntypedObject> for UnencodedAuthorizationResponse {
     type Error = Error;
 
     fn try_from(value: UntypedObject) -> Result<Self, Self::Error> {
         let vp_token = value.get().parsing_error()?;
-        let presentation_submission = value.get().parsing_error()?;
+        let presentation_submission = match (value.get::<PresentationSubmission>().transpose()?, &vp_token) {
+            (Some(presentation_submission), _) => Some(presentation_submission),
+            (None, VpToken::ByQueryId(_)) => None,
+            (None, _) => bail!(
+                "'presentation_submission' is required unless the 'vp_token' is keyed by credential query id"
+            ),
+        };
         Ok(Self(value, vp_token, presentation_submission))
     }
 }
@@ -90,7 +322,10 @@ mod test {
 
     use crate::core::object::UntypedObject;
 
-    use super::{JwtAuthorizationResponse, UnencodedAuthorizationResponse};
+    use super::{
+        parameters::{State, VpToken},
+        JwtAuthorizationResponse, RedirectResponse, UnencodedAuthorizationResponse,
+    };
 
     #[test]
     fn jwt_authorization_response_to_form_urlencoded() {
@@ -122,4 +357,76 @@ mod test {
             "presentation_submission=%7B%22definition_id%22%3A%22definition_id%22%2C%22descriptor_map%22%3A%5B%5D%2C%22id%22%3A%22id%22%7D&vp_token=string",
         )
     }
+
+    #[test]
+    fn unencoded_authorization_response_new() {
+        let presentation_submission = || {
+            json!({
+                "id": "id",
+                "definition_id": "definition_id",
+                "descriptor_map": []
+            })
+            .try_into()
+            .unwrap()
+        };
+
+        let response = UnencodedAuthorizationResponse::new(
+            VpToken::Single("string".into()),
+            presentation_submission(),
+        )
+        .with_state(State("state".into()));
+
+        let mut object = UntypedObject::default();
+        object.insert(State("state".into()));
+        let tuple = UnencodedAuthorizationResponse(
+            object,
+            VpToken::Single("string".into()),
+            Some(presentation_submission()),
+        );
+
+        assert_eq!(
+            response.into_x_www_form_urlencoded().unwrap(),
+            tuple.into_x_www_form_urlencoded().unwrap()
+        );
+    }
+
+    #[test]
+    fn unencoded_authorization_response_by_query_id() {
+        let object: UntypedObject =
+            serde_json::from_value(json!({ "vp_token": { "pid": "header.body.signature" } }))
+                .unwrap();
+        let response = UnencodedAuthorizationResponse::try_from(object).unwrap();
+        assert!(response.presentation_submission().is_none());
+        assert_eq!(
+            response.vp_token().by_query_id("pid"),
+            Some("header.body.signature")
+        );
+
+        let object: UntypedObject =
+            serde_json::from_value(json!({ "vp_token": "header.body.signature" })).unwrap();
+        UnencodedAuthorizationResponse::try_from(object).unwrap_err();
+    }
+
+    #[test]
+    fn redirect_response_code() {
+        let redirect = RedirectResponse::new(
+            "https://client.example.org/cb".parse().unwrap(),
+            Some("091535f699ea575c7937fa5f0f454aee".into()),
+        );
+        assert_eq!(
+            serde_json::to_value(&redirect).unwrap(),
+            json!({
+                "redirect_uri": "https://client.example.org/cb#response_code=091535f699ea575c7937fa5f0f454aee"
+            })
+        );
+
+        let parsed: RedirectResponse =
+            serde_json::from_value(serde_json::to_value(&redirect).unwrap()).unwrap();
+        assert_eq!(parsed, redirect);
+
+        let parsed: RedirectResponse =
+            serde_json::from_value(json!({ "redirect_uri": "https://client.example.org/cb" }))
+                .unwrap();
+        assert_eq!(parsed.response_code, None);
+    }
 }
@@ -1,8 +1,15 @@
-use anyhow::Error;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "p256")]
+use anyhow::Context;
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
 use serde_json::Value as Json;
 
 pub use crate::core::authorization_request::parameters::State;
 use crate::core::object::TypedParameter;
+#[cfg(feature = "p256")]
+use crate::core::{metadata::parameters::verifier::JWKs, response::jwe};
 
 #[derive(Debug, Clone)]
 pub struct IdToken(pub String);
@@ -25,8 +32,97 @@ impl From<IdToken> for Json {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct VpToken(pub String);
+/// `vp_token` field in the Authorization Response.
+///
+/// Contains an array of presentations when more than one presentation is submitted, in which case
+/// the descriptor map paths index into the array (e.g. `$[0]`). In response to a DCQL query, it is
+/// a JSON object with a presentation for each credential query id.
+///
+/// Presentations that are JSON objects, such as `ldp_vp` presentations, are kept in their
+/// serialized form. A JSON object `vp_token` with an `@context` is such a presentation, rather
+/// than a map of credential query ids.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "Json", into = "Json")]
+pub enum VpToken {
+    Single(String),
+    Multiple(Vec<String>),
+    ByQueryId(BTreeMap<String, String>),
+}
+
+impl VpToken {
+    /// The submitted presentations, ordered by credential query id for [VpToken::ByQueryId].
+    pub fn presentations(&self) -> Vec<&str> {
+        match self {
+            VpToken::Single(vp) => vec![vp],
+            VpToken::Multiple(vps) => vps.iter().map(String::as_str).collect(),
+            VpToken::ByQueryId(vps) => vps.values().map(String::as_str).collect(),
+        }
+    }
+
+    /// The presentation for a DCQL credential query id.
+    pub fn by_query_id(&self, id: &str) -> Option<&str> {
+        match self {
+            VpToken::ByQueryId(vps) => vps.get(id).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Whether any of the presentations is an encrypted JWE, see [VpToken::encrypt].
+    pub fn is_encrypted(&self) -> bool {
+        self.presentations().into_iter().any(is_jwe)
+    }
+
+    /// Encrypt each presentation as a compact JWE for the verifier.
+    ///
+    /// The recipient key is the first P-256 encryption key in `jwks`, and only the `ECDH-ES` key
+    /// management algorithm is supported.
+    #[cfg(feature = "p256")]
+    pub fn encrypt(self, jwks: &JWKs, enc: &str) -> Result<Self> {
+        let jwk = jwe::recipient_key(&jwks.keys)
+            .context("'jwks' does not contain a P-256 encryption key")?;
+        self.try_map(|vp| jwe::encrypt(vp.as_bytes(), jwk, enc))
+    }
+
+    /// Decrypt the presentations that are encrypted JWEs, see [VpToken::encrypt].
+    #[cfg(feature = "p256")]
+    pub fn decrypt(self, key: &p256::SecretKey) -> Result<Self> {
+        self.try_map(|vp| {
+            if !is_jwe(&vp) {
+                return Ok(vp);
+            }
+            String::from_utf8(jwe::decrypt(&vp, key)?)
+                .context("decrypted presentation is not valid UTF-8")
+        })
+    }
+
+    #[cfg(feature = "p256")]
+    fn try_map(self, mut f: impl FnMut(String) -> Result<String>) -> Result<Self> {
+        Ok(match self {
+            VpToken::Single(vp) => VpToken::Single(f(vp)?),
+            VpToken::Multiple(vps) => {
+                VpToken::Multiple(vps.into_iter().map(f).collect::<Result<_>>()?)
+            }
+            VpToken::ByQueryId(vps) => VpToken::ByQueryId(
+                vps.into_iter()
+                    .map(|(id, vp)| Ok((id, f(vp)?)))
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+/// Whether a presentation is a compact JWE, rather than a JWT, an SD-JWT or a JSON presentation.
+fn is_jwe(presentation: &str) -> bool {
+    !presentation.starts_with('{')
+        && !presentation.contains('~')
+        && presentation.split('.').count() == 5
+}
+
+impl From<String> for VpToken {
+    fn from(vp: String) -> Self {
+        Self::Single(vp)
+    }
+}
 
 impl TypedParameter for VpToken {
     const KEY: &'static str = "vp_token";
@@ -36,13 +132,42 @@ impl TryFrom<Json> for VpToken {
     type Error = Error;
 
     fn try_from(value: Json) -> Result<Self, Self::Error> {
-        serde_json::from_value(value).map(Self).map_err(Into::into)
+        let presentation = |value: Json| match value {
+            Json::String(presentation) => Ok(presentation),
+            presentation @ Json::Object(_) => Ok(presentation.to_string()),
+            _ => bail!("presentation was not a string or a JSON object"),
+        };
+
+        match value {
+            Json::Array(presentations) => Ok(Self::Multiple(
+                presentations
+                    .into_iter()
+                    .map(presentation)
+                    .collect::<Result<_, _>>()?,
+            )),
+            Json::Object(presentations) if !presentations.contains_key("@context") => {
+                Ok(Self::ByQueryId(
+                    presentations
+                        .into_iter()
+                        .map(|(id, value)| Ok((id, presentation(value)?)))
+                        .collect::<Result<_, Error>>()?,
+                ))
+            }
+            value => Ok(Self::Single(presentation(value)?)),
+        }
     }
 }
 
 impl From<VpToken> for Json {
     fn from(value: VpToken) -> Self {
-        value.0.into()
+        match value {
+            VpToken::Single(vp) => vp.into(),
+            VpToken::Multiple(vps) => vps.into(),
+            VpToken::ByQueryId(vps) => vps
+                .into_iter()
+                .map(|(id, vp)| (id, Json::String(vp)))
+                .collect(),
+        }
     }
 }
 
@@ -91,3 +216,94 @@ impl From<PresentationSubmission> for Json {
         value.raw
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn vp_token_single() {
+        let vp_token: VpToken = serde_json::from_value(json!(
+            "eyJhbGciOiJFUzI1NiIsInR5cCI6IkpXVCJ9.eyJ2cCI6e319.c2lnbmF0dXJl"
+        ))
+        .unwrap();
+        assert_eq!(
+            vp_token.presentations(),
+            ["eyJhbGciOiJFUzI1NiIsInR5cCI6IkpXVCJ9.eyJ2cCI6e319.c2lnbmF0dXJl"]
+        );
+
+        let vp_token: VpToken = serde_json::from_value(json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiablePresentation"],
+            "proof": { "challenge": "n-0S6_WzA2Mj" }
+        }))
+        .unwrap();
+        assert!(matches!(vp_token, VpToken::Single(_)));
+    }
+
+    #[cfg(feature = "p256")]
+    #[test]
+    fn vp_token_encryption() {
+        let key =
+            p256::SecretKey::from_jwk_str(include_str!("../../../tests/examples/encryption.jwk"))
+                .unwrap();
+        let jwks: JWKs = serde_json::from_value(json!({
+            "keys": [serde_json::from_str::<Json>(&key.public_key().to_jwk_string()).unwrap()]
+        }))
+        .unwrap();
+
+        let vp_token = VpToken::Multiple(vec![
+            "eyJhbGciOiJFUzI1NiJ9.eyJ2cCI6e319.c2lnbmF0dXJl".into(),
+            r#"{"@context":["https://www.w3.org/2018/credentials/v1"]}"#.into(),
+        ]);
+        assert!(!vp_token.is_encrypted());
+
+        let encrypted = vp_token.clone().encrypt(&jwks, "A256GCM").unwrap();
+        assert!(encrypted.is_encrypted());
+
+        let decrypted = encrypted.clone().decrypt(&key).unwrap();
+        assert_eq!(decrypted.presentations(), vp_token.presentations());
+
+        let other =
+            p256::SecretKey::from_jwk_str(include_str!("../../../tests/examples/verifier.jwk"))
+                .unwrap();
+        encrypted.decrypt(&other).unwrap_err();
+    }
+
+    #[test]
+    fn vp_token_multiple() {
+        let vp_token: VpToken = serde_json::from_value(json!([
+            "eyJhbGciOiJFUzI1NiJ9.eyJ2cCI6e319.c2lnbmF0dXJl",
+            "o2d2ZXJzaW9uYzEuMGlkb2N1bWVudHOA"
+        ]))
+        .unwrap();
+        assert_eq!(
+            vp_token.presentations(),
+            [
+                "eyJhbGciOiJFUzI1NiJ9.eyJ2cCI6e319.c2lnbmF0dXJl",
+                "o2d2ZXJzaW9uYzEuMGlkb2N1bWVudHOA"
+            ]
+        );
+    }
+
+    #[test]
+    fn vp_token_by_query_id() {
+        let json = json!({
+            "my_credential": "eyJhbGciOiJFUzI1NiJ9.eyJ2cCI6e319.c2lnbmF0dXJl",
+            "mdl": "o2d2ZXJzaW9uYzEuMGlkb2N1bWVudHOA"
+        });
+        let vp_token: VpToken = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(
+            vp_token.by_query_id("my_credential"),
+            Some("eyJhbGciOiJFUzI1NiJ9.eyJ2cCI6e319.c2lnbmF0dXJl")
+        );
+        assert_eq!(
+            vp_token.by_query_id("mdl"),
+            Some("o2d2ZXJzaW9uYzEuMGlkb2N1bWVudHOA")
+        );
+        assert_eq!(vp_token.presentations().len(), 2);
+        assert_eq!(serde_json::to_value(vp_token).unwrap(), json);
+    }
+}
@@ -1,25 +1,58 @@
-#[cfg(feature = "reqwest")]
-use anyhow::Context;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use http::{Request, Response};
+use http::{HeaderMap, Request, Response};
+use url::Url;
 
 /// Generic HTTP client.
 ///
 /// A trait is used here so to facilitate native HTTP/TLS when compiled for mobile applications.
-#[async_trait]
+/// When compiled for `wasm32`, the returned futures are not required to be `Send`, see
+/// `FetchClient` (behind the `wasm` feature).
+/// The wallet makes all of its requests through this trait, so any transport can be used by
+/// implementing [AsyncHttpClient::execute].
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait AsyncHttpClient {
     async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>>;
+
+    /// Make a `GET` request to `url`.
+    async fn get(&self, url: &Url) -> Result<Response<Vec<u8>>>
+    where
+        Self: Sync,
+    {
+        let request = base_request()
+            .method("GET")
+            .uri(url.as_str())
+            .body(vec![])
+            .context(format!("failed to build GET request to {url}"))?;
+        self.execute(request).await
+    }
+
+    /// Make a `POST` request to `url`, with the `body` and additional `headers`.
+    async fn post(&self, url: &Url, body: Vec<u8>, headers: HeaderMap) -> Result<Response<Vec<u8>>>
+    where
+        Self: Sync,
+    {
+        let mut builder = base_request().method("POST").uri(url.as_str());
+        builder
+            .headers_mut()
+            .context("unable to set headers")?
+            .extend(headers);
+        let request = builder
+            .body(body)
+            .context(format!("failed to build POST request to {url}"))?;
+        self.execute(request).await
+    }
 }
 
 pub(crate) fn base_request() -> http::request::Builder {
     Request::builder().header("Prefer", "OID4VP-0.0.20")
 }
 
-#[cfg(feature = "reqwest")]
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
 pub struct ReqwestClient(reqwest::Client);
 
-#[cfg(feature = "reqwest")]
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
 impl ReqwestClient {
     pub fn new() -> Result<Self> {
         reqwest::Client::builder()
@@ -30,7 +63,7 @@ impl ReqwestClient {
     }
 }
 
-#[cfg(feature = "reqwest")]
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
 #[async_trait]
 impl AsyncHttpClient for ReqwestClient {
     async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
@@ -66,13 +99,132 @@ impl AsyncHttpClient for ReqwestClient {
     }
 }
 
+/// An [AsyncHttpClient] using the `fetch` API of the browser or worker.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[derive(Debug, Clone, Default)]
+pub struct FetchClient;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[async_trait(?Send)]
+impl AsyncHttpClient for FetchClient {
+    async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+        use anyhow::anyhow;
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        let js_error = |e: wasm_bindgen::JsValue| anyhow!("{e:?}");
+
+        let headers = web_sys::Headers::new().map_err(js_error)?;
+        for (name, value) in request.headers() {
+            headers
+                .append(name.as_str(), value.to_str()?)
+                .map_err(js_error)?;
+        }
+
+        let init = web_sys::RequestInit::new();
+        init.set_method(request.method().as_str());
+        init.set_headers(&headers);
+        if !request.body().is_empty() {
+            init.set_body(&js_sys::Uint8Array::from(request.body().as_slice()));
+        }
+
+        let fetch_request =
+            web_sys::Request::new_with_str_and_init(&request.uri().to_string(), &init)
+                .map_err(js_error)
+                .context("unable to convert request")?;
+
+        let global = js_sys::global();
+        let promise = if let Some(window) = global.dyn_ref::<web_sys::Window>() {
+            window.fetch_with_request(&fetch_request)
+        } else {
+            global
+                .dyn_into::<web_sys::WorkerGlobalScope>()
+                .map_err(|_| anyhow!("fetch is not available in this environment"))?
+                .fetch_with_request(&fetch_request)
+        };
+
+        let response: web_sys::Response = JsFuture::from(promise)
+            .await
+            .map_err(js_error)
+            .context("http request failed")?
+            .dyn_into()
+            .map_err(js_error)?;
+
+        let mut builder = Response::builder().status(response.status());
+        for entry in js_sys::try_iter(response.headers().as_ref())
+            .map_err(js_error)?
+            .context("unable to iterate headers")?
+        {
+            let entry: js_sys::Array = entry.map_err(js_error)?.dyn_into().map_err(js_error)?;
+            if let (Some(name), Some(value)) = (entry.get(0).as_string(), entry.get(1).as_string())
+            {
+                builder = builder.header(name, value);
+            }
+        }
+
+        let body = JsFuture::from(response.array_buffer().map_err(js_error)?)
+            .await
+            .map_err(js_error)
+            .context("failed to extract response body")?;
+
+        builder
+            .body(js_sys::Uint8Array::new(&body).to_vec())
+            .context("unable to construct response")
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use http::Response;
+    use std::sync::Mutex;
+
+    use http::{header::CONTENT_TYPE, HeaderValue, Response};
+
+    use super::*;
 
     #[test]
     fn debug() {
         Response::builder().extensions_mut().unwrap();
         Response::builder().headers_mut().unwrap();
     }
+
+    #[derive(Default)]
+    struct RecordingHttpClient(Mutex<Vec<Request<Vec<u8>>>>);
+
+    #[async_trait]
+    impl AsyncHttpClient for RecordingHttpClient {
+        async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+            self.0.lock().unwrap().push(request);
+            Ok(Response::builder().status(204).body(vec![])?)
+        }
+    }
+
+    #[tokio::test]
+    async fn get_and_post() {
+        let client = RecordingHttpClient::default();
+        let url: Url = "https://example.com/path".parse().unwrap();
+
+        let response = client.get(&url).await.unwrap();
+        assert_eq!(response.status(), 204);
+
+        let headers = HeaderMap::from_iter([(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        )]);
+        client.post(&url, b"a=b".to_vec(), headers).await.unwrap();
+
+        let requests = client.0.lock().unwrap();
+        let [get, post] = &requests[..] else {
+            panic!("expected two requests")
+        };
+        assert_eq!(get.method(), "GET");
+        assert_eq!(get.uri(), "https://example.com/path");
+        assert_eq!(get.headers()["Prefer"], "OID4VP-0.0.20");
+        assert_eq!(post.method(), "POST");
+        assert_eq!(post.body(), b"a=b");
+        assert_eq!(
+            post.headers()[CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(post.headers()["Prefer"], "OID4VP-0.0.20");
+    }
 }
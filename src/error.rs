@@ -0,0 +1,22 @@
+/// An error returned by the wallet and presentation exchange APIs, classified by failure mode.
+///
+/// Each variant wraps the underlying error, which is included when it is displayed.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The authorization request could not be parsed or validated.
+    #[error("invalid authorization request: {0:#}")]
+    InvalidRequest(anyhow::Error),
+    /// The authorization response does not satisfy the presentation definition.
+    #[error("invalid authorization response: {0:#}")]
+    InvalidResponse(anyhow::Error),
+    /// A resource referenced by the request, such as the request object, could not be fetched.
+    #[error("unable to resolve resource: {0:#}")]
+    Resolution(anyhow::Error),
+    /// A JWT could not be decoded.
+    #[error("unable to decode JWT: {0:#}")]
+    JwtDecode(anyhow::Error),
+    /// A presented credential does not satisfy the constraints, such as the JSON Schema filters,
+    /// of an input descriptor.
+    #[error("constraints not satisfied: {0:#}")]
+    ConstraintsNotSatisfied(anyhow::Error),
+}
@@ -0,0 +1,1051 @@
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use url::Url;
+
+/// A JSON Schema, as used for the `filter` of a constraint field.
+///
+/// Supports the subset of JSON Schema keywords that is used to constrain credential claims, and
+/// annotations such as `title` and `description`. A schema with any other keyword is invalid,
+/// so that a filter never accepts values that its author meant to reject.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SchemaValidator {
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    schema: Option<String>,
+    #[serde(rename = "$id", skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(rename = "$comment", skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: Option<Json>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    examples: Option<Vec<Json>>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    schema_type: Option<SchemaType>,
+    #[serde(rename = "const", skip_serializing_if = "Option::is_none")]
+    const_value: Option<Json>,
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    enum_values: Option<Vec<Json>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pattern: Option<Pattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Format>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format_minimum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format_maximum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format_exclusive_minimum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format_exclusive_maximum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maximum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exclusive_minimum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exclusive_maximum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    multiple_of: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<Box<SchemaValidator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contains: Option<Box<SchemaValidator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_items: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_items: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unique_items: Option<bool>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<String, SchemaValidator>,
+    #[serde(default, skip_serializing_if = "PatternProperties::is_empty")]
+    pattern_properties: PatternProperties,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    additional_properties: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    required: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all_of: Option<Vec<SchemaValidator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    any_of: Option<Vec<SchemaValidator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    one_of: Option<Vec<SchemaValidator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    not: Option<Box<SchemaValidator>>,
+}
+
+/// A regular expression of a `pattern` or `patternProperties` keyword, compiled when the schema is
+/// parsed.
+#[derive(Clone, Debug)]
+struct Pattern(Regex);
+
+impl Serialize for Pattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map(Self).map_err(|e| {
+            serde::de::Error::custom(format!("invalid regular expression '{pattern}': {e}"))
+        })
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// The `patternProperties` keyword, with its patterns compiled and ordered by their source.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct PatternProperties(Vec<(Pattern, SchemaValidator)>);
+
+impl PatternProperties {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for PatternProperties {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|(Pattern(regex), schema)| (regex.as_str(), schema)),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for PatternProperties {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BTreeMap::<String, SchemaValidator>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(pattern, schema)| {
+                let regex = Regex::new(&pattern).map_err(|e| {
+                    serde::de::Error::custom(format!(
+                        "invalid 'patternProperties' key '{pattern}': {e}"
+                    ))
+                })?;
+                Ok((Pattern(regex), schema))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// The maximum nesting depth of a value that [SchemaValidator::validate] descends into.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// The relative tolerance of a `multipleOf` comparison.
+const MULTIPLE_OF_TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+    Null,
+}
+
+/// A `format` of string values, which is validated rather than only an annotation.
+///
+/// A schema with any other format is invalid, as its values could not be checked.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// An RFC 3339 `full-date`, such as `2000-01-31`.
+    Date,
+    /// An RFC 3339 `date-time`, such as `2000-01-31T12:00:00Z`.
+    DateTime,
+    Email,
+    Uri,
+    Uuid,
+}
+
+impl SchemaValidator {
+    /// Parse a JSON Schema object, such as the `filter` of a constraint field.
+    ///
+    /// Fails if the schema has an unsupported keyword, if a supported keyword has an invalid
+    /// value, including a `pattern` that is not a valid regular expression, or if the schema is nested more than [DEFAULT_MAX_DEPTH] levels
+    /// deep. Patterns are compiled once, so the validator should be reused.
+    pub fn from_value(schema: &Json) -> Result<Self> {
+        if exceeds_depth(schema, DEFAULT_MAX_DEPTH) {
+            bail!("invalid JSON Schema: nested more than the maximum depth of {DEFAULT_MAX_DEPTH}")
+        }
+        serde_json::from_value(schema.clone()).context("invalid JSON Schema")
+    }
+
+    /// Set the `format` that string values must have.
+    pub fn set_format(&mut self, format: Format) {
+        self.format = Some(format)
+    }
+
+    /// Set whether objects may have properties that are neither in `properties` nor match
+    /// `patternProperties`. They may by default.
+    pub fn set_additional_properties(&mut self, additional_properties: bool) {
+        self.additional_properties = Some(additional_properties)
+    }
+
+    /// Require values to match all of the `schemas`.
+    pub fn set_all_of(&mut self, schemas: Vec<SchemaValidator>) {
+        self.all_of = Some(schemas)
+    }
+
+    /// Require values to match at least one of the `schemas`.
+    pub fn set_any_of(&mut self, schemas: Vec<SchemaValidator>) {
+        self.any_of = Some(schemas)
+    }
+
+    /// Require values to match exactly one of the `schemas`.
+    pub fn set_one_of(&mut self, schemas: Vec<SchemaValidator>) {
+        self.one_of = Some(schemas)
+    }
+
+    /// Validate a value against this schema, descending at most [DEFAULT_MAX_DEPTH] levels.
+    pub fn validate(&self, value: &Json) -> Result<()> {
+        self.validate_with_max_depth(value, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Validate a value against this schema, failing if the schema is applied to array items or
+    /// object properties nested more than `max_depth` levels deep.
+    pub fn validate_with_max_depth(&self, value: &Json, max_depth: usize) -> Result<()> {
+        self.validate_at(value, 0, max_depth)
+    }
+
+    fn validate_at(&self, value: &Json, depth: usize, max_depth: usize) -> Result<()> {
+        if depth > max_depth {
+            bail!("value is nested more than the maximum depth of {max_depth}")
+        }
+
+        if let Some(schema_type) = self.schema_type {
+            if !schema_type.matches(value) {
+                bail!("value {value} is not of type '{schema_type:?}'")
+            }
+        }
+
+        if let Some(const_value) = &self.const_value {
+            if const_value != value {
+                bail!("value {value} is not equal to {const_value}")
+            }
+        }
+
+        if let Some(enum_values) = &self.enum_values {
+            if !enum_values.contains(value) {
+                bail!(
+                    "value {value} is not one of {}",
+                    Json::from(enum_values.clone())
+                )
+            }
+        }
+
+        if let Some(all_of) = &self.all_of {
+            for (i, schema) in all_of.iter().enumerate() {
+                schema
+                    .validate_at(value, depth, max_depth)
+                    .context(format!("value {value} does not match 'allOf' schema {i}"))?;
+            }
+        }
+
+        if let Some(any_of) = &self.any_of {
+            let results = validate_each(any_of, value, depth, max_depth);
+            if results.iter().all(Result::is_err) {
+                bail!(
+                    "value {value} matches none of the 'anyOf' schemas: {}",
+                    describe_failures(&results)
+                )
+            }
+        }
+
+        if let Some(one_of) = &self.one_of {
+            let results = validate_each(one_of, value, depth, max_depth);
+            let matched: Vec<usize> = (0..results.len()).filter(|&i| results[i].is_ok()).collect();
+            match matched.len() {
+                1 => (),
+                0 => bail!(
+                    "value {value} matches none of the 'oneOf' schemas: {}",
+                    describe_failures(&results)
+                ),
+                _ => {
+                    bail!("value {value} matches more than one of the 'oneOf' schemas: {matched:?}")
+                }
+            }
+        }
+
+        if let Some(not) = &self.not {
+            if not.validate_at(value, depth, max_depth).is_ok() {
+                bail!("value {value} matches the 'not' schema")
+            }
+        }
+
+        match value {
+            Json::String(s) => self.validate_string(s),
+            Json::Number(n) => match n.as_f64() {
+                Some(n) => self.validate_number(n),
+                None => Ok(()),
+            },
+            Json::Array(array) => self.validate_array(array, depth, max_depth),
+            Json::Object(object) => self.validate_object(object, depth, max_depth),
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_string(&self, s: &str) -> Result<()> {
+        let length = s.chars().count();
+
+        if let Some(min_length) = self.min_length {
+            if length < min_length {
+                bail!("string '{s}' is shorter than {min_length} characters")
+            }
+        }
+
+        if let Some(max_length) = self.max_length {
+            if length > max_length {
+                bail!("string '{s}' is longer than {max_length} characters")
+            }
+        }
+
+        if let Some(Pattern(regex)) = &self.pattern {
+            if !regex.is_match(s) {
+                bail!("string '{s}' does not match pattern '{}'", regex.as_str())
+            }
+        }
+
+        if let Some(format) = self.format {
+            if !format.is_valid(s) {
+                bail!("string '{s}' is not a valid '{format:?}'")
+            }
+        }
+
+        for (keyword, bound, satisfied) in [
+            (
+                "formatMinimum",
+                &self.format_minimum,
+                f64::ge as fn(&f64, &f64) -> bool,
+            ),
+            ("formatMaximum", &self.format_maximum, f64::le),
+            (
+                "formatExclusiveMinimum",
+                &self.format_exclusive_minimum,
+                f64::gt,
+            ),
+            (
+                "formatExclusiveMaximum",
+                &self.format_exclusive_maximum,
+                f64::lt,
+            ),
+        ] {
+            let Some(bound) = bound else {
+                continue;
+            };
+            let instant = |s: &str| {
+                self.format.and_then(|format| format.instant(s)).context(format!(
+                    "'{keyword}' requires a 'date' or 'date-time' format and a valid bound, not '{bound}'"
+                ))
+            };
+            if !satisfied(&instant(s)?, &instant(bound)?) {
+                bail!("string '{s}' does not satisfy '{keyword}' '{bound}'")
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_number(&self, n: f64) -> Result<()> {
+        if let Some(minimum) = self.minimum {
+            if n < minimum {
+                bail!("number {n} is less than the minimum {minimum}")
+            }
+        }
+
+        if let Some(maximum) = self.maximum {
+            if n > maximum {
+                bail!("number {n} is greater than the maximum {maximum}")
+            }
+        }
+
+        if let Some(exclusive_minimum) = self.exclusive_minimum {
+            if n <= exclusive_minimum {
+                bail!("number {n} is not greater than {exclusive_minimum}")
+            }
+        }
+
+        if let Some(exclusive_maximum) = self.exclusive_maximum {
+            if n >= exclusive_maximum {
+                bail!("number {n} is not less than {exclusive_maximum}")
+            }
+        }
+
+        if let Some(multiple_of) = self.multiple_of {
+            if multiple_of <= 0.0 {
+                bail!("'multipleOf' must be greater than 0, not {multiple_of}")
+            }
+            // The remainder of a division is inexact for fractions such as `0.1`, so the nearest
+            // multiple is compared instead, within a tolerance relative to the number.
+            let nearest = (n / multiple_of).round() * multiple_of;
+            if (nearest - n).abs() > MULTIPLE_OF_TOLERANCE * n.abs().max(1.0) {
+                bail!("number {n} is not a multiple of {multiple_of}")
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_array(&self, array: &[Json], depth: usize, max_depth: usize) -> Result<()> {
+        if let Some(min_items) = self.min_items {
+            if array.len() < min_items {
+                bail!("array has fewer than {min_items} items")
+            }
+        }
+
+        if let Some(max_items) = self.max_items {
+            if array.len() > max_items {
+                bail!("array has more than {max_items} items")
+            }
+        }
+
+        if self.unique_items == Some(true) {
+            for (i, item) in array.iter().enumerate() {
+                if array[..i].contains(item) {
+                    bail!("array item {i} ({item}) is a duplicate")
+                }
+            }
+        }
+
+        if let Some(items) = &self.items {
+            for (i, item) in array.iter().enumerate() {
+                items
+                    .validate_at(item, depth + 1, max_depth)
+                    .context(format!("invalid array item {i}"))?;
+            }
+        }
+
+        if let Some(contains) = &self.contains {
+            if !array
+                .iter()
+                .any(|item| contains.validate_at(item, depth + 1, max_depth).is_ok())
+            {
+                bail!("no array item matches the 'contains' schema")
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_object(
+        &self,
+        object: &serde_json::Map<String, Json>,
+        depth: usize,
+        max_depth: usize,
+    ) -> Result<()> {
+        for name in &self.required {
+            if !object.contains_key(name) {
+                bail!("required property '{name}' is missing")
+            }
+        }
+
+        for (name, schema) in &self.properties {
+            if let Some(value) = object.get(name) {
+                schema
+                    .validate_at(value, depth + 1, max_depth)
+                    .context(format!("invalid property '{name}'"))?;
+            }
+        }
+
+        for (Pattern(regex), schema) in &self.pattern_properties.0 {
+            for (name, value) in object.iter().filter(|(name, _)| regex.is_match(name)) {
+                schema
+                    .validate_at(value, depth + 1, max_depth)
+                    .context(format!("invalid property '{name}'"))?;
+            }
+        }
+
+        if self.additional_properties == Some(false) {
+            if let Some(name) = object.keys().find(|name| {
+                !self.properties.contains_key(*name)
+                    && !self
+                        .pattern_properties
+                        .0
+                        .iter()
+                        .any(|(Pattern(regex), _)| regex.is_match(name))
+            }) {
+                bail!("property '{name}' is not allowed")
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of validating a value against each schema of an `anyOf` or `oneOf` keyword.
+fn validate_each(
+    schemas: &[SchemaValidator],
+    value: &Json,
+    depth: usize,
+    max_depth: usize,
+) -> Vec<Result<()>> {
+    schemas
+        .iter()
+        .map(|schema| schema.validate_at(value, depth, max_depth))
+        .collect()
+}
+
+/// The failures of the schemas that a value did not match, by the index of each schema.
+fn describe_failures(results: &[Result<()>]) -> String {
+    results
+        .iter()
+        .enumerate()
+        .filter_map(|(i, result)| Some(format!("schema {i}: {:#}", result.as_ref().err()?)))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Whether arrays and objects are nested more than `max_depth` levels deep in a value, checked
+/// without recursion so that it is safe for values of any depth.
+fn exceeds_depth(value: &Json, max_depth: usize) -> bool {
+    let mut stack = vec![(value, 1)];
+    while let Some((value, depth)) = stack.pop() {
+        let children: Box<dyn Iterator<Item = &Json>> = match value {
+            Json::Array(array) => Box::new(array.iter()),
+            Json::Object(object) => Box::new(object.values()),
+            _ => continue,
+        };
+        if depth > max_depth {
+            return true;
+        }
+        stack.extend(children.map(|value| (value, depth + 1)));
+    }
+    false
+}
+
+impl Format {
+    fn is_valid(self, s: &str) -> bool {
+        match self {
+            Self::Date | Self::DateTime => self.instant(s).is_some(),
+            Self::Email => match s.split_once('@') {
+                Some((local, domain)) => {
+                    !local.is_empty()
+                        && !domain.is_empty()
+                        && !domain.contains('@')
+                        && !s.contains(char::is_whitespace)
+                }
+                None => false,
+            },
+            Self::Uri => Url::parse(s).is_ok(),
+            Self::Uuid => {
+                s.len() == 36
+                    && s.char_indices().all(|(i, c)| match i {
+                        8 | 13 | 18 | 23 => c == '-',
+                        _ => c.is_ascii_hexdigit(),
+                    })
+            }
+        }
+    }
+
+    /// The seconds since the Unix epoch of a `date` or `date-time`, or `None` if the string is not
+    /// valid or the format has no order.
+    fn instant(self, s: &str) -> Option<f64> {
+        static DATE_TIME: OnceLock<Regex> = OnceLock::new();
+
+        match self {
+            Self::Date => full_date(s).map(|days| days as f64 * 86_400.0),
+            Self::DateTime => {
+                let captures = DATE_TIME
+                    .get_or_init(|| {
+                        Regex::new(concat!(
+                            r"^([0-9]{4}-[0-9]{2}-[0-9]{2})[Tt]([0-9]{2}):([0-9]{2}):",
+                            r"([0-9]{2}(?:\.[0-9]+)?)(?:[Zz]|([+-])([0-9]{2}):([0-9]{2}))$"
+                        ))
+                        .unwrap()
+                    })
+                    .captures(s)?;
+                let number = |i: usize| captures[i].parse::<u32>().ok();
+                let days = full_date(&captures[1])?;
+                let (hour, minute) = (number(2)?, number(3)?);
+                // Allow for a leap second.
+                let second: f64 = captures[4].parse().ok()?;
+                if hour > 23 || minute > 59 || second >= 61.0 {
+                    return None;
+                }
+                let offset = match captures.get(5) {
+                    Some(sign) => {
+                        let (hours, minutes) = (number(6)?, number(7)?);
+                        if hours > 23 || minutes > 59 {
+                            return None;
+                        }
+                        let offset = f64::from(hours * 3600 + minutes * 60);
+                        if sign.as_str() == "-" {
+                            -offset
+                        } else {
+                            offset
+                        }
+                    }
+                    None => 0.0,
+                };
+                Some(
+                    days as f64 * 86_400.0 + f64::from(hour * 3600 + minute * 60) + second - offset,
+                )
+            }
+            Self::Email | Self::Uri | Self::Uuid => None,
+        }
+    }
+}
+
+/// The days since the Unix epoch of an RFC 3339 `full-date`, or `None` if it is not valid.
+fn full_date(s: &str) -> Option<i64> {
+    let number = |range: std::ops::Range<usize>| {
+        s.get(range)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))?
+            .parse::<i64>()
+            .ok()
+    };
+    if s.len() != 10 || s.as_bytes()[4] != b'-' || s.as_bytes()[7] != b'-' {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap_year => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+
+    // The civil calendar algorithm of http://howardhinnant.github.io/date_algorithms.html, with
+    // years starting in March.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+impl SchemaType {
+    fn matches(self, value: &Json) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Number => value.is_number(),
+            // Numbers with a zero fractional part, such as `5.0`, are integers in JSON Schema.
+            Self::Integer => {
+                value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            Self::Boolean => value.is_boolean(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+            Self::Null => value.is_null(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn string_constraints() {
+        let validator = SchemaValidator::from_value(&json!({
+            "type": "string",
+            "minLength": 2,
+            "maxLength": 4,
+            "pattern": "^a"
+        }))
+        .unwrap();
+
+        validator.validate(&json!("ab")).unwrap();
+        validator.validate(&json!("abcd")).unwrap();
+        assert!(validator.validate(&json!("a")).is_err());
+        assert!(validator.validate(&json!("abcde")).is_err());
+        assert!(validator.validate(&json!("ba")).is_err());
+        assert!(validator.validate(&json!(12)).is_err());
+    }
+
+    #[test]
+    fn formats() {
+        let date =
+            SchemaValidator::from_value(&json!({ "type": "string", "format": "date" })).unwrap();
+        date.validate(&json!("2000-02-29")).unwrap();
+        assert!(date.validate(&json!("1900-02-29")).is_err());
+        assert!(date.validate(&json!("2000-13-01")).is_err());
+        assert!(date.validate(&json!("2000-1-1")).is_err());
+
+        let date_time = SchemaValidator::from_value(&json!({ "format": "date-time" })).unwrap();
+        date_time
+            .validate(&json!("2000-01-31T12:00:00.5+01:00"))
+            .unwrap();
+        assert!(date_time.validate(&json!("2000-01-31T24:00:00Z")).is_err());
+        assert!(date_time.validate(&json!("2000-01-31")).is_err());
+
+        let email = SchemaValidator::from_value(&json!({ "format": "email" })).unwrap();
+        email.validate(&json!("alice@example.com")).unwrap();
+        assert!(email.validate(&json!("alice.example.com")).is_err());
+        assert!(email.validate(&json!("alice@@example.com")).is_err());
+
+        let uri = SchemaValidator::from_value(&json!({ "format": "uri" })).unwrap();
+        uri.validate(&json!("https://example.com/a")).unwrap();
+        assert!(uri.validate(&json!("/a")).is_err());
+
+        let uuid = SchemaValidator::from_value(&json!({ "format": "uuid" })).unwrap();
+        uuid.validate(&json!("0b4dd017-efa6-4a05-a269-9790fa3c22c2"))
+            .unwrap();
+        assert!(uuid
+            .validate(&json!("0b4dd017efa64a05a2699790fa3c22c2"))
+            .is_err());
+
+        assert!(SchemaValidator::from_value(&json!({ "format": "ipv4" })).is_err());
+    }
+
+    #[test]
+    fn format_bounds() {
+        let validator = SchemaValidator::from_value(&json!({
+            "type": "string",
+            "format": "date",
+            "formatMaximum": "2006-01-01"
+        }))
+        .unwrap();
+
+        validator.validate(&json!("1999-05-16")).unwrap();
+        validator.validate(&json!("2006-01-01")).unwrap();
+        assert!(validator.validate(&json!("2006-01-02")).is_err());
+
+        let validator = SchemaValidator::from_value(&json!({
+            "format": "date-time",
+            "formatExclusiveMinimum": "2000-01-01T00:00:00Z"
+        }))
+        .unwrap();
+
+        validator.validate(&json!("2000-01-01T00:00:01Z")).unwrap();
+        assert!(validator.validate(&json!("2000-01-01T00:00:00Z")).is_err());
+        // The same instant with an offset.
+        assert!(validator
+            .validate(&json!("2000-01-01T01:00:00+01:00"))
+            .is_err());
+
+        for schema in [
+            json!({ "formatMinimum": "2000-01-01" }),
+            json!({ "format": "email", "formatMinimum": "a@example.com" }),
+            json!({ "format": "date", "formatMinimum": "yesterday" }),
+        ] {
+            let validator = SchemaValidator::from_value(&schema).unwrap();
+            assert!(validator.validate(&json!("2000-01-01")).is_err());
+        }
+    }
+
+    #[test]
+    fn integer_type() {
+        let validator = SchemaValidator::from_value(&json!({ "type": "integer" })).unwrap();
+
+        validator.validate(&json!(5)).unwrap();
+        validator.validate(&json!(-5)).unwrap();
+        validator.validate(&json!(5.0)).unwrap();
+        assert!(validator.validate(&json!(5.5)).is_err());
+        assert!(validator.validate(&json!("5")).is_err());
+    }
+
+    #[test]
+    fn multiple_of() {
+        let validator = SchemaValidator::from_value(&json!({ "multipleOf": 0.1 })).unwrap();
+
+        validator.validate(&json!(0.3)).unwrap();
+        validator.validate(&json!(-1.2)).unwrap();
+        validator.validate(&json!(7)).unwrap();
+        assert!(validator.validate(&json!(0.35)).is_err());
+
+        let validator = SchemaValidator::from_value(&json!({ "multipleOf": 5 })).unwrap();
+        validator.validate(&json!(1_000_000_000_000_u64)).unwrap();
+        assert!(validator.validate(&json!(12)).is_err());
+
+        assert!(SchemaValidator::from_value(&json!({ "multipleOf": 0 }))
+            .unwrap()
+            .validate(&json!(1))
+            .is_err());
+    }
+
+    #[test]
+    fn composition() {
+        let any_of = SchemaValidator::from_value(&json!({
+            "anyOf": [{ "pattern": "^A" }, { "pattern": "^B" }]
+        }))
+        .unwrap();
+        any_of.validate(&json!("Alice")).unwrap();
+        any_of.validate(&json!("Bob")).unwrap();
+        let error = any_of.validate(&json!("Carol")).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("schema 0") && format!("{error:#}").contains("schema 1"),
+            "{error:#}"
+        );
+
+        let all_of = SchemaValidator::from_value(&json!({
+            "allOf": [{ "type": "string" }, { "maxLength": 3 }]
+        }))
+        .unwrap();
+        all_of.validate(&json!("Bob")).unwrap();
+        let error = all_of.validate(&json!("Alice")).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("'allOf' schema 1"),
+            "{error:#}"
+        );
+
+        let one_of = SchemaValidator::from_value(&json!({
+            "oneOf": [{ "pattern": "^A" }, { "maxLength": 3 }]
+        }))
+        .unwrap();
+        one_of.validate(&json!("Alice")).unwrap();
+        one_of.validate(&json!("Bob")).unwrap();
+        assert!(one_of.validate(&json!("Carol")).is_err());
+        let error = one_of.validate(&json!("Al")).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("more than one of the 'oneOf' schemas: [0, 1]"),
+            "{error:#}"
+        );
+    }
+
+    #[test]
+    fn nested_constraints() {
+        let validator = SchemaValidator::from_value(&json!({
+            "type": "object",
+            "required": ["degree"],
+            "properties": {
+                "degree": { "enum": ["Bachelor", "Master"] },
+                "scores": { "items": { "type": "number", "minimum": 0, "maximum": 100 } }
+            }
+        }))
+        .unwrap();
+
+        validator
+            .validate(&json!({ "degree": "Master", "scores": [0, 100] }))
+            .unwrap();
+        assert!(validator.validate(&json!({ "degree": "PhD" })).is_err());
+        assert!(validator
+            .validate(&json!({ "degree": "Master", "scores": [101] }))
+            .is_err());
+        assert!(validator.validate(&json!({})).is_err());
+    }
+
+    #[test]
+    fn array_length() {
+        let validator = SchemaValidator::from_value(&json!({
+            "minItems": 2,
+            "maxItems": 3,
+            "minLength": 5
+        }))
+        .unwrap();
+
+        validator.validate(&json!([1, 2])).unwrap();
+        validator.validate(&json!([1, 2, 3])).unwrap();
+        assert!(validator.validate(&json!([1])).is_err());
+        assert!(validator.validate(&json!([1, 2, 3, 4])).is_err());
+        assert!(validator.validate(&json!("abc")).is_err());
+    }
+
+    #[test]
+    fn unique_items() {
+        let validator = SchemaValidator::from_value(&json!({ "uniqueItems": true })).unwrap();
+
+        validator.validate(&json!(["a", "b", { "c": 1 }])).unwrap();
+        assert!(validator.validate(&json!(["a", "b", "a"])).is_err());
+        assert!(validator
+            .validate(&json!([{ "c": 1 }, { "c": 1 }]))
+            .is_err());
+
+        SchemaValidator::from_value(&json!({ "uniqueItems": false }))
+            .unwrap()
+            .validate(&json!(["a", "a"]))
+            .unwrap();
+    }
+
+    #[test]
+    fn contains() {
+        let validator = SchemaValidator::from_value(&json!({
+            "type": "array",
+            "contains": { "const": "UniversityDegreeCredential" }
+        }))
+        .unwrap();
+
+        validator
+            .validate(&json!([
+                "VerifiableCredential",
+                "UniversityDegreeCredential"
+            ]))
+            .unwrap();
+        assert!(validator
+            .validate(&json!(["VerifiableCredential", "IDCardCredential"]))
+            .is_err());
+        assert!(validator.validate(&json!([])).is_err());
+
+        let validator = SchemaValidator::from_value(&json!({
+            "items": { "type": "string", "pattern": "Credential$" },
+            "contains": { "const": "UniversityDegreeCredential" }
+        }))
+        .unwrap();
+
+        validator
+            .validate(&json!([
+                "VerifiableCredential",
+                "UniversityDegreeCredential"
+            ]))
+            .unwrap();
+        assert!(validator
+            .validate(&json!(["UniversityDegreeCredential", "Diploma"]))
+            .is_err());
+        assert!(validator
+            .validate(&json!(["VerifiableCredential"]))
+            .is_err());
+    }
+
+    #[test]
+    fn pattern_properties() {
+        let validator = SchemaValidator::from_value(&json!({
+            "patternProperties": {
+                "^x-": { "type": "string" }
+            }
+        }))
+        .unwrap();
+
+        validator
+            .validate(&json!({ "x-a": "a", "x-b": "b", "other": 1 }))
+            .unwrap();
+        assert!(validator
+            .validate(&json!({ "x-a": "a", "x-b": 1 }))
+            .is_err());
+    }
+
+    #[test]
+    fn additional_properties() {
+        let mut schema = json!({
+            "properties": { "name": { "type": "string" } },
+            "patternProperties": { "^x-": {} }
+        });
+        let value = json!({ "name": "Alice", "x-a": 1, "age": 30 });
+
+        SchemaValidator::from_value(&schema)
+            .unwrap()
+            .validate(&value)
+            .unwrap();
+
+        schema["additionalProperties"] = json!(false);
+        let validator = SchemaValidator::from_value(&schema).unwrap();
+        let error = validator.validate(&value).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("property 'age' is not allowed"),
+            "{error:#}"
+        );
+        validator
+            .validate(&json!({ "name": "Alice", "x-a": 1 }))
+            .unwrap();
+    }
+
+    #[test]
+    fn max_depth() {
+        let (mut schema, mut value) = (json!({ "type": "string" }), json!("a"));
+        for _ in 0..DEFAULT_MAX_DEPTH - 1 {
+            schema = json!({ "items": schema });
+            value = json!([value]);
+        }
+        let validator = SchemaValidator::from_value(&schema).unwrap();
+
+        validator.validate(&value).unwrap();
+        let error = validator.validate_with_max_depth(&value, 2).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("maximum depth of 2"),
+            "{error:#}"
+        );
+        validator.validate_with_max_depth(&json!(["a"]), 1).unwrap();
+        assert!(validator
+            .validate_with_max_depth(&json!([["a"]]), 1)
+            .is_err());
+    }
+
+    #[test]
+    fn schema_max_depth() {
+        let mut schema = json!({ "type": "string" });
+        for _ in 0..DEFAULT_MAX_DEPTH {
+            schema = json!({ "items": schema });
+        }
+        let error = SchemaValidator::from_value(&schema).unwrap_err();
+        assert!(
+            format!("{error:#}").contains("maximum depth of 64"),
+            "{error:#}"
+        );
+
+        let mut schema = json!({ "const": "a" });
+        for _ in 0..10 * DEFAULT_MAX_DEPTH {
+            schema = json!({ "const": [schema] });
+        }
+        assert!(SchemaValidator::from_value(&schema).is_err());
+    }
+
+    #[test]
+    fn not() {
+        let validator = SchemaValidator::from_value(&json!({
+            "type": "string",
+            "not": { "enum": ["revoked", "suspended"] }
+        }))
+        .unwrap();
+
+        validator.validate(&json!("valid")).unwrap();
+        assert!(validator.validate(&json!("revoked")).is_err());
+    }
+
+    #[test]
+    fn unknown_keywords() {
+        SchemaValidator::from_value(&json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$comment": "The degree of the holder",
+            "title": "Degree",
+            "description": "A bachelor degree",
+            "examples": ["BachelorDegree"],
+            "const": "BachelorDegree"
+        }))
+        .unwrap();
+
+        for schema in [
+            json!({ "typ": "string" }),
+            json!({ "$ref": "#/$defs/degree" }),
+            json!({ "if": { "const": "a" }, "then": { "minLength": 2 } }),
+            json!({ "items": { "dependentRequired": { "a": ["b"] } } }),
+            json!({ "additionalProperties": { "type": "string" } }),
+        ] {
+            let error = SchemaValidator::from_value(&schema).unwrap_err();
+            assert!(
+                format!("{error:#}").contains("unknown field")
+                    || format!("{error:#}").contains("invalid type"),
+                "{error:#}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(SchemaValidator::from_value(&json!({ "pattern": "(" })).is_err());
+        assert!(SchemaValidator::from_value(&json!({ "patternProperties": { "(": {} } })).is_err());
+    }
+}
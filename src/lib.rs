@@ -1,5 +1,9 @@
 pub mod core;
+mod error;
+pub mod json_schema_validation;
 pub mod presentation_exchange;
 mod utils;
 pub mod verifier;
 pub mod wallet;
+
+pub use error::Error;
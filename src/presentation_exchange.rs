@@ -1,6 +1,25 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    sync::OnceLock,
+};
+
 pub use crate::utils::NonEmptyVec;
+use anyhow::{bail, Context, Result};
+use base64::prelude::*;
 use serde::{Deserialize, Serialize};
-use serde_json::Map;
+use serde_json::{Map, Value as Json};
+use serde_json_path::JsonPath;
+use uuid::Uuid;
+
+use crate::core::{
+    credential_format::{
+        sd_jwt::SdJwtPresentation, CredentialFormat, DcSdJwt, JwtVc, LdpVc, LdpVp, MsoMdoc, VcSdJwt,
+    },
+    response::{AuthorizationResponse, UnencodedAuthorizationResponse},
+};
+use crate::json_schema_validation::SchemaValidator;
+use crate::Error;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PresentationDefinition {
@@ -11,7 +30,623 @@ pub struct PresentationDefinition {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub purpose: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub format: Option<serde_json::Value>, // TODO
+    pub format: Option<ClaimFormatMap>,
+    /// The combinations of input descriptors that may be submitted. Without submission
+    /// requirements, every input descriptor must be submitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submission_requirements: Option<Vec<SubmissionRequirement>>,
+    /// Extension properties, preserved when the definition is serialized again. Localized
+    /// names and purposes are kept here, see [PresentationDefinition::name_for_locale].
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+impl PresentationDefinition {
+    /// Parse a presentation definition from JSON, and validate its structure.
+    ///
+    /// See [PresentationDefinition::validate_structure].
+    pub fn from_json(json: &str) -> Result<Self> {
+        let presentation_definition: Self =
+            serde_json::from_str(json).context("unable to parse presentation definition")?;
+        presentation_definition.validate_structure()?;
+        Ok(presentation_definition)
+    }
+
+    /// The `name` for a locale, given as a `name#<locale>` property such as `name#fr`, falling
+    /// back to the default `name`.
+    pub fn name_for_locale(&self, locale: &str) -> Option<&str> {
+        localized(&self.extra, "name", locale).or(self.name.as_deref())
+    }
+
+    /// The `purpose` for a locale, given as a `purpose#<locale>` property such as `purpose#fr`,
+    /// falling back to the default `purpose`.
+    pub fn purpose_for_locale(&self, locale: &str) -> Option<&str> {
+        localized(&self.extra, "purpose", locale).or(self.purpose.as_deref())
+    }
+
+    /// Validate the structure of this presentation definition.
+    ///
+    /// The `id` must not be empty, and there must be at least one input descriptor, each with a
+    /// non-empty and unique `id` and valid constraints (see [Constraints::validate_structure]).
+    pub fn validate_structure(&self) -> Result<()> {
+        if self.id.is_empty() {
+            bail!("presentation definition 'id' must not be empty")
+        }
+
+        if self.input_descriptors.is_empty() {
+            bail!("presentation definition must have at least one input descriptor")
+        }
+
+        let mut ids = HashSet::new();
+        for (i, input_descriptor) in self.input_descriptors.iter().enumerate() {
+            if input_descriptor.id.is_empty() {
+                bail!("input descriptor {i} must have a non-empty 'id'")
+            }
+            if !ids.insert(input_descriptor.id.as_str()) {
+                bail!(
+                    "input descriptor id '{}' is not unique",
+                    input_descriptor.id
+                )
+            }
+            if let Some(constraints) = &input_descriptor.constraints {
+                constraints.validate_structure().context(format!(
+                    "input descriptor '{}' has invalid constraints",
+                    input_descriptor.id
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The fields requested by all input descriptors, for presenting to the holder, e.g. in a
+    /// consent screen.
+    pub fn requested_fields(&self) -> Vec<RequestedField> {
+        self.input_descriptors
+            .iter()
+            .flat_map(|input_descriptor| {
+                input_descriptor
+                    .constraints
+                    .iter()
+                    .flat_map(|constraints| constraints.fields.iter().flatten())
+                    .map(|field| RequestedField {
+                        input_descriptor_id: input_descriptor.id.clone(),
+                        path: field.path.clone(),
+                        name: field.name.clone(),
+                        purpose: field.purpose.clone(),
+                        required: !field.is_optional(),
+                        intent_to_retain: field.intent_to_retain.unwrap_or(false),
+                        group: input_descriptor.group.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Whether this definition requests no more than `other`, e.g. so that a wallet can re-use the
+    /// consent that the holder gave to `other` instead of prompting again.
+    ///
+    /// Each input descriptor must have an input descriptor in `other` with the same `id` that
+    /// requests no less, see [InputDescriptor::is_subset_of], and the accepted formats must be
+    /// the same as or narrower than those of `other`.
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        if !is_narrower_format(self.format.as_ref(), other.format.as_ref()) {
+            return false;
+        }
+
+        self.input_descriptors.iter().all(|input_descriptor| {
+            other
+                .input_descriptors
+                .iter()
+                .filter(|other| other.id == input_descriptor.id)
+                .any(|other| input_descriptor.is_subset_of(other))
+        })
+    }
+
+    /// Validate an [AuthorizationResponse] against this presentation definition.
+    ///
+    /// Checks that the presentation submission refers to this definition, that each input
+    /// descriptor is mapped to a presentation in the `vp_token`, and that the descriptor map
+    /// contains no entries for unknown input descriptors. JWT-encoded responses are decoded
+    /// before validation.
+    ///
+    /// If the definition has `submission_requirements`, only the input descriptors in the
+    /// presentation submission are validated, and they must satisfy every requirement, see
+    /// [SubmissionRequirement::is_satisfied].
+    ///
+    /// When the `vp_token` contains multiple presentations, the descriptor map paths are resolved
+    /// against the array of presentations.
+    ///
+    /// The `alg` of each JWT-encoded presentation or credential, and the proof type of each Data
+    /// Integrity one, must be accepted by the format of the input descriptor, or if it has none,
+    /// the format of this presentation definition.
+    ///
+    /// The signatures of the response and of the presentations are not verified. mdoc
+    /// presentations are reported as unverifiable, as the issuer signature that protects their
+    /// data elements is not verified either, see
+    /// [DeviceResponse](crate::core::credential_format::mso_mdoc::DeviceResponse).
+    ///
+    /// Failures to satisfy the constraints of an input descriptor are reported as
+    /// [Error::ConstraintsNotSatisfied].
+    pub fn validate_authorization_response(
+        &self,
+        auth_response: &AuthorizationResponse,
+    ) -> Result<(), Error> {
+        let response = match auth_response {
+            AuthorizationResponse::Unencoded(response) => response.clone(),
+            AuthorizationResponse::Jwt(jwt) => jwt.decode_unverified().map_err(Error::JwtDecode)?,
+        };
+
+        self.validate_response(&response).map_err(|e| {
+            if e.is::<ConstraintsNotSatisfied>() {
+                Error::ConstraintsNotSatisfied(e)
+            } else {
+                Error::InvalidResponse(e)
+            }
+        })
+    }
+
+    fn validate_response(&self, response: &UnencodedAuthorizationResponse) -> Result<()> {
+        self.validate_structure()
+            .context("invalid presentation definition")?;
+
+        let presentation_submission = response
+            .presentation_submission()
+            .context("the response has no presentation submission")?
+            .parsed();
+
+        if presentation_submission.definition_id != self.id {
+            bail!(
+                "presentation submission refers to definition '{}', expected '{}'",
+                presentation_submission.definition_id,
+                self.id
+            )
+        }
+
+        let vp_token: Json = response.vp_token().clone().into();
+
+        for DescriptorMap { id, .. } in &presentation_submission.descriptor_map {
+            if !self
+                .input_descriptors
+                .iter()
+                .any(|input_descriptor| input_descriptor.id == *id)
+            {
+                bail!("presentation submission refers to unknown input descriptor '{id}'")
+            }
+        }
+
+        // The subject of the credential presented for each constraint field, by field id.
+        let mut field_subjects = BTreeMap::new();
+        // The constraint fields of input descriptors that were not submitted, as allowed by the
+        // submission requirements.
+        let mut unsubmitted_fields = HashSet::new();
+        let mut submitted = vec![];
+
+        for input_descriptor in &self.input_descriptors {
+            let descriptor = match presentation_submission.descriptor_by_id(&input_descriptor.id) {
+                Some(descriptor) => descriptor,
+                None if self.submission_requirements.is_some() => {
+                    unsubmitted_fields.extend(input_descriptor.field_ids());
+                    continue;
+                }
+                None => bail!(
+                    "input descriptor '{}' is missing from the presentation submission",
+                    input_descriptor.id
+                ),
+            };
+            submitted.push(input_descriptor.id.as_str());
+
+            let credential = input_descriptor
+                .validate_credential(&vp_token, descriptor, self.format.as_ref())
+                .context(format!(
+                    "input descriptor '{}' is not satisfied",
+                    input_descriptor.id
+                ))?;
+
+            let subject = credential_subject(&credential).map(str::to_owned);
+            for field_id in input_descriptor.field_ids() {
+                field_subjects.insert(field_id, subject.clone());
+            }
+        }
+
+        for (i, requirement) in self.submission_requirements.iter().flatten().enumerate() {
+            if !requirement.is_satisfied(&self.input_descriptors, &submitted) {
+                bail!("submission requirement {i} is not satisfied by the presentation submission")
+            }
+        }
+
+        self.validate_same_subject(&field_subjects, &unsubmitted_fields)
+    }
+
+    /// Check that the constraint fields of each required `same_subject` constraint were presented
+    /// in credentials with the same subject.
+    ///
+    /// Constraints of input descriptors that were not submitted, and `unsubmitted_fields` that
+    /// other constraints refer to, are skipped.
+    fn validate_same_subject(
+        &self,
+        field_subjects: &BTreeMap<&str, Option<String>>,
+        unsubmitted_fields: &HashSet<&str>,
+    ) -> Result<()> {
+        let same_subjects = self
+            .input_descriptors
+            .iter()
+            .filter(|input_descriptor| {
+                input_descriptor
+                    .field_ids()
+                    .all(|field_id| !unsubmitted_fields.contains(field_id))
+            })
+            .filter_map(|input_descriptor| input_descriptor.constraints.as_ref())
+            .flat_map(|constraints| constraints.same_subject.iter().flatten())
+            .filter(|same_subject| same_subject.is_required());
+
+        for same_subject in same_subjects {
+            let mut expected: Option<(&str, &str)> = None;
+            for field_id in same_subject
+                .field_id
+                .iter()
+                .filter(|field_id| !unsubmitted_fields.contains(field_id.as_str()))
+            {
+                let Some(subject) = field_subjects.get(field_id.as_str()).context(format!(
+                    "'same_subject' refers to unknown constraint field '{field_id}'"
+                ))?
+                else {
+                    bail!(ConstraintsNotSatisfied(format!(
+                        "the subject of field '{field_id}' could not be determined"
+                    )))
+                };
+                match expected {
+                    None => expected = Some((field_id, subject)),
+                    Some((expected_id, expected)) if expected != subject => {
+                        bail!(ConstraintsNotSatisfied(format!(
+                            "fields '{expected_id}' and '{field_id}' do not have the same subject"
+                        )))
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A constraint field of a [PresentationDefinition], flattened with the details of its input
+/// descriptor. See [PresentationDefinition::requested_fields].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestedField {
+    /// The id of the input descriptor requesting the field.
+    pub input_descriptor_id: String,
+    pub path: NonEmptyVec<String>,
+    pub name: Option<String>,
+    pub purpose: Option<String>,
+    /// Whether the field must be disclosed, rather than being optional.
+    pub required: bool,
+    /// Whether the verifier intends to retain the value.
+    pub intent_to_retain: bool,
+    /// The submission requirement groups of the input descriptor.
+    pub group: Vec<String>,
+}
+
+/// Get the localized `<property>#<locale>` string from extension properties.
+fn localized<'a>(extra: &'a Map<String, Json>, property: &str, locale: &str) -> Option<&'a str> {
+    extra
+        .get(&format!("{property}#{locale}"))
+        .and_then(Json::as_str)
+}
+
+fn decode_jwt(jwt: &str) -> Result<Json> {
+    ssi::jwt::decode_unverified(jwt).context("value is not a valid JWT")
+}
+
+/// Check that the `alg` of a JWT is accepted for its claim format designation by a format map.
+fn validate_jwt_alg(jwt: &str, designation: &str, format: &ClaimFormatMap) -> Result<()> {
+    let Some(payload) = format.0.get(&designation.into()) else {
+        bail!("format '{designation}' is not accepted")
+    };
+
+    let ClaimFormatPayload::Alg(accepted_algs) = payload else {
+        return Ok(());
+    };
+
+    let (headers_b64, _, _) = ssi::jws::split_jws(jwt)?;
+
+    let headers_json_bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(headers_b64)
+        .context("jwt headers were not valid base64url")?;
+
+    let mut headers = serde_json::from_slice::<Map<String, Json>>(&headers_json_bytes)
+        .context("jwt headers were not valid json")?;
+
+    let Json::String(alg) = headers
+        .remove("alg")
+        .context("'alg' was missing from jwt headers")?
+    else {
+        bail!("'alg' header was not a string")
+    };
+
+    if !accepted_algs.contains(&alg) {
+        bail!("'{alg}' is not an accepted algorithm for format '{designation}'")
+    }
+
+    Ok(())
+}
+
+/// Check that a resolved value is a presentation or a credential, as declared by its claim format
+/// designation.
+///
+/// JWT values are recognized by their `vp` or `vc` claim, and Data Integrity values by their
+/// `type`. Values that are neither are accepted, as are designations without such a distinction.
+fn validate_declared_format(value: &Json, designation: &str) -> Result<()> {
+    let (declares_presentation, is_jwt) = match ClaimFormatDesignation::from(designation) {
+        ClaimFormatDesignation::JwtVp | ClaimFormatDesignation::JwtVpJson => (true, true),
+        ClaimFormatDesignation::JwtVc | ClaimFormatDesignation::JwtVcJson => (false, true),
+        ClaimFormatDesignation::LdpVp => (true, false),
+        ClaimFormatDesignation::LdpVc => (false, false),
+        _ => return Ok(()),
+    };
+
+    let is_presentation = if is_jwt {
+        match (value.get("vp").is_some(), value.get("vc").is_some()) {
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            _ => None,
+        }
+    } else {
+        let types = match value.get("type") {
+            Some(Json::Array(types)) => types.iter().filter_map(Json::as_str).collect(),
+            Some(Json::String(t)) => vec![t.as_str()],
+            _ => vec![],
+        };
+        if types.contains(&"VerifiablePresentation") {
+            Some(true)
+        } else if types.contains(&"VerifiableCredential") {
+            Some(false)
+        } else {
+            None
+        }
+    };
+
+    match is_presentation {
+        Some(true) if !declares_presentation => {
+            bail!("the declared format is '{designation}', but the value is a presentation")
+        }
+        Some(false) if declares_presentation => {
+            bail!("the declared format is '{designation}', but the value is a credential")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the proof types of a Data Integrity secured value are accepted for its claim
+/// format designation by a format map.
+fn validate_proof_type(value: &Json, designation: &str, format: &ClaimFormatMap) -> Result<()> {
+    let Some(payload) = format.0.get(&designation.into()) else {
+        bail!("format '{designation}' is not accepted")
+    };
+
+    let ClaimFormatPayload::ProofType(accepted_proof_types) = payload else {
+        return Ok(());
+    };
+
+    let proofs = match value.get("proof") {
+        Some(Json::Array(proofs)) => proofs.iter().collect(),
+        Some(proof) => vec![proof],
+        None => bail!("'proof' is missing"),
+    };
+
+    for proof in proofs {
+        let Some(proof_type) = proof.get("type").and_then(Json::as_str) else {
+            bail!("proof 'type' was missing or not a string")
+        };
+        if !accepted_proof_types
+            .iter()
+            .any(|accepted| accepted == proof_type)
+        {
+            bail!("'{proof_type}' is not an accepted proof type for format '{designation}'")
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `format` accepts no formats, algorithms or proof types that `other` does not. No format
+/// accepts any.
+fn is_narrower_format(format: Option<&ClaimFormatMap>, other: Option<&ClaimFormatMap>) -> bool {
+    match (format, other) {
+        (_, None) => true,
+        (None, Some(_)) => false,
+        (Some(format), Some(other)) => format.intersect(other) == *format,
+    }
+}
+
+/// The accepted claim formats, keyed by claim format designation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ClaimFormatMap(pub BTreeMap<ClaimFormatDesignation, ClaimFormatPayload>);
+
+impl ClaimFormatMap {
+    /// The formats accepted by both maps, with the algorithms or proof types accepted by both.
+    ///
+    /// Formats for which no algorithm or proof type is accepted by both are omitted.
+    pub fn intersect(&self, other: &ClaimFormatMap) -> ClaimFormatMap {
+        ClaimFormatMap(
+            self.0
+                .iter()
+                .filter_map(|(designation, payload)| {
+                    let intersection = payload.intersect(other.0.get(designation)?)?;
+                    Some((designation.clone(), intersection))
+                })
+                .collect(),
+        )
+    }
+
+    /// The formats accepted by either map, with the algorithms or proof types accepted by either.
+    pub fn union(&self, other: &ClaimFormatMap) -> ClaimFormatMap {
+        let mut union = self.clone();
+        for (designation, payload) in &other.0 {
+            let merged = match union.0.get(designation) {
+                Some(existing) => existing.union(payload),
+                None => payload.clone(),
+            };
+            union.0.insert(designation.clone(), merged);
+        }
+        union
+    }
+}
+
+/// A claim format designation, such as `jwt_vc_json`.
+///
+/// Designations that are not known to this crate are kept as [ClaimFormatDesignation::Other],
+/// so that they round-trip, and are never matched by a known format.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ClaimFormatDesignation {
+    JwtVc,
+    JwtVcJson,
+    JwtVp,
+    JwtVpJson,
+    LdpVc,
+    LdpVp,
+    MsoMdoc,
+    VcSdJwt,
+    DcSdJwt,
+    Other(String),
+}
+
+impl ClaimFormatDesignation {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::JwtVc => JwtVc::ID,
+            Self::JwtVcJson => "jwt_vc_json",
+            Self::JwtVp => "jwt_vp",
+            Self::JwtVpJson => "jwt_vp_json",
+            Self::LdpVc => LdpVc::ID,
+            Self::LdpVp => LdpVp::ID,
+            Self::MsoMdoc => MsoMdoc::ID,
+            Self::VcSdJwt => VcSdJwt::ID,
+            Self::DcSdJwt => DcSdJwt::ID,
+            Self::Other(designation) => designation,
+        }
+    }
+}
+
+impl From<&str> for ClaimFormatDesignation {
+    fn from(value: &str) -> Self {
+        match value {
+            JwtVc::ID => Self::JwtVc,
+            "jwt_vc_json" => Self::JwtVcJson,
+            "jwt_vp" => Self::JwtVp,
+            "jwt_vp_json" => Self::JwtVpJson,
+            LdpVc::ID => Self::LdpVc,
+            LdpVp::ID => Self::LdpVp,
+            MsoMdoc::ID => Self::MsoMdoc,
+            VcSdJwt::ID => Self::VcSdJwt,
+            DcSdJwt::ID => Self::DcSdJwt,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+impl From<String> for ClaimFormatDesignation {
+    fn from(value: String) -> Self {
+        value.as_str().into()
+    }
+}
+
+impl From<ClaimFormatDesignation> for String {
+    fn from(value: ClaimFormatDesignation) -> Self {
+        match value {
+            ClaimFormatDesignation::Other(designation) => designation,
+            known => known.as_str().to_owned(),
+        }
+    }
+}
+
+impl std::fmt::Display for ClaimFormatDesignation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The accepted algorithms for a claim format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClaimFormatPayload {
+    /// The accepted JWS `alg` values.
+    Alg(Vec<String>),
+    /// The accepted Data Integrity `proof_type` values, for the `ldp_*` formats.
+    ProofType(Vec<String>),
+    /// A payload without an `alg` or `proof_type`, such as the `sd-jwt_alg_values` of an SD-JWT format.
+    Other(Map<String, Json>),
+}
+
+impl ClaimFormatPayload {
+    fn intersect(&self, other: &ClaimFormatPayload) -> Option<ClaimFormatPayload> {
+        let intersect = |a: &[String], b: &[String]| -> Option<Vec<String>> {
+            let values: Vec<String> = a
+                .iter()
+                .filter(|value| b.contains(value))
+                .cloned()
+                .collect();
+            (!values.is_empty()).then_some(values)
+        };
+        match (self, other) {
+            (Self::Alg(a), Self::Alg(b)) => intersect(a, b).map(Self::Alg),
+            (Self::ProofType(a), Self::ProofType(b)) => intersect(a, b).map(Self::ProofType),
+            (Self::Other(_), restricted) | (restricted, Self::Other(_)) => Some(restricted.clone()),
+            _ => None,
+        }
+    }
+
+    fn union(&self, other: &ClaimFormatPayload) -> ClaimFormatPayload {
+        let union = |a: &[String], b: &[String]| -> Vec<String> {
+            let mut values = a.to_vec();
+            values.extend(b.iter().filter(|value| !a.contains(value)).cloned());
+            values
+        };
+        match (self, other) {
+            (Self::Alg(a), Self::Alg(b)) => Self::Alg(union(a, b)),
+            (Self::ProofType(a), Self::ProofType(b)) => Self::ProofType(union(a, b)),
+            (unrestricted @ Self::Other(_), _) | (_, unrestricted @ Self::Other(_)) => {
+                unrestricted.clone()
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Serialize for ClaimFormatPayload {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Alg(alg) => {
+                let mut map = Map::new();
+                map.insert("alg".into(), alg.clone().into());
+                map.serialize(serializer)
+            }
+            Self::ProofType(proof_type) => {
+                let mut map = Map::new();
+                map.insert("proof_type".into(), proof_type.clone().into());
+                map.serialize(serializer)
+            }
+            Self::Other(map) => map.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ClaimFormatPayload {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut map = Map::deserialize(deserializer)?;
+        if let Some(alg) = map.remove("alg") {
+            serde_json::from_value(alg)
+                .map(Self::Alg)
+                .map_err(serde::de::Error::custom)
+        } else if let Some(proof_type) = map.remove("proof_type") {
+            serde_json::from_value(proof_type)
+                .map(Self::ProofType)
+                .map_err(serde::de::Error::custom)
+        } else {
+            Ok(Self::Other(map))
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -21,12 +656,346 @@ pub struct InputDescriptor {
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub purpose: Option<String>,
+    /// The accepted formats, which take precedence over the format of the presentation
+    /// definition.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub format: Option<serde_json::Value>, // TODO
+    pub format: Option<ClaimFormatMap>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub constraints: Option<Constraints>, // TODO shouldn't be optional
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub group: Vec<String>,
+    /// Extension properties, such as localized names and purposes. See
+    /// [InputDescriptor::name_for_locale].
+    #[serde(flatten)]
+    pub extra: Map<String, Json>,
+}
+
+impl InputDescriptor {
+    /// Add this input descriptor to a group that submission requirements can select `from`.
+    pub fn add_group(&mut self, group: String) {
+        self.group.push(group)
+    }
+
+    /// The submission requirement groups that this input descriptor belongs to.
+    pub fn groups(&self) -> &[String] {
+        &self.group
+    }
+
+    /// Set the formats accepted for this input descriptor.
+    pub fn set_format(&mut self, format: ClaimFormatMap) {
+        self.format = Some(format)
+    }
+
+    /// The formats accepted for this input descriptor, if they are restricted more tightly than
+    /// by the presentation definition.
+    pub fn format(&self) -> Option<&ClaimFormatMap> {
+        self.format.as_ref()
+    }
+
+    /// The `name` for a locale, given as a `name#<locale>` property such as `name#fr`, falling
+    /// back to the default `name`.
+    pub fn name_for_locale(&self, locale: &str) -> Option<&str> {
+        localized(&self.extra, "name", locale).or(self.name.as_deref())
+    }
+
+    /// The `purpose` for a locale, given as a `purpose#<locale>` property such as `purpose#fr`,
+    /// falling back to the default `purpose`.
+    pub fn purpose_for_locale(&self, locale: &str) -> Option<&str> {
+        localized(&self.extra, "purpose", locale).or(self.purpose.as_deref())
+    }
+
+    /// Whether this input descriptor requests no more than `other`.
+    ///
+    /// Each constraint field must have a field in `other` with the same `path` and a `filter` that
+    /// is the same or stricter, see [ConstraintsField::is_subset_of]. An input descriptor without
+    /// constraint fields requests the whole credential, so it is only a subset of another without
+    /// fields. If `other` requires limited disclosure, so must this input descriptor, and the
+    /// accepted formats must be the same as or narrower than those of `other`.
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        if !is_narrower_format(self.format.as_ref(), other.format.as_ref()) {
+            return false;
+        }
+
+        let limits_disclosure = |input_descriptor: &Self| {
+            input_descriptor
+                .constraints
+                .as_ref()
+                .and_then(|constraints| constraints.limit_disclosure.as_ref())
+                == Some(&ConstraintsLimitDisclosure::Required)
+        };
+
+        if limits_disclosure(other) && !limits_disclosure(self) {
+            return false;
+        }
+
+        match (self.constraints_fields(), other.constraints_fields()) {
+            (_, []) => true,
+            ([], _) => false,
+            (fields, other_fields) => fields.iter().all(|field| {
+                other_fields
+                    .iter()
+                    .any(|other_field| field.is_subset_of(other_field))
+            }),
+        }
+    }
+
+    /// Validate the `vp_token` against this input descriptor, using the [DescriptorMap] entry that
+    /// the presentation submission provided for it.
+    ///
+    /// The format of this input descriptor takes precedence over `definition_format`.
+    ///
+    /// If `limit_disclosure` is `required`, every selectively disclosed claim of the credential
+    /// must be at, within or above a location matched by a constraint field. Only formats with
+    /// selective disclosure (SD-JWT) can satisfy it.
+    pub fn validate_verifiable_presentation(
+        &self,
+        vp_token: &Json,
+        descriptor: &DescriptorMap,
+        definition_format: Option<&ClaimFormatMap>,
+    ) -> Result<()> {
+        self.validate_credential(vp_token, descriptor, definition_format)
+            .map(|_| ())
+    }
+
+    /// See [InputDescriptor::validate_verifiable_presentation], returning the validated
+    /// credential.
+    fn validate_credential(
+        &self,
+        vp_token: &Json,
+        descriptor: &DescriptorMap,
+        definition_format: Option<&ClaimFormatMap>,
+    ) -> Result<Json> {
+        let format = self.format.as_ref().or(definition_format);
+
+        let (credential, disclosed) = descriptor.resolve(vp_token, format)?;
+
+        let requested = self.validate_constraints_fields(&credential)?;
+
+        let subject_is_issuer = self
+            .constraints
+            .as_ref()
+            .and_then(|constraints| constraints.subject_is_issuer.as_ref());
+        if subject_is_issuer == Some(&ConstraintsSubjectIsIssuer::Required) {
+            validate_subject_is_issuer(&credential)?;
+        }
+
+        self.validate_is_holder(vp_token, descriptor, &credential)?;
+
+        let limit_disclosure = self
+            .constraints
+            .as_ref()
+            .and_then(|constraints| constraints.limit_disclosure.as_ref());
+        if limit_disclosure == Some(&ConstraintsLimitDisclosure::Required) {
+            let Some(disclosed) = disclosed else {
+                bail!(ConstraintsNotSatisfied(format!(
+                    "format '{}' does not support limited disclosure",
+                    descriptor.innermost().format
+                )))
+            };
+
+            let covers = |outer: &str, inner: &str| {
+                inner == outer || inner.starts_with(&format!("{outer}/"))
+            };
+            for pointer in disclosed {
+                if !requested
+                    .iter()
+                    .any(|requested| covers(requested, &pointer) || covers(&pointer, requested))
+                {
+                    bail!(ConstraintsNotSatisfied(format!(
+                        "claim '{pointer}' was disclosed but not requested"
+                    )))
+                }
+            }
+        }
+
+        Ok(credential)
+    }
+
+    /// Check that the subject of the credential is the holder of the enveloping presentation, if
+    /// a required `is_holder` constraint refers to a constraint field of this input descriptor.
+    ///
+    /// The holder is the `iss` of a JWT presentation, or the `holder` of a Data Integrity one. A
+    /// credential that is not enveloped in a presentation has no holder.
+    fn validate_is_holder(
+        &self,
+        vp_token: &Json,
+        descriptor: &DescriptorMap,
+        credential: &Json,
+    ) -> Result<()> {
+        let field_ids: Vec<&str> = self
+            .constraints
+            .iter()
+            .flat_map(|constraints| constraints.is_holder.iter().flatten())
+            .filter(|is_holder| is_holder.is_required())
+            .flat_map(|is_holder| is_holder.field_id.iter())
+            .map(String::as_str)
+            .collect();
+        let Some(field_id) = field_ids.first() else {
+            return Ok(());
+        };
+
+        for field_id in &field_ids {
+            if !self.field_ids().any(|id| id == *field_id) {
+                bail!("'is_holder' refers to unknown constraint field '{field_id}'")
+            }
+        }
+
+        let holder = match descriptor.path_nested {
+            Some(_) => {
+                let presentation = DescriptorMap {
+                    path_nested: None,
+                    ..descriptor.clone()
+                };
+                let (presentation, _) = presentation.resolve(vp_token, None)?;
+                presentation_holder(&presentation).map(str::to_owned)
+            }
+            None => None,
+        };
+
+        match (holder, credential_subject(credential)) {
+            (Some(holder), Some(subject)) if holder == subject => Ok(()),
+            (Some(holder), Some(subject)) => bail!(ConstraintsNotSatisfied(format!(
+                "the subject '{subject}' of field '{field_id}' is not the holder '{holder}'"
+            ))),
+            (None, _) => bail!(ConstraintsNotSatisfied(format!(
+                "the holder of field '{field_id}' could not be determined"
+            ))),
+            (_, None) => bail!(ConstraintsNotSatisfied(format!(
+                "the subject of field '{field_id}' could not be determined"
+            ))),
+        }
+    }
+
+    /// The constraint fields of this input descriptor.
+    fn constraints_fields(&self) -> &[ConstraintsField] {
+        self.constraints
+            .as_ref()
+            .and_then(|constraints| constraints.fields.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// The `id`s of the constraint fields of this input descriptor.
+    fn field_ids(&self) -> impl Iterator<Item = &str> {
+        self.constraints
+            .iter()
+            .flat_map(|constraints| constraints.fields.iter().flatten())
+            .filter_map(|field| field.id.as_deref())
+    }
+
+    /// Evaluate the claims of a credential against the constraint fields without failing, e.g. to
+    /// preview which requirements a credential satisfies before presenting it.
+    ///
+    /// Only the constraint fields are evaluated, not the other constraints such as
+    /// `limit_disclosure`.
+    pub fn evaluate(&self, credential: &Json) -> FieldEvaluation {
+        FieldEvaluation {
+            fields: self
+                .constraints
+                .iter()
+                .flat_map(|constraints| constraints.fields.iter().flatten())
+                .map(|field| field.evaluate(credential))
+                .collect(),
+        }
+    }
+
+    /// Validate the claims of a credential against the constraint fields, returning JSON
+    /// pointers to the values that satisfied them.
+    pub(crate) fn validate_constraints_fields(&self, credential: &Json) -> Result<Vec<String>> {
+        let fields = self
+            .constraints
+            .iter()
+            .flat_map(|constraints| constraints.fields.iter().flatten());
+        let mut pointers = vec![];
+        for field in fields {
+            if let Some(pointer) = field.validate(credential)? {
+                pointers.push(pointer);
+            }
+        }
+        Ok(pointers)
+    }
+
+    /// Whether a credential format is accepted by the format of this input descriptor, or if it
+    /// has none, the `definition_format`, either directly or enveloped in a presentation.
+    pub(crate) fn accepts_format(
+        &self,
+        format: &str,
+        definition_format: Option<&ClaimFormatMap>,
+    ) -> bool {
+        let Some(accepted) = self.format.as_ref().or(definition_format) else {
+            return true;
+        };
+        accepted.0.contains_key(&format.into())
+            || envelope_format(format)
+                .is_some_and(|(envelope, _)| accepted.0.contains_key(&envelope.into()))
+    }
+}
+
+/// Check that the issuer of a credential is its subject.
+///
+/// The issuer is the `iss` claim of a JWT, or else the `issuer` (or its `id`) of a credential. The
+/// subject is found by [credential_subject].
+fn validate_subject_is_issuer(credential: &Json) -> Result<()> {
+    let issuer = credential
+        .get("iss")
+        .or_else(|| {
+            let issuer = credential.get("issuer")?;
+            issuer.get("id").or(Some(issuer))
+        })
+        .and_then(Json::as_str);
+    let subject = credential_subject(credential);
+
+    match (issuer, subject) {
+        (Some(issuer), Some(subject)) if issuer == subject => Ok(()),
+        (Some(issuer), Some(subject)) => bail!(ConstraintsNotSatisfied(format!(
+            "the subject '{subject}' is not the issuer '{issuer}'"
+        ))),
+        (None, _) => bail!(ConstraintsNotSatisfied(
+            "the issuer of the credential could not be determined".into()
+        )),
+        (_, None) => bail!(ConstraintsNotSatisfied(
+            "the subject of the credential could not be determined".into()
+        )),
+    }
+}
+
+/// The subject of a credential: the `credentialSubject.id` (of the `vc` claim for a JWT), or
+/// else the `sub` claim.
+fn credential_subject(credential: &Json) -> Option<&str> {
+    credential
+        .get("vc")
+        .unwrap_or(credential)
+        .pointer("/credentialSubject/id")
+        .or_else(|| credential.get("sub"))
+        .and_then(Json::as_str)
+}
+
+/// The holder of a presentation: the `iss` claim of a JWT, or else the `holder` (or its `id`).
+fn presentation_holder(presentation: &Json) -> Option<&str> {
+    presentation
+        .get("iss")
+        .or_else(|| {
+            let holder = presentation.get("holder")?;
+            holder.get("id").or(Some(holder))
+        })
+        .and_then(Json::as_str)
+}
+
+/// The presentation format that envelops a credential format, and the JSONPath of the
+/// credentials within it.
+fn envelope_format(format: &str) -> Option<(&'static str, &'static str)> {
+    match format {
+        "jwt_vc_json" => Some(("jwt_vp_json", "$.vp.verifiableCredential")),
+        JwtVc::ID => Some(("jwt_vp", "$.vp.verifiableCredential")),
+        LdpVc::ID => Some((LdpVp::ID, "$.verifiableCredential")),
+        _ => None,
+    }
 }
 
+/// The presented credential does not satisfy the constraints of an input descriptor.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct ConstraintsNotSatisfied(pub String);
+
 // TODO must have at least one
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Constraints {
@@ -34,10 +1003,60 @@ pub struct Constraints {
     pub fields: Option<Vec<ConstraintsField>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit_disclosure: Option<ConstraintsLimitDisclosure>,
+    /// Whether the subject of the credential must be its issuer, as for self-attested
+    /// credentials.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_is_issuer: Option<ConstraintsSubjectIsIssuer>,
+    /// Constraint fields, of this input descriptor, whose subject must be the holder of the
+    /// presentation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_holder: Option<Vec<ConstraintsSubject>>,
+    /// Constraint fields, of any input descriptor, which must all have the same subject.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub same_subject: Option<Vec<ConstraintsSubject>>,
+}
+
+impl Constraints {
+    /// Validate the structure of these constraints.
+    ///
+    /// If `limit_disclosure` is set, there must be at least one field, as otherwise the wallet
+    /// cannot know which claims to disclose.
+    pub fn validate_structure(&self) -> Result<()> {
+        let has_fields = self
+            .fields
+            .as_ref()
+            .is_some_and(|fields| !fields.is_empty());
+        if self.limit_disclosure.is_some() && !has_fields {
+            bail!("'limit_disclosure' requires at least one constraint field")
+        }
+        Ok(())
+    }
+}
+
+/// A set of constraint fields, by `id`, that an `is_holder` or `same_subject` constraint applies
+/// to.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConstraintsSubject {
+    pub field_id: NonEmptyVec<String>,
+    pub directive: ConstraintsSubjectDirective,
+}
+
+impl ConstraintsSubject {
+    fn is_required(&self) -> bool {
+        self.directive == ConstraintsSubjectDirective::Required
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConstraintsSubjectDirective {
+    Required,
+    Preferred,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ConstraintsField {
+    /// JSONPath expressions, evaluated in order until one resolves a value.
     pub path: NonEmptyVec<String>, // TODO JsonPath validation at deserialization time
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
@@ -49,18 +1068,63 @@ pub struct ConstraintsField {
     pub filter: Option<serde_json::Value>, // TODO JSONSchema validation at deserialization time
     #[serde(skip_serializing_if = "Option::is_none")]
     pub optional: Option<bool>,
+    /// Whether the verifier intends to retain the value, e.g. a data element of an mdoc.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub intent_to_retain: Option<bool>,
+    /// Whether the holder should present the boolean result of the `filter` instead of the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<Predicate>,
+    #[serde(skip)]
+    parsed_path: Parsed<NonEmptyVec<String>, Vec<JsonPath>>,
+    #[serde(skip)]
+    parsed_filter: Parsed<Json, SchemaValidator>,
 }
 
-pub type ConstraintsFields = Vec<ConstraintsField>;
+/// A parsed `path` or `filter` of a [ConstraintsField], cached on first use together with the
+/// source it was parsed from.
+#[derive(Clone, Debug)]
+struct Parsed<S, T>(OnceLock<(S, T)>);
 
-impl ConstraintsField {
-    pub fn new(
-        path: NonEmptyVec<String>,
-        id: Option<String>,
-        purpose: Option<String>,
-        name: Option<String>,
+impl<S, T> Default for Parsed<S, T> {
+    fn default() -> Self {
+        Self(OnceLock::new())
+    }
+}
+
+/// The cache does not affect equality.
+impl<S, T> PartialEq for Parsed<S, T> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<S, T> Eq for Parsed<S, T> {}
+
+impl<S: Clone + PartialEq, T: Clone> Parsed<S, T> {
+    /// The value parsed from `source`, which is only parsed again if it differs from the source
+    /// of the cached value.
+    fn get_or_parse(&self, source: &S, parse: impl FnOnce(&S) -> Result<T>) -> Result<Cow<'_, T>> {
+        if let Some((cached, parsed)) = self.0.get() {
+            if cached == source {
+                return Ok(Cow::Borrowed(parsed));
+            }
+            return parse(source).map(Cow::Owned);
+        }
+
+        let parsed = parse(source)?;
+        let (_, parsed) = self.0.get_or_init(|| (source.clone(), parsed));
+        Ok(Cow::Borrowed(parsed))
+    }
+}
+
+pub type ConstraintsFields = Vec<ConstraintsField>;
+
+impl ConstraintsField {
+    pub fn new(
+        path: NonEmptyVec<String>,
+        id: Option<String>,
+        purpose: Option<String>,
+        name: Option<String>,
         filter: Option<serde_json::Value>,
         optional: Option<bool>,
         intent_to_retain: Option<bool>,
@@ -73,8 +1137,247 @@ impl ConstraintsField {
             filter,
             optional,
             intent_to_retain,
+            predicate: None,
+            parsed_path: Parsed::default(),
+            parsed_filter: Parsed::default(),
+        }
+    }
+
+    /// Set whether the field may be absent from the credential.
+    pub fn set_optional(&mut self, optional: bool) {
+        self.optional = Some(optional)
+    }
+
+    /// Whether the field may be absent from the credential. Fields are required by default.
+    pub fn is_optional(&self) -> bool {
+        self.optional.unwrap_or(false)
+    }
+
+    /// Set whether the verifier intends to retain the value of the field, which wallets can
+    /// present to the holder, see [PresentationDefinition::requested_fields].
+    pub fn set_intent_to_retain(&mut self, intent_to_retain: bool) {
+        self.intent_to_retain = Some(intent_to_retain)
+    }
+
+    /// Set the JSON Schema `filter` that the value of the field must match.
+    ///
+    /// Fails if the filter is not a valid [SchemaValidator].
+    pub fn set_filter(&mut self, filter: Json) -> Result<()> {
+        SchemaValidator::from_value(&filter)?;
+        self.filter = Some(filter);
+        Ok(())
+    }
+
+    /// Set whether the holder must or may present the boolean result of the `filter` instead of
+    /// the value.
+    pub fn set_predicate(&mut self, predicate: Predicate) {
+        self.predicate = Some(predicate)
+    }
+
+    /// Whether this field requests no more than `other`.
+    ///
+    /// The `path` must be the same, and the verifier may only intend to retain the value if it
+    /// did for `other`. The `filter` must be the same as that of `other`, or stricter: any filter
+    /// is stricter than none, and a filter that has all of the keywords of the other with the same
+    /// values, and more, is stricter. If `other` requires a `predicate`, so must this field.
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        let stricter_filter = match (&self.filter, &other.filter) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(filter), Some(other_filter)) => {
+                filter == other_filter
+                    || match (filter, other_filter) {
+                        (Json::Object(filter), Json::Object(other_filter)) => other_filter
+                            .iter()
+                            .all(|(keyword, value)| filter.get(keyword) == Some(value)),
+                        _ => false,
+                    }
+            }
+        };
+
+        self.path == other.path
+            && stricter_filter
+            && (!self.intent_to_retain.unwrap_or(false) || other.intent_to_retain.unwrap_or(false))
+            && (other.predicate != Some(Predicate::Required)
+                || self.predicate == Some(Predicate::Required))
+    }
+
+    /// Validate a credential against this field.
+    ///
+    /// The paths are evaluated in order until one resolves any values, at least one of which must
+    /// match the `filter`. If no value is found, validation fails unless the field is `optional`,
+    /// in which case the filter is not applied. An optional field that is present must still
+    /// match the filter.
+    ///
+    /// If the `predicate` is `required`, the value found must be `true`, the result of the filter
+    /// as evaluated by the holder, and a raw value is rejected. If it is `preferred`, either `true`
+    /// or a value matching the filter is accepted.
+    ///
+    /// Returns the JSON pointer to the value that matched, if any.
+    pub fn validate(&self, credential: &Json) -> Result<Option<String>> {
+        let found = self.find(credential)?;
+        if found.is_empty() {
+            if self.is_optional() {
+                return Ok(None);
+            }
+            bail!(ConstraintsNotSatisfied(format!(
+                "no value found at constraint field path(s) {:?}",
+                self.path.as_ref()
+            )))
+        }
+
+        let mut errors = vec![];
+        for (pointer, value) in &found {
+            match self.validate_value(value) {
+                Ok(()) => return Ok(Some(pointer.clone())),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let error = errors.swap_remove(0);
+        if found.len() == 1 || !error.is::<ConstraintsNotSatisfied>() {
+            return Err(error);
+        }
+        bail!(ConstraintsNotSatisfied(format!(
+            "none of the {} values found at constraint field path(s) {:?} match: {error:#}",
+            found.len(),
+            self.path.as_ref()
+        )))
+    }
+
+    /// Evaluate a credential against this field without failing, reporting whether a value was
+    /// found and whether it matched the `filter`. See [ConstraintsField::validate].
+    ///
+    /// An invalid `path` or `filter` is reported as not resolved or not matched.
+    ///
+    /// If several values are found, the value is the first that matched, or else the first found.
+    pub fn evaluate(&self, credential: &Json) -> FieldMatch {
+        let found = self.find(credential).unwrap_or_default();
+        let matched = found
+            .iter()
+            .find(|(_, value)| self.validate_value(value).is_ok());
+        FieldMatch {
+            id: self.id.clone(),
+            path: self.path.clone(),
+            optional: self.is_optional(),
+            resolved: !found.is_empty(),
+            filter_matched: matched.is_some(),
+            value: matched.or(found.first()).map(|(_, value)| (*value).clone()),
         }
     }
+
+    /// Find the values at the first path that resolves any, with their JSON pointers.
+    fn find<'a>(&self, credential: &'a Json) -> Result<Vec<(String, &'a Json)>> {
+        for json_path in self.json_paths()?.iter() {
+            let nodes = json_path.query_located(credential);
+            if !nodes.is_empty() {
+                return Ok(nodes
+                    .into_iter()
+                    .map(|node| (node.location().to_json_pointer(), node.node()))
+                    .collect());
+            }
+        }
+        Ok(vec![])
+    }
+
+    /// Check a value found at the paths against the `predicate` and `filter`.
+    fn validate_value(&self, value: &Json) -> Result<()> {
+        match self.predicate {
+            Some(Predicate::Required) if value != &Json::Bool(true) => {
+                bail!(ConstraintsNotSatisfied(format!(
+                    "value {value} is not a satisfied predicate for constraint field path(s) {:?}",
+                    self.path.as_ref()
+                )))
+            }
+            Some(_) if value == &Json::Bool(true) => (),
+            _ => {
+                if let Some(filter) = &self.filter {
+                    let validator = self
+                        .parsed_filter
+                        .get_or_parse(filter, SchemaValidator::from_value)
+                        .context(format!(
+                            "invalid filter for constraint field path(s) {:?}",
+                            self.path.as_ref()
+                        ))?;
+                    if let Err(e) = validator.validate(value) {
+                        bail!(ConstraintsNotSatisfied(format!(
+                            "value {value} does not match the filter of constraint field path(s) {:?}: {e:#}",
+                            self.path.as_ref()
+                        )))
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The parsed `path` expressions, which are only parsed once unless `path` is modified.
+    fn json_paths(&self) -> Result<Cow<'_, [JsonPath]>> {
+        let parsed = self.parsed_path.get_or_parse(&self.path, |paths| {
+            paths
+                .iter()
+                .map(|path| {
+                    JsonPath::parse(path).context(format!(
+                        "constraint field path '{path}' is not valid JSONPath"
+                    ))
+                })
+                .collect()
+        })?;
+        Ok(match parsed {
+            Cow::Borrowed(parsed) => Cow::Borrowed(parsed),
+            Cow::Owned(parsed) => Cow::Owned(parsed),
+        })
+    }
+}
+
+/// The result of evaluating a credential against a [ConstraintsField], see
+/// [ConstraintsField::evaluate].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldMatch {
+    pub id: Option<String>,
+    pub path: NonEmptyVec<String>,
+    pub optional: bool,
+    /// Whether a value was found at one of the paths.
+    pub resolved: bool,
+    /// Whether the value found matched the `filter` (and `predicate`), or there is none.
+    pub filter_matched: bool,
+    /// The value found, if any.
+    pub value: Option<Json>,
+}
+
+impl FieldMatch {
+    /// Whether the field is satisfied: its value was found and matched, or it is optional and
+    /// absent.
+    pub fn is_satisfied(&self) -> bool {
+        if self.resolved {
+            self.filter_matched
+        } else {
+            self.optional
+        }
+    }
+}
+
+/// The result of evaluating a credential against the constraint fields of an input descriptor,
+/// see [InputDescriptor::evaluate].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldEvaluation {
+    pub fields: Vec<FieldMatch>,
+}
+
+impl FieldEvaluation {
+    /// The number of fields that are satisfied.
+    pub fn satisfied_count(&self) -> usize {
+        self.fields
+            .iter()
+            .filter(|field| field.is_satisfied())
+            .count()
+    }
+
+    /// Whether every field is satisfied.
+    pub fn is_satisfied(&self) -> bool {
+        self.fields.iter().all(FieldMatch::is_satisfied)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -84,6 +1387,22 @@ pub enum ConstraintsLimitDisclosure {
     Preferred,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConstraintsSubjectIsIssuer {
+    Required,
+    Preferred,
+}
+
+/// Whether a holder presents the boolean result of a constraint field's filter, rather than the
+/// value itself.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Predicate {
+    Required,
+    Preferred,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PresentationSubmission {
     pub id: String,
@@ -91,27 +1410,278 @@ pub struct PresentationSubmission {
     pub descriptor_map: Vec<DescriptorMap>,
 }
 
+impl PresentationSubmission {
+    /// The descriptor for an input descriptor `id`.
+    pub fn descriptor_by_id(&self, id: &str) -> Option<&DescriptorMap> {
+        self.descriptor_map
+            .iter()
+            .find(|descriptor| descriptor.id == id)
+    }
+
+    /// Generate the presentation submission for the credentials selected by the holder, with a
+    /// random `id`.
+    ///
+    /// Each input descriptor of the definition must be matched by exactly one credential. The
+    /// `path` of a descriptor is `$` when the `vp_token` contains a single presentation, and
+    /// `$[<index>]` otherwise. Credentials enveloped in a presentation are referenced by a
+    /// `path_nested` into its `verifiableCredential`, with the presentation format for the
+    /// credential format (`jwt_vp_json` for `jwt_vc_json`, `jwt_vp` for `jwt_vc` and `ldp_vp`
+    /// for `ldp_vc`).
+    ///
+    /// The format of each descriptor must be accepted by the format of the input descriptor, or
+    /// if it has none, the format of the definition.
+    pub fn from_definition(
+        definition: &PresentationDefinition,
+        matches: &[CredentialMatch],
+    ) -> Result<Self> {
+        let presentations = matches
+            .iter()
+            .map(|credential| credential.presentation_index + 1)
+            .max()
+            .unwrap_or_default();
+
+        for credential in matches {
+            if !definition
+                .input_descriptors
+                .iter()
+                .any(|input_descriptor| input_descriptor.id == credential.input_descriptor_id)
+            {
+                bail!(
+                    "credential matches unknown input descriptor '{}'",
+                    credential.input_descriptor_id
+                )
+            }
+        }
+
+        let descriptor_map = definition
+            .input_descriptors
+            .iter()
+            .map(|input_descriptor| {
+                let id = &input_descriptor.id;
+                let credential = match matches
+                    .iter()
+                    .filter(|credential| credential.input_descriptor_id == *id)
+                    .collect::<Vec<_>>()
+                    .as_slice()
+                {
+                    [credential] => *credential,
+                    [] => bail!("no credential matches input descriptor '{id}'"),
+                    _ => bail!("more than one credential matches input descriptor '{id}'"),
+                };
+
+                let path = if presentations == 1 {
+                    "$".to_owned()
+                } else {
+                    format!("$[{}]", credential.presentation_index)
+                };
+
+                let descriptor = match credential.credential_index {
+                    None => DescriptorMap::new(id.clone(), credential.format.clone(), path)?,
+                    Some(index) => {
+                        let Some((format, nested_path)) = envelope_format(&credential.format)
+                        else {
+                            bail!(
+                                "credentials of format '{}' cannot be enveloped in a presentation",
+                                credential.format
+                            )
+                        };
+                        let mut descriptor =
+                            DescriptorMap::new(id.clone(), format.to_owned(), path)?;
+                        descriptor.set_path_nested(DescriptorMap::new(
+                            id.clone(),
+                            credential.format.clone(),
+                            format!("{nested_path}[{index}]"),
+                        )?);
+                        descriptor
+                    }
+                };
+
+                if let Some(format) = input_descriptor
+                    .format
+                    .as_ref()
+                    .or(definition.format.as_ref())
+                {
+                    if !format.0.contains_key(&descriptor.format.as_str().into()) {
+                        bail!(
+                            "format '{}' is not accepted by input descriptor '{id}'",
+                            descriptor.format
+                        )
+                    }
+                }
+
+                Ok(descriptor)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            definition_id: definition.id.clone(),
+            descriptor_map,
+        })
+    }
+}
+
+/// A credential selected by the holder to satisfy an input descriptor, see
+/// [PresentationSubmission::from_definition].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CredentialMatch {
+    /// The `id` of the input descriptor.
+    pub input_descriptor_id: String,
+    /// The claim format designation of the credential, such as `jwt_vc_json` or `vc+sd-jwt`.
+    pub format: String,
+    /// The index of the presentation in the `vp_token`.
+    pub presentation_index: usize,
+    /// The index of the credential in the `verifiableCredential` of the presentation, or `None`
+    /// if the credential is itself the presentation, such as an SD-JWT or an mdoc.
+    pub credential_index: Option<usize>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct DescriptorMap {
     pub id: String,
     pub format: String, // TODO should be enum of supported formats
     pub path: String,
-    //pub path_nested: Option<Box<DescriptorMap>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_nested: Option<Box<DescriptorMap>>,
 }
 
-#[derive(Deserialize)]
+impl DescriptorMap {
+    /// Create a descriptor without a `path_nested`.
+    ///
+    /// Fails if the `path` is not a valid JSONPath expression.
+    pub fn new(id: String, format: String, path: String) -> Result<Self> {
+        JsonPath::parse(&path).context(format!(
+            "descriptor map path '{path}' is not valid JSONPath"
+        ))?;
+        Ok(Self {
+            id,
+            format,
+            path,
+            path_nested: None,
+        })
+    }
+
+    /// Set the descriptor of the value nested in the one that `path` refers to.
+    pub fn set_path_nested(&mut self, nested: DescriptorMap) {
+        self.path_nested = Some(Box::new(nested));
+    }
+
+    /// Resolve the value that this descriptor refers to, following `path_nested`.
+    ///
+    /// JWT-encoded values are decoded before any nested path is applied, so a nested path is
+    /// always evaluated relative to the claims of its parent. SD-JWT values are decoded into the
+    /// claims disclosed by the holder, and Data Integrity (`ldp_vp`, `ldp_vc`) values are parsed
+    /// as JSON. mdoc values are rejected, as they cannot be verified, see
+    /// [DeviceResponse](crate::core::credential_format::mso_mdoc::DeviceResponse). If a
+    /// `format` map is provided, the `alg` of each JWT and the proof type of each Data Integrity
+    /// value must be accepted by it.
+    ///
+    /// Also returns JSON pointers to the selectively disclosed claims of the resolved value, or
+    /// `None` if its format has no selective disclosure.
+    pub(crate) fn resolve(
+        &self,
+        value: &Json,
+        format: Option<&ClaimFormatMap>,
+    ) -> Result<(Json, Option<Vec<String>>)> {
+        let path = JsonPath::parse(&self.path).context(format!(
+            "descriptor map path '{}' is not valid JSONPath",
+            self.path
+        ))?;
+
+        let resolved = path.query(value).exactly_one().context(format!(
+            "descriptor map path '{}' does not resolve to a single value",
+            self.path
+        ))?;
+
+        let (resolved, disclosed) = match resolved {
+            Json::String(sd_jwt) if [VcSdJwt::ID, DcSdJwt::ID].contains(&self.format.as_str()) => {
+                let (presentation, (claims, disclosed)) = SdJwtPresentation::parse(sd_jwt)
+                    .and_then(|presentation| {
+                        let claims = presentation.disclosed_claims_and_pointers()?;
+                        Ok((presentation, claims))
+                    })
+                    .context(format!(
+                        "unable to decode the SD-JWT at descriptor map path '{}'",
+                        self.path
+                    ))?;
+                if claims.get("cnf").is_some() && presentation.key_binding_jwt.is_none() {
+                    bail!(
+                        "the SD-JWT at descriptor map path '{}' is bound to a key by 'cnf' but has no key binding JWT",
+                        self.path
+                    )
+                }
+                (claims, Some(disclosed))
+            }
+            Json::String(presentation)
+                if [LdpVp::ID, LdpVc::ID].contains(&self.format.as_str()) =>
+            {
+                let presentation = serde_json::from_str(presentation).context(format!(
+                    "the value at descriptor map path '{}' is not a JSON-LD object",
+                    self.path
+                ))?;
+                (presentation, None)
+            }
+            Json::String(_) if self.format == MsoMdoc::ID => bail!(
+                "the mdoc at descriptor map path '{}' cannot be verified, as the issuer signature of mdocs is not verified",
+                self.path
+            ),
+            Json::String(jwt) if self.format.starts_with("jwt") => {
+                if let Some(format) = format {
+                    validate_jwt_alg(jwt, &self.format, format)?;
+                }
+                let claims = decode_jwt(jwt).context(format!(
+                    "unable to decode the value at descriptor map path '{}'",
+                    self.path
+                ))?;
+                (claims, None)
+            }
+            other => (other.clone(), None),
+        };
+
+        validate_declared_format(&resolved, &self.format).context(format!(
+            "the value at descriptor map path '{}' does not match its format",
+            self.path
+        ))?;
+
+        if let Some(format) = format {
+            if [LdpVp::ID, LdpVc::ID].contains(&self.format.as_str()) {
+                validate_proof_type(&resolved, &self.format, format)?;
+            }
+        }
+
+        match &self.path_nested {
+            Some(nested) => nested.resolve(&resolved, format),
+            None => Ok((resolved, disclosed)),
+        }
+    }
+
+    /// This descriptor followed by each descriptor of its `path_nested` chain, outermost first.
+    pub fn nested_chain(&self) -> impl Iterator<Item = &DescriptorMap> {
+        std::iter::successors(Some(self), |descriptor| descriptor.path_nested.as_deref())
+    }
+
+    /// The innermost descriptor, following `path_nested`.
+    fn innermost(&self) -> &DescriptorMap {
+        self.nested_chain().last().unwrap_or(self)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SubmissionRequirementBaseBase {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub purpose: Option<String>,
     #[serde(flatten)]
     pub property_set: Option<Map<String, serde_json::Value>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum SubmissionRequirementBase {
     From {
-        from: String, // TODO `group` string??
+        /// The `group` of input descriptors to select from.
+        from: String,
         #[serde(flatten)]
         submission_requirement_base: SubmissionRequirementBaseBase,
     },
@@ -122,25 +1692,93 @@ pub enum SubmissionRequirementBase {
     },
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(tag = "rule", rename_all = "snake_case")]
 pub enum SubmissionRequirement {
     All(SubmissionRequirementBase),
     Pick(SubmissionRequirementPick),
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SubmissionRequirementPick {
     #[serde(flatten)]
     pub submission_requirement: SubmissionRequirementBase,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub min: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max: Option<u64>,
 }
 
+impl SubmissionRequirement {
+    /// Check whether this submission requirement is satisfied.
+    ///
+    /// `input_descriptors` are the input descriptors of the presentation definition, and
+    /// `submitted` are the ids of the input descriptors present in the presentation submission.
+    pub fn is_satisfied(&self, input_descriptors: &[InputDescriptor], submitted: &[&str]) -> bool {
+        match self {
+            SubmissionRequirement::All(base) => {
+                let (satisfied, total) = base.count_satisfied(input_descriptors, submitted);
+                satisfied == total
+            }
+            SubmissionRequirement::Pick(SubmissionRequirementPick {
+                submission_requirement,
+                count,
+                min,
+                max,
+            }) => {
+                let (satisfied, _) =
+                    submission_requirement.count_satisfied(input_descriptors, submitted);
+                let satisfied = satisfied as u64;
+                if let Some(count) = count {
+                    return satisfied == *count;
+                }
+                min.iter().all(|min| satisfied >= *min) && max.iter().all(|max| satisfied <= *max)
+            }
+        }
+    }
+}
+
+impl SubmissionRequirementBase {
+    /// Returns the number of satisfied members of the `from` group or `from_nested` requirements,
+    /// and the total number of members.
+    fn count_satisfied(
+        &self,
+        input_descriptors: &[InputDescriptor],
+        submitted: &[&str],
+    ) -> (usize, usize) {
+        match self {
+            SubmissionRequirementBase::From { from, .. } => {
+                let group: Vec<&InputDescriptor> = input_descriptors
+                    .iter()
+                    .filter(|descriptor| descriptor.group.contains(from))
+                    .collect();
+                let satisfied = group
+                    .iter()
+                    .filter(|descriptor| submitted.contains(&descriptor.id.as_str()))
+                    .count();
+                (satisfied, group.len())
+            }
+            SubmissionRequirementBase::FromNested { from_nested, .. } => {
+                let satisfied = from_nested
+                    .iter()
+                    .filter(|requirement| requirement.is_satisfied(input_descriptors, submitted))
+                    .count();
+                (satisfied, from_nested.len())
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
+    use crate::core::{
+        credential_format::sd_jwt::{disclosure_digest, test::sign_key_binding},
+        object::UntypedObject,
+        response::{parameters::VpToken, JwtAuthorizationResponse, UnencodedAuthorizationResponse},
+    };
     use serde_json::json;
     use std::{
         ffi::OsStr,
@@ -182,6 +1820,1393 @@ pub(crate) mod tests {
         let _: PresentationDefinition = serde_json::from_value(value).unwrap();
     }
 
+    fn vc_definition() -> PresentationDefinition {
+        serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "vc",
+                    "format": {
+                        "jwt_vc_json": {
+                            "alg": ["ES256"]
+                        }
+                    }
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn vc_response(definition_id: &str) -> Json {
+        json!({
+            "vp_token": include_str!("../tests/examples/vc.jwt"),
+            "presentation_submission": {
+                "id": "39881a17-e454-4d98-87ba-e3073d1014d6",
+                "definition_id": definition_id,
+                "descriptor_map": [
+                    {
+                        "id": "vc",
+                        "path": "$",
+                        "format": "jwt_vc_json"
+                    }
+                ]
+            }
+        })
+    }
+
+    /// An unencoded response to a definition with the `id` used in these tests, presenting the
+    /// `vp_token` with the given descriptor map.
+    fn authorization_response(vp_token: Json, descriptor_map: Json) -> AuthorizationResponse {
+        let object: UntypedObject = serde_json::from_value(json!({
+            "vp_token": vp_token,
+            "presentation_submission": {
+                "id": "39881a17-e454-4d98-87ba-e3073d1014d6",
+                "definition_id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "descriptor_map": descriptor_map
+            }
+        }))
+        .unwrap();
+        AuthorizationResponse::Unencoded(object.try_into().unwrap())
+    }
+
+    #[test]
+    fn sd_jwt_constraints() {
+        let disclosure =
+            BASE64_URL_SAFE_NO_PAD.encode(json!(["salt", "age_over_18", true]).to_string());
+        let issuer_jwt = ssi::jwt::encode_unsigned(&json!({
+            "iss": "https://issuer.example.com",
+            "vct": "https://credentials.example.com/identity_credential",
+            "_sd": [disclosure_digest(&disclosure)]
+        }))
+        .unwrap();
+
+        let definition = |path: &str| -> PresentationDefinition {
+            serde_json::from_value(json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [
+                    {
+                        "id": "pid",
+                        "constraints": {
+                            "fields": [
+                                {
+                                    "path": [path],
+                                    "filter": { "type": "boolean", "const": true }
+                                }
+                            ]
+                        }
+                    }
+                ]
+            }))
+            .unwrap()
+        };
+
+        let response = authorization_response(
+            json!(format!("{issuer_jwt}~{disclosure}~")),
+            json!([{ "id": "pid", "path": "$", "format": "vc+sd-jwt" }]),
+        );
+
+        definition("$.age_over_18")
+            .validate_authorization_response(&response)
+            .unwrap();
+
+        let error = definition("$.age_over_21")
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(matches!(error, Error::ConstraintsNotSatisfied(_)));
+    }
+
+    #[test]
+    fn sd_jwt_key_binding_required() {
+        let key: ssi::jwk::JWK =
+            serde_json::from_str(include_str!("../tests/examples/subject.jwk")).unwrap();
+        let issuer_jwt = ssi::jwt::encode_unsigned(&json!({
+            "iss": "https://issuer.example.com",
+            "cnf": { "jwk": key.to_public() }
+        }))
+        .unwrap();
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [{ "id": "pid" }]
+        }))
+        .unwrap();
+        let response = |presentation: &SdJwtPresentation| {
+            authorization_response(
+                json!(presentation.to_string()),
+                json!([{ "id": "pid", "path": "$", "format": "vc+sd-jwt" }]),
+            )
+        };
+
+        let mut presentation = SdJwtPresentation::parse(&format!("{issuer_jwt}~")).unwrap();
+        let error = definition
+            .validate_authorization_response(&response(&presentation))
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("has no key binding JWT"),
+            "{error:#}"
+        );
+
+        let claims = presentation.key_binding_claims("nonce", "did:example:verifier");
+        presentation.key_binding_jwt = Some(sign_key_binding(&claims, &key));
+        definition
+            .validate_authorization_response(&response(&presentation))
+            .unwrap();
+    }
+
+    #[test]
+    fn subject_is_issuer() {
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "vc",
+                    "constraints": { "subject_is_issuer": "required" }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let response = |subject: &str| -> AuthorizationResponse {
+            let vc = ssi::jwt::encode_unsigned(&json!({
+                "iss": "did:example:holder",
+                "vc": {
+                    "type": ["VerifiableCredential"],
+                    "credentialSubject": { "id": subject }
+                }
+            }))
+            .unwrap();
+            authorization_response(
+                json!(vc),
+                json!([{ "id": "vc", "path": "$", "format": "jwt_vc_json" }]),
+            )
+        };
+
+        definition
+            .validate_authorization_response(&response("did:example:holder"))
+            .unwrap();
+
+        let error = definition
+            .validate_authorization_response(&response("did:example:someone-else"))
+            .unwrap_err();
+        assert!(matches!(error, Error::ConstraintsNotSatisfied(_)));
+    }
+
+    #[test]
+    fn same_subject() {
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "identity",
+                    "constraints": {
+                        "fields": [{ "id": "name", "path": ["$.vc.credentialSubject.name"] }],
+                        "same_subject": [{ "field_id": ["name", "degree"], "directive": "required" }]
+                    }
+                },
+                {
+                    "id": "diploma",
+                    "constraints": {
+                        "fields": [{ "id": "degree", "path": ["$.vc.credentialSubject.degree"] }]
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let vc = |subject: &str, claim: &str| -> String {
+            ssi::jwt::encode_unsigned(&json!({
+                "iss": "https://issuer.example.com",
+                "vc": {
+                    "type": ["VerifiableCredential"],
+                    "credentialSubject": { "id": subject, claim: "value" }
+                }
+            }))
+            .unwrap()
+        };
+        let response = |diploma_subject: &str| -> AuthorizationResponse {
+            authorization_response(
+                json!([
+                    vc("did:example:holder", "name"),
+                    vc(diploma_subject, "degree")
+                ]),
+                json!([
+                    { "id": "identity", "path": "$[0]", "format": "jwt_vc_json" },
+                    { "id": "diploma", "path": "$[1]", "format": "jwt_vc_json" }
+                ]),
+            )
+        };
+
+        definition
+            .validate_authorization_response(&response("did:example:holder"))
+            .unwrap();
+
+        let error = definition
+            .validate_authorization_response(&response("did:example:someone-else"))
+            .unwrap_err();
+        assert!(matches!(error, Error::ConstraintsNotSatisfied(_)));
+    }
+
+    #[test]
+    fn mso_mdoc_unverifiable() {
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "org.iso.18013.5.1.mDL",
+                    "constraints": {
+                        "limit_disclosure": "required",
+                        "fields": [
+                            {
+                                "path": ["$['org.iso.18013.5.1']['family_name']"],
+                                "filter": { "type": "string", "const": "Doe" }
+                            },
+                            {
+                                "path": ["$['org.iso.18013.5.1']['age_over_18']"]
+                            }
+                        ]
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let response = authorization_response(
+            json!(crate::core::credential_format::mso_mdoc::test::device_response()),
+            json!([{ "id": "org.iso.18013.5.1.mDL", "path": "$", "format": "mso_mdoc" }]),
+        );
+
+        // The data elements satisfy the constraints, but their issuer signature is not verified.
+        let error = definition
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("cannot be verified"),
+            "{error:#}"
+        );
+    }
+
+    #[test]
+    fn limit_disclosure_required() {
+        let given_name =
+            BASE64_URL_SAFE_NO_PAD.encode(json!(["salt1", "given_name", "Alice"]).to_string());
+        let age_over_18 =
+            BASE64_URL_SAFE_NO_PAD.encode(json!(["salt2", "age_over_18", true]).to_string());
+        let issuer_jwt = ssi::jwt::encode_unsigned(&json!({
+            "iss": "https://issuer.example.com",
+            "vct": "https://credentials.example.com/identity_credential",
+            "_sd": [disclosure_digest(&given_name), disclosure_digest(&age_over_18)]
+        }))
+        .unwrap();
+
+        let definition = |limit_disclosure: &str| -> PresentationDefinition {
+            serde_json::from_value(json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [
+                    {
+                        "id": "pid",
+                        "constraints": {
+                            "limit_disclosure": limit_disclosure,
+                            "fields": [
+                                { "path": ["$.iss"] },
+                                { "path": ["$.age_over_18"] }
+                            ]
+                        }
+                    }
+                ]
+            }))
+            .unwrap()
+        };
+
+        let response = |disclosures: &[&str]| {
+            authorization_response(
+                json!(format!("{issuer_jwt}~{}~", disclosures.join("~"))),
+                json!([{ "id": "pid", "path": "$", "format": "vc+sd-jwt" }]),
+            )
+        };
+
+        definition("required")
+            .validate_authorization_response(&response(&[&age_over_18]))
+            .unwrap();
+
+        let error = definition("required")
+            .validate_authorization_response(&response(&[&given_name, &age_over_18]))
+            .unwrap_err();
+        assert!(matches!(error, Error::ConstraintsNotSatisfied(_)));
+        assert!(error.to_string().contains("/given_name"));
+
+        definition("preferred")
+            .validate_authorization_response(&response(&[&given_name, &age_over_18]))
+            .unwrap();
+    }
+
+    #[test]
+    fn ldp_vp_constraints() {
+        let mut definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "degree",
+                    "constraints": {
+                        "fields": [
+                            {
+                                "path": ["$.credentialSubject.degree.type"],
+                                "filter": { "type": "string", "const": "BachelorDegree" }
+                            }
+                        ]
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let response = authorization_response(
+            json!({
+                "@context": ["https://www.w3.org/2018/credentials/v1"],
+                "type": ["VerifiablePresentation"],
+                "verifiableCredential": [
+                    {
+                        "@context": ["https://www.w3.org/2018/credentials/v1"],
+                        "type": ["VerifiableCredential", "UniversityDegreeCredential"],
+                        "issuer": "did:example:issuer",
+                        "issuanceDate": "2024-01-01T00:00:00Z",
+                        "credentialSubject": {
+                            "id": "did:example:holder",
+                            "degree": { "type": "BachelorDegree" }
+                        },
+                        "proof": { "type": "DataIntegrityProof" }
+                    }
+                ],
+                "proof": {
+                    "type": "DataIntegrityProof",
+                    "challenge": "random123",
+                    "domain": "did:example:verifier"
+                }
+            }),
+            json!([
+                {
+                    "id": "degree",
+                    "path": "$",
+                    "format": "ldp_vp",
+                    "path_nested": {
+                        "id": "degree",
+                        "path": "$.verifiableCredential[0]",
+                        "format": "ldp_vc"
+                    }
+                }
+            ]),
+        );
+
+        definition
+            .validate_authorization_response(&response)
+            .unwrap();
+
+        let proof_type = |proof_type: &str| -> ClaimFormatMap {
+            serde_json::from_value(json!({
+                "ldp_vp": { "proof_type": [proof_type] },
+                "ldp_vc": { "proof_type": [proof_type] }
+            }))
+            .unwrap()
+        };
+        definition.format = Some(proof_type("DataIntegrityProof"));
+        definition
+            .validate_authorization_response(&response)
+            .unwrap();
+        definition.format = Some(proof_type("Ed25519Signature2018"));
+        let error = definition
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("'DataIntegrityProof' is not an accepted proof type"));
+    }
+
+    #[test]
+    fn proof_type_format() {
+        let json = json!({ "ldp_vp": { "proof_type": ["DataIntegrityProof"] } });
+
+        let format: ClaimFormatMap = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(
+            format.0[&ClaimFormatDesignation::LdpVp],
+            ClaimFormatPayload::ProofType(vec!["DataIntegrityProof".into()])
+        );
+        assert_eq!(serde_json::to_value(&format).unwrap(), json);
+    }
+
+    #[test]
+    fn claim_format_map_intersect_and_union() {
+        let format = |json: Json| -> ClaimFormatMap { serde_json::from_value(json).unwrap() };
+        let verifier = format(json!({
+            "jwt_vc_json": { "alg": ["ES256", "EdDSA"] },
+            "ldp_vp": { "proof_type": ["DataIntegrityProof"] },
+            "mso_mdoc": {}
+        }));
+        let wallet = format(json!({
+            "jwt_vc_json": { "alg": ["ES256", "ES384"] },
+            "mso_mdoc": { "alg": ["ES256"] }
+        }));
+
+        assert_eq!(
+            verifier.intersect(&wallet),
+            format(json!({
+                "jwt_vc_json": { "alg": ["ES256"] },
+                "mso_mdoc": { "alg": ["ES256"] }
+            }))
+        );
+        assert_eq!(
+            verifier.union(&wallet),
+            format(json!({
+                "jwt_vc_json": { "alg": ["ES256", "EdDSA", "ES384"] },
+                "ldp_vp": { "proof_type": ["DataIntegrityProof"] },
+                "mso_mdoc": {}
+            }))
+        );
+
+        let disjoint = format(json!({ "jwt_vc_json": { "alg": ["ES384"] } }));
+        assert_eq!(verifier.intersect(&disjoint), ClaimFormatMap::default());
+        assert_eq!(
+            verifier.union(&disjoint).0[&ClaimFormatDesignation::JwtVcJson],
+            ClaimFormatPayload::Alg(vec!["ES256".into(), "EdDSA".into(), "ES384".into()])
+        );
+    }
+
+    #[test]
+    fn unknown_format_designation() {
+        let json = json!({
+            "jwt_vc_json": { "alg": ["ES256"] },
+            "jwt_vc_json-ld": { "alg": ["ES256"] }
+        });
+
+        let format: ClaimFormatMap = serde_json::from_value(json.clone()).unwrap();
+        assert!(format.0.contains_key(&ClaimFormatDesignation::JwtVcJson));
+        assert!(format
+            .0
+            .contains_key(&ClaimFormatDesignation::Other("jwt_vc_json-ld".into())));
+        assert_eq!(serde_json::to_value(&format).unwrap(), json);
+
+        let mut definition = vc_definition();
+        definition.input_descriptors[0].set_format(
+            serde_json::from_value(json!({ "jwt_vc_json-ld": { "alg": ["ES256"] } })).unwrap(),
+        );
+        let object: UntypedObject = serde_json::from_value(vc_response(&definition.id)).unwrap();
+        let response = AuthorizationResponse::Unencoded(object.try_into().unwrap());
+        let error = definition
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("format 'jwt_vc_json' is not accepted"));
+    }
+
+    #[test]
+    fn from_json() {
+        let definition =
+            PresentationDefinition::from_json(&serde_json::to_string(&vc_definition()).unwrap())
+                .unwrap();
+        assert_eq!(definition, vc_definition());
+
+        let error =
+            PresentationDefinition::from_json(r#"{"id": "", "input_descriptors": [{"id": "vc"}]}"#)
+                .unwrap_err();
+        assert!(error.to_string().contains("'id' must not be empty"));
+
+        let error = PresentationDefinition::from_json(r#"{"id": "pd", "input_descriptors": []}"#)
+            .unwrap_err();
+        assert!(error.to_string().contains("at least one input descriptor"));
+
+        let error =
+            PresentationDefinition::from_json(r#"{"id": "pd", "input_descriptors": [{"id": ""}]}"#)
+                .unwrap_err();
+        assert!(error.to_string().contains("non-empty 'id'"));
+    }
+
+    #[test]
+    fn duplicate_input_descriptor_ids() {
+        let mut definition = vc_definition();
+        definition
+            .input_descriptors
+            .push(definition.input_descriptors[0].clone());
+
+        let error = definition.validate_structure().unwrap_err();
+        assert!(error.to_string().contains("'vc' is not unique"));
+
+        let object: UntypedObject = serde_json::from_value(vc_response(&definition.id)).unwrap();
+        let response = AuthorizationResponse::Unencoded(
+            UnencodedAuthorizationResponse::try_from(object).unwrap(),
+        );
+        definition
+            .validate_authorization_response(&response)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn definition_subset() {
+        let definition = |fields: Json| -> PresentationDefinition {
+            serde_json::from_value(json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{ "id": "pid", "constraints": { "fields": fields } }]
+            }))
+            .unwrap()
+        };
+
+        let approved = definition(json!([
+            { "path": ["$.given_name"] },
+            { "path": ["$.family_name"] }
+        ]));
+
+        assert!(approved.is_subset_of(&approved.clone()));
+
+        let subset = definition(json!([{ "path": ["$.family_name"] }]));
+        assert!(subset.is_subset_of(&approved));
+        assert!(!approved.is_subset_of(&subset));
+
+        let superset = definition(json!([
+            { "path": ["$.given_name"] },
+            { "path": ["$.family_name"] },
+            { "path": ["$.birthdate"] }
+        ]));
+        assert!(!superset.is_subset_of(&approved));
+        assert!(approved.is_subset_of(&superset));
+
+        let retained = definition(json!([{ "path": ["$.family_name"], "intent_to_retain": true }]));
+        assert!(!retained.is_subset_of(&approved));
+    }
+
+    #[test]
+    fn definition_subset_filter() {
+        let definition = |filter: Json| -> PresentationDefinition {
+            serde_json::from_value(json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{
+                    "id": "pid",
+                    "constraints": { "fields": [{ "path": ["$.type"], "filter": filter }] }
+                }]
+            }))
+            .unwrap()
+        };
+
+        let drivers_license = definition(json!({ "type": "string", "const": "DriversLicense" }));
+        let passport = definition(json!({ "type": "string", "const": "Passport" }));
+        assert!(!passport.is_subset_of(&drivers_license));
+        assert!(!drivers_license.is_subset_of(&passport));
+
+        let any_string = definition(json!({ "type": "string" }));
+        assert!(drivers_license.is_subset_of(&any_string));
+        assert!(!any_string.is_subset_of(&drivers_license));
+    }
+
+    #[test]
+    fn definition_subset_format() {
+        let definition = |format: Json| -> PresentationDefinition {
+            serde_json::from_value(json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{
+                    "id": "pid",
+                    "format": format,
+                    "constraints": { "fields": [{ "path": ["$.type"] }] }
+                }]
+            }))
+            .unwrap()
+        };
+
+        let es256 = definition(json!({ "jwt_vc_json": { "alg": ["ES256"] } }));
+        let es256_eddsa = definition(json!({ "jwt_vc_json": { "alg": ["ES256", "EdDSA"] } }));
+        assert!(es256.is_subset_of(&es256_eddsa));
+        assert!(!es256_eddsa.is_subset_of(&es256));
+
+        let mso_mdoc = definition(json!({ "mso_mdoc": { "alg": ["ES256"] } }));
+        assert!(!mso_mdoc.is_subset_of(&es256));
+    }
+
+    #[test]
+    fn limit_disclosure_without_fields() {
+        let definition = |constraints: Json| -> PresentationDefinition {
+            serde_json::from_value(json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{ "id": "pid", "constraints": constraints }]
+            }))
+            .unwrap()
+        };
+
+        for constraints in [
+            json!({ "limit_disclosure": "required" }),
+            json!({ "limit_disclosure": "preferred", "fields": [] }),
+        ] {
+            let error = definition(constraints).validate_structure().unwrap_err();
+            assert!(format!("{error:#}").contains("requires at least one constraint field"));
+        }
+
+        definition(json!({
+            "limit_disclosure": "required",
+            "fields": [{ "path": ["$.age_over_18"] }]
+        }))
+        .validate_structure()
+        .unwrap();
+    }
+
+    #[test]
+    fn unknown_descriptor_map_entry() {
+        let definition = vc_definition();
+        let mut response = vc_response(&definition.id);
+        response["presentation_submission"]["descriptor_map"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"id": "unknown", "format": "jwt_vp_json", "path": "$"}));
+        let object: UntypedObject = serde_json::from_value(response).unwrap();
+        let response = AuthorizationResponse::Unencoded(
+            UnencodedAuthorizationResponse::try_from(object).unwrap(),
+        );
+        let error = definition
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unknown input descriptor 'unknown'"));
+    }
+
+    #[test]
+    fn submission_requirements() {
+        let definition = |rule: Option<Json>| -> PresentationDefinition {
+            let mut definition = json!({
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [
+                    {
+                        "id": "vc",
+                        "group": ["A"],
+                        "format": { "jwt_vc_json": { "alg": ["ES256"] } }
+                    },
+                    { "id": "passport", "group": ["A"] }
+                ]
+            });
+            if let Some(rule) = rule {
+                definition["submission_requirements"] = json!([rule]);
+            }
+            serde_json::from_value(definition).unwrap()
+        };
+        let object: UntypedObject =
+            serde_json::from_value(vc_response(&vc_definition().id)).unwrap();
+        let response = AuthorizationResponse::Unencoded(
+            UnencodedAuthorizationResponse::try_from(object).unwrap(),
+        );
+
+        let pick = definition(Some(json!({ "rule": "pick", "count": 1, "from": "A" })));
+        pick.validate_authorization_response(&response).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PresentationDefinition>(&serde_json::to_string(&pick).unwrap())
+                .unwrap(),
+            pick
+        );
+
+        let error = definition(Some(json!({ "rule": "all", "from": "A" })))
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("submission requirement 0 is not satisfied"),
+            "{error:#}"
+        );
+
+        let error = definition(None)
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("'passport' is missing"),
+            "{error:#}"
+        );
+    }
+
+    #[test]
+    fn constraints_field() {
+        let credential = json!({
+            "vc": {
+                "type": ["VerifiableCredential"],
+                "credentialSubject": {
+                    "id": "did:example:subject"
+                }
+            }
+        });
+
+        let field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.vc.credentialSubject.id"],
+            "filter": {
+                "type": "string",
+                "const": "did:example:subject"
+            }
+        }))
+        .unwrap();
+        field.validate(&credential).unwrap();
+
+        let field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.vc.credentialSubject.id"],
+            "filter": {
+                "type": "string",
+                "const": "did:example:other"
+            }
+        }))
+        .unwrap();
+        let error = field.validate(&credential).unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+
+        let field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.vc.credentialSubject.name"]
+        }))
+        .unwrap();
+        let error = field.validate(&credential).unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+    }
+
+    #[test]
+    fn constraints_field_path_alternatives() {
+        let credential = json!({
+            "vc": {
+                "credentialSubject": {
+                    "id": "did:example:subject"
+                }
+            },
+            "sub": "did:example:other"
+        });
+
+        let field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.credentialSubject.id", "$.vc.credentialSubject.id"],
+            "filter": {
+                "type": "string",
+                "const": "did:example:subject"
+            }
+        }))
+        .unwrap();
+        field.validate(&credential).unwrap();
+
+        // Only the first path that resolves is used.
+        let field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.sub", "$.vc.credentialSubject.id"],
+            "filter": {
+                "type": "string",
+                "const": "did:example:subject"
+            }
+        }))
+        .unwrap();
+        let error = field.validate(&credential).unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+    }
+
+    #[test]
+    fn optional_constraints_field() {
+        let credential = json!({
+            "vc": {
+                "credentialSubject": {
+                    "id": "did:example:subject"
+                }
+            }
+        });
+
+        let field = |path: &str| -> ConstraintsField {
+            serde_json::from_value(json!({
+                "path": [path],
+                "filter": {
+                    "type": "string",
+                    "const": "did:example:other"
+                }
+            }))
+            .unwrap()
+        };
+
+        let mut optional_absent = field("$.vc.credentialSubject.name");
+        optional_absent.set_optional(true);
+        optional_absent.validate(&credential).unwrap();
+
+        let mut optional_present_invalid = field("$.vc.credentialSubject.id");
+        optional_present_invalid.set_optional(true);
+        let error = optional_present_invalid.validate(&credential).unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+
+        let required_absent = field("$.vc.credentialSubject.name");
+        assert!(!required_absent.is_optional());
+        let error = required_absent.validate(&credential).unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+    }
+
+    #[test]
+    fn predicate_constraints_field() {
+        let mut field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.credentialSubject.birthDate"],
+            "filter": {
+                "type": "string",
+                "const": "1990-01-01"
+            }
+        }))
+        .unwrap();
+
+        let boolean = json!({ "credentialSubject": { "birthDate": true } });
+        let raw = json!({ "credentialSubject": { "birthDate": "1990-01-01" } });
+
+        field.set_predicate(Predicate::Required);
+        field.validate(&boolean).unwrap();
+        let error = field.validate(&raw).unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+        let error = field
+            .validate(&json!({ "credentialSubject": { "birthDate": false } }))
+            .unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+
+        field.set_predicate(Predicate::Preferred);
+        field.validate(&boolean).unwrap();
+        field.validate(&raw).unwrap();
+    }
+
+    #[test]
+    fn constraints_field_filter() {
+        let mut field = ConstraintsField::new(
+            NonEmptyVec::new("$.credentialSubject.id".into()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        field
+            .set_filter(json!({ "type": "string", "pattern": "did:key:.*" }))
+            .unwrap();
+
+        field
+            .validate(&json!({ "credentialSubject": { "id": "did:key:z6Mk" } }))
+            .unwrap();
+        let error = field
+            .validate(&json!({ "credentialSubject": { "id": "https://example.com" } }))
+            .unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+
+        assert!(field.set_filter(json!({ "pattern": "(" })).is_err());
+        // An unsupported keyword would otherwise be ignored, accepting any value.
+        assert!(field
+            .set_filter(json!({ "type": "string", "$ref": "#/$defs/did" }))
+            .is_err());
+    }
+
+    #[test]
+    fn constraints_field_multiple_values() {
+        let field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.vp.verifiableCredential[*].type"],
+            "filter": { "const": ["VerifiableCredential", "UniversityDegreeCredential"] }
+        }))
+        .unwrap();
+        let presentation = |types: &[&str]| {
+            json!({
+                "vp": {
+                    "verifiableCredential": [
+                        { "type": ["VerifiableCredential", "IDCardCredential"] },
+                        { "type": types }
+                    ]
+                }
+            })
+        };
+
+        let credential = presentation(&["VerifiableCredential", "UniversityDegreeCredential"]);
+        assert_eq!(
+            field.validate(&credential).unwrap().as_deref(),
+            Some("/vp/verifiableCredential/1/type")
+        );
+        let evaluation = field.evaluate(&credential);
+        assert!(evaluation.filter_matched);
+        assert_eq!(
+            evaluation.value,
+            Some(json!([
+                "VerifiableCredential",
+                "UniversityDegreeCredential"
+            ]))
+        );
+
+        let error = field
+            .validate(&presentation(&["VerifiableCredential"]))
+            .unwrap_err();
+        assert!(error.is::<ConstraintsNotSatisfied>());
+        assert!(error.to_string().contains("none of the 2 values"));
+    }
+
+    #[test]
+    fn intent_to_retain() {
+        let mut field = ConstraintsField::new(
+            NonEmptyVec::new("$['org.iso.18013.5.1']['portrait']".into()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        field.set_intent_to_retain(true);
+
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "org.iso.18013.5.1.mDL",
+                    "constraints": { "fields": [field] }
+                }
+            ]
+        }))
+        .unwrap();
+        let serialized = serde_json::to_value(&definition).unwrap();
+        assert_eq!(
+            serialized["input_descriptors"][0]["constraints"]["fields"][0]["intent_to_retain"],
+            true
+        );
+        assert_eq!(
+            serde_json::from_value::<PresentationDefinition>(serialized).unwrap(),
+            definition
+        );
+
+        let requested_fields = definition.requested_fields();
+        assert_eq!(requested_fields.len(), 1);
+        assert!(requested_fields[0].intent_to_retain);
+    }
+
+    #[test]
+    fn constraints_field_parsed_once() {
+        let mut field: ConstraintsField = serde_json::from_value(json!({
+            "path": ["$.credentialSubject.id"],
+            "filter": { "type": "string", "const": "did:example:subject" }
+        }))
+        .unwrap();
+        let credential = json!({ "credentialSubject": { "id": "did:example:subject" } });
+
+        let first = field.validate(&credential).unwrap();
+        let parsed = field.parsed_path.0.get().unwrap() as *const _;
+        let filter = field.parsed_filter.0.get().unwrap() as *const _;
+        for _ in 0..10 {
+            assert_eq!(field.validate(&credential).unwrap(), first);
+        }
+        assert_eq!(field.parsed_path.0.get().unwrap() as *const _, parsed);
+        assert_eq!(field.parsed_filter.0.get().unwrap() as *const _, filter);
+
+        field.filter = Some(json!({ "type": "string", "const": "did:example:other" }));
+        assert!(field.validate(&credential).is_err());
+        field.filter = Some(json!({ "type": "string" }));
+
+        field.path = vec!["$.credentialSubject.name".to_owned()]
+            .try_into()
+            .unwrap();
+        assert!(field.validate(&credential).is_err());
+    }
+
+    #[test]
+    fn evaluate_partially_matching_credential() {
+        let input_descriptor: InputDescriptor = serde_json::from_value(json!({
+            "id": "diploma",
+            "constraints": {
+                "fields": [
+                    { "path": ["$.credentialSubject.name"] },
+                    {
+                        "path": ["$.credentialSubject.degree"],
+                        "filter": { "type": "string", "const": "Master" }
+                    },
+                    { "path": ["$.credentialSubject.gpa"], "optional": true },
+                    { "path": ["$.credentialSubject.university"] }
+                ]
+            }
+        }))
+        .unwrap();
+        let credential = json!({
+            "credentialSubject": { "name": "Alice", "degree": "Bachelor" }
+        });
+
+        let evaluation = input_descriptor.evaluate(&credential);
+        let results: Vec<_> = evaluation
+            .fields
+            .iter()
+            .map(|field| (field.resolved, field.filter_matched, field.value.clone()))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                (true, true, Some(json!("Alice"))),
+                (true, false, Some(json!("Bachelor"))),
+                (false, false, None),
+                (false, false, None),
+            ]
+        );
+        assert_eq!(evaluation.satisfied_count(), 2);
+        assert!(!evaluation.is_satisfied());
+    }
+
+    #[test]
+    fn validate_unencoded_authorization_response() {
+        let definition = vc_definition();
+        let object: UntypedObject = serde_json::from_value(vc_response(&definition.id)).unwrap();
+        let response = AuthorizationResponse::Unencoded(
+            UnencodedAuthorizationResponse::try_from(object).unwrap(),
+        );
+        definition
+            .validate_authorization_response(&response)
+            .unwrap();
+    }
+
+    #[test]
+    fn validate_jwt_authorization_response() {
+        let definition = vc_definition();
+        let response = AuthorizationResponse::Jwt(JwtAuthorizationResponse {
+            response: ssi::jwt::encode_unsigned(&vc_response(&definition.id)).unwrap(),
+        });
+        definition
+            .validate_authorization_response(&response)
+            .unwrap();
+
+        let response = AuthorizationResponse::Jwt(JwtAuthorizationResponse {
+            response: ssi::jwt::encode_unsigned(&vc_response("other")).unwrap(),
+        });
+        assert!(definition
+            .validate_authorization_response(&response)
+            .is_err());
+    }
+
+    #[test]
+    fn validate_unaccepted_alg() {
+        let mut definition = vc_definition();
+        definition.input_descriptors[0].set_format(ClaimFormatMap(BTreeMap::from([(
+            "jwt_vc_json".into(),
+            ClaimFormatPayload::Alg(vec!["EdDSA".into()]),
+        )])));
+        let object: UntypedObject = serde_json::from_value(vc_response(&definition.id)).unwrap();
+        let response = AuthorizationResponse::Unencoded(object.try_into().unwrap());
+
+        let error = definition
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("'ES256' is not an accepted algorithm"));
+    }
+
+    #[test]
+    fn descriptor_format_takes_precedence() {
+        let mut definition = vc_definition();
+        definition.input_descriptors[0].format = None;
+        definition.format = Some(
+            serde_json::from_value(json!({
+                "jwt_vc_json": {
+                    "alg": ["ES256", "EdDSA"]
+                }
+            }))
+            .unwrap(),
+        );
+        let object: UntypedObject = serde_json::from_value(vc_response(&definition.id)).unwrap();
+        let response = AuthorizationResponse::Unencoded(object.try_into().unwrap());
+
+        definition
+            .validate_authorization_response(&response)
+            .unwrap();
+
+        definition.input_descriptors[0].set_format(ClaimFormatMap(BTreeMap::from([(
+            "jwt_vc_json".into(),
+            ClaimFormatPayload::Alg(vec!["EdDSA".into()]),
+        )])));
+        let error = definition
+            .validate_authorization_response(&response)
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("'ES256' is not an accepted algorithm"));
+    }
+
+    #[test]
+    fn validate_multiple_vp_tokens() {
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "multiple",
+            "input_descriptors": [{ "id": "a" }, { "id": "b" }]
+        }))
+        .unwrap();
+        let vp = |holder: &str| {
+            ssi::jwt::encode_unsigned(&json!({
+                "vp": {
+                    "@context": ["https://www.w3.org/2018/credentials/v1"],
+                    "type": ["VerifiablePresentation"],
+                    "holder": holder
+                }
+            }))
+            .unwrap()
+        };
+        let response = |b_path: &str| {
+            let object: UntypedObject = serde_json::from_value(json!({
+                "vp_token": [vp("did:example:a"), vp("did:example:b")],
+                "presentation_submission": {
+                    "id": "submission",
+                    "definition_id": "multiple",
+                    "descriptor_map": [
+                        { "id": "a", "format": "jwt_vp_json", "path": "$[0]" },
+                        { "id": "b", "format": "jwt_vp_json", "path": b_path }
+                    ]
+                }
+            }))
+            .unwrap();
+            AuthorizationResponse::Unencoded(object.try_into().unwrap())
+        };
+
+        definition
+            .validate_authorization_response(&response("$[1]"))
+            .unwrap();
+        assert!(definition
+            .validate_authorization_response(&response("$[2]"))
+            .is_err());
+    }
+
+    #[test]
+    fn resolve_path_nested() {
+        let vc_jwt = include_str!("../tests/examples/vc.jwt");
+        let vp: Json = json!({
+            "vp": {
+                "@context": ["https://www.w3.org/2018/credentials/v1"],
+                "type": ["VerifiablePresentation"],
+                "verifiableCredential": [vc_jwt]
+            }
+        });
+        let descriptor = |nested_path: &str| -> DescriptorMap {
+            serde_json::from_value(json!({
+                "id": "vc",
+                "format": "jwt_vp_json",
+                "path": "$",
+                "path_nested": {
+                    "id": "vc",
+                    "format": "jwt_vc_json",
+                    "path": nested_path
+                }
+            }))
+            .unwrap()
+        };
+
+        let (credential, disclosed) = descriptor("$.vp.verifiableCredential[0]")
+            .resolve(&vp, None)
+            .unwrap();
+        assert_eq!(credential, decode_jwt(vc_jwt).unwrap());
+        assert_eq!(disclosed, None);
+
+        assert!(descriptor("$.vp.verifiableCredential[1]")
+            .resolve(&vp, None)
+            .is_err());
+    }
+
+    #[test]
+    fn presentation_submission_from_definition() {
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "vc",
+                    "format": {
+                        "jwt_vp_json": {},
+                        "jwt_vc_json": { "alg": ["ES256"] }
+                    }
+                },
+                { "id": "pid" }
+            ]
+        }))
+        .unwrap();
+
+        let vp = ssi::jwt::encode_unsigned(&json!({
+            "vp": {
+                "@context": ["https://www.w3.org/2018/credentials/v1"],
+                "type": ["VerifiablePresentation"],
+                "verifiableCredential": [include_str!("../tests/examples/vc.jwt")]
+            }
+        }))
+        .unwrap();
+        let issuer_jwt = ssi::jwt::encode_unsigned(&json!({
+            "iss": "https://issuer.example.com",
+            "vct": "https://credentials.example.com/identity_credential"
+        }))
+        .unwrap();
+
+        let matches = [
+            CredentialMatch {
+                input_descriptor_id: "vc".into(),
+                format: "jwt_vc_json".into(),
+                presentation_index: 0,
+                credential_index: Some(0),
+            },
+            CredentialMatch {
+                input_descriptor_id: "pid".into(),
+                format: VcSdJwt::ID.into(),
+                presentation_index: 1,
+                credential_index: None,
+            },
+        ];
+        let submission = PresentationSubmission::from_definition(&definition, &matches).unwrap();
+        assert_eq!(submission.definition_id, definition.id);
+        assert_eq!(
+            serde_json::to_value(&submission.descriptor_map).unwrap(),
+            json!([
+                {
+                    "id": "vc",
+                    "format": "jwt_vp_json",
+                    "path": "$[0]",
+                    "path_nested": {
+                        "id": "vc",
+                        "format": "jwt_vc_json",
+                        "path": "$.vp.verifiableCredential[0]"
+                    }
+                },
+                { "id": "pid", "format": "vc+sd-jwt", "path": "$[1]" }
+            ])
+        );
+
+        let response = UnencodedAuthorizationResponse::new(
+            VpToken::Multiple(vec![vp, format!("{issuer_jwt}~")]),
+            submission.try_into().unwrap(),
+        );
+        definition
+            .validate_authorization_response(&AuthorizationResponse::Unencoded(response))
+            .unwrap();
+
+        let error =
+            PresentationSubmission::from_definition(&vc_definition(), &matches[..1]).unwrap_err();
+        assert!(error.to_string().contains("'jwt_vp_json' is not accepted"));
+        assert!(PresentationSubmission::from_definition(&definition, &matches[..1]).is_err());
+    }
+
+    #[test]
+    fn descriptor_map_path_validation() {
+        let descriptor = DescriptorMap::new("vc".into(), "jwt_vp_json".into(), "$".into()).unwrap();
+        assert_eq!(descriptor.path, "$");
+        assert!(descriptor.path_nested.is_none());
+
+        let error = DescriptorMap::new("vc".into(), "jwt_vp_json".into(), "$[".into()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "descriptor map path '$[' is not valid JSONPath"
+        );
+    }
+
+    #[test]
+    fn declared_format_mismatch() {
+        let vp = ssi::jwt::encode_unsigned(&json!({
+            "vp": {
+                "@context": ["https://www.w3.org/2018/credentials/v1"],
+                "type": ["VerifiablePresentation"],
+                "verifiableCredential": [include_str!("../tests/examples/vc.jwt")]
+            }
+        }))
+        .unwrap();
+        let descriptor = |format: &str| -> DescriptorMap {
+            serde_json::from_value(json!({ "id": "vc", "format": format, "path": "$" })).unwrap()
+        };
+
+        descriptor("jwt_vp").resolve(&json!(vp), None).unwrap();
+
+        let error = descriptor("jwt_vc").resolve(&json!(vp), None).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "the value at descriptor map path '$' does not match its format: the declared format is 'jwt_vc', but the value is a presentation"
+        );
+
+        let ldp_vc = json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiableCredential"]
+        })
+        .to_string();
+        descriptor("ldp_vc").resolve(&json!(ldp_vc), None).unwrap();
+        assert!(descriptor("ldp_vp").resolve(&json!(ldp_vc), None).is_err());
+    }
+
+    #[test]
+    fn descriptor_lookup_and_nested_chain() {
+        let submission: PresentationSubmission = serde_json::from_value(json!({
+            "id": "39881a17-e454-4d98-87ba-e3073d1014d6",
+            "definition_id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "descriptor_map": [
+                { "id": "pid", "format": "vc+sd-jwt", "path": "$[1]" },
+                {
+                    "id": "vc",
+                    "format": "jwt_vp_json",
+                    "path": "$[0]",
+                    "path_nested": {
+                        "id": "vc",
+                        "format": "jwt_vc_json",
+                        "path": "$.vp.verifiableCredential[0]",
+                        "path_nested": {
+                            "id": "vc",
+                            "format": "jwt_vc_json",
+                            "path": "$.vc.evidence[0]"
+                        }
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert!(submission.descriptor_by_id("unknown").is_none());
+
+        let pid = submission.descriptor_by_id("pid").unwrap();
+        assert_eq!(pid.nested_chain().count(), 1);
+
+        let vc = submission.descriptor_by_id("vc").unwrap();
+        assert_eq!(
+            vc.nested_chain()
+                .map(|descriptor| descriptor.path.as_str())
+                .collect::<Vec<_>>(),
+            ["$[0]", "$.vp.verifiableCredential[0]", "$.vc.evidence[0]"]
+        );
+        assert_eq!(vc.innermost().path, "$.vc.evidence[0]");
+    }
+
+    #[test]
+    fn preserve_extension_properties() {
+        let json = r#"{"id":"0b4dd017-efa6-4a05-a269-9790fa3c22c2","input_descriptors":[{"id":"vc"}],"vendor_x":{"level":"high","tags":["a","b"]}}"#;
+
+        let definition = PresentationDefinition::from_json(json).unwrap();
+        assert_eq!(
+            definition.extra.get("vendor_x"),
+            Some(&json!({ "level": "high", "tags": ["a", "b"] }))
+        );
+        assert_eq!(serde_json::to_string(&definition).unwrap(), json);
+    }
+
+    #[test]
+    fn localized_purpose() {
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "purpose": "Proof of age",
+            "purpose#fr": "Preuve de l'âge",
+            "input_descriptors": [
+                {
+                    "id": "vc",
+                    "name": "Identity credential",
+                    "purpose": "To verify your age",
+                    "purpose#fr": "Pour vérifier votre âge"
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(definition.purpose_for_locale("fr"), Some("Preuve de l'âge"));
+        assert_eq!(definition.purpose_for_locale("de"), Some("Proof of age"));
+        assert_eq!(definition.name_for_locale("fr"), None);
+
+        let input_descriptor = &definition.input_descriptors[0];
+        assert_eq!(
+            input_descriptor.purpose_for_locale("fr"),
+            Some("Pour vérifier votre âge")
+        );
+        assert_eq!(
+            input_descriptor.purpose_for_locale("de"),
+            Some("To verify your age")
+        );
+        assert_eq!(
+            input_descriptor.name_for_locale("fr"),
+            Some("Identity credential")
+        );
+    }
+
+    #[test]
+    fn submission_requirement_group() {
+        let mut a1: InputDescriptor = serde_json::from_value(json!({ "id": "a1" })).unwrap();
+        a1.add_group("A".into());
+        let mut a2: InputDescriptor = serde_json::from_value(json!({ "id": "a2" })).unwrap();
+        a2.add_group("A".into());
+        let input_descriptors = [a1, a2];
+        assert_eq!(input_descriptors[0].groups(), ["A".to_string()]);
+
+        let requirement: SubmissionRequirement = serde_json::from_value(json!({
+            "name": "pick one",
+            "rule": "pick",
+            "count": 1,
+            "from": "A"
+        }))
+        .unwrap();
+
+        assert!(requirement.is_satisfied(&input_descriptors, &["a1"]));
+        assert!(requirement.is_satisfied(&input_descriptors, &["a2"]));
+        assert!(!requirement.is_satisfied(&input_descriptors, &[]));
+        assert!(!requirement.is_satisfied(&input_descriptors, &["a1", "a2"]));
+    }
+
     #[derive(Deserialize)]
     pub struct PresentationDefinitionTest {
         #[serde(alias = "presentation_definition")]
@@ -1,15 +1,33 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
 use std::{fmt::Debug, future::Future, pin::Pin, sync::Arc};
 
 use anyhow::{bail, Context, Result};
+use base64::prelude::*;
 use client::Client;
 use request_builder::RequestBuilder;
-use session::{Outcome, Session, SessionStore, Status};
+use serde_json::Value as Json;
+use session::{MemoryNonceStore, NonceStore, Outcome, OutcomeError, Session, SessionStore, Status};
+use ssi::did_resolve::{resolve_key, DIDResolver};
 use url::Url;
 use uuid::Uuid;
 
-use crate::core::{
-    object::{TypedParameter, UntypedObject},
-    response::AuthorizationResponse,
+use crate::{
+    core::{
+        authorization_request::parameters::{ClientId, Nonce, ResponseType, State},
+        credential_format::{
+            ldp,
+            sd_jwt::{self, SdJwtPresentation},
+        },
+        metadata::WalletMetadata,
+        object::{ParsingErrorContext, TypedParameter, UntypedObject},
+        response::{
+            id_token, parameters::IdToken, AuthorizationResponse, RedirectResponse,
+            UnencodedAuthorizationResponse,
+        },
+    },
+    presentation_exchange::ClaimFormatMap,
+    Error,
 };
 
 use by_reference::ByReference;
@@ -20,14 +38,56 @@ pub mod request_builder;
 pub mod request_signer;
 pub mod session;
 
+/// How often [Verifier::wait_for_status] polls the session store.
+#[cfg(not(target_arch = "wasm32"))]
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The default for [VerifierBuilder::with_max_presentation_size], 1 MiB.
+pub const DEFAULT_MAX_PRESENTATION_SIZE: usize = 1024 * 1024;
+
+/// The default for [VerifierBuilder::with_max_response_size], 4 MiB.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 4 * 1024 * 1024;
+
+/// The default for [VerifierBuilder::with_max_credentials].
+pub const DEFAULT_MAX_CREDENTIALS: usize = 16;
+
+/// The session did not reach the expected status in time, see [Verifier::wait_for_status].
+#[derive(Debug, thiserror::Error)]
+#[error("timed out waiting for the session status")]
+pub struct TimedOut;
+
 /// An OpenID4VP verifier, also known as the client.
 #[derive(Debug, Clone)]
 pub struct Verifier {
     client: Arc<dyn Client + Send + Sync>,
     default_request_params: UntypedObject,
     pass_by_reference: ByReference,
+    #[cfg(feature = "p256")]
+    response_decryption_key: Option<p256::SecretKey>,
     session_store: Arc<dyn SessionStore + Send + Sync>,
+    nonce_store: Arc<dyn NonceStore + Send + Sync>,
     submission_endpoint: Url,
+    redirect_uri: Option<Url>,
+    did_resolver: Option<SharedResolver>,
+    limits: ResponseLimits,
+}
+
+/// Limits on the authorization responses that a [Verifier] processes.
+#[derive(Debug, Clone, Copy)]
+struct ResponseLimits {
+    max_response_size: usize,
+    max_presentation_size: usize,
+    max_credentials: usize,
+}
+
+/// A DID resolver shared by the verifier, see [VerifierBuilder::with_did_resolver].
+#[derive(Clone)]
+struct SharedResolver(Arc<dyn DIDResolver + Send + Sync>);
+
+impl Debug for SharedResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedResolver")
+    }
 }
 
 impl Verifier {
@@ -36,6 +96,11 @@ impl Verifier {
         VerifierBuilder::default()
     }
 
+    /// The `client_id` of this verifier, as provided by its [Client].
+    pub fn client_id(&self) -> &ClientId {
+        self.client.id()
+    }
+
     /// Begin building a new authorization request (credential presentation).
     pub fn build_authorization_request(&self) -> RequestBuilder<'_> {
         RequestBuilder::new(self)
@@ -48,10 +113,58 @@ impl Verifier {
     /// ## Returns
     /// The status of the authorization request.
     pub async fn poll_status(&self, uuid: Uuid) -> Result<Status> {
-        self.session_store
-            .get_session(uuid)
+        let session = self.session_store.get_session(uuid).await?;
+        if session.is_expired() && !matches!(session.status, Status::Complete(_)) {
+            return Ok(Status::Expired);
+        }
+        Ok(session.status)
+    }
+
+    /// Wait until an authorization request reaches the `target` status, or is complete or
+    /// expired.
+    ///
+    /// The status is polled until it is at least `target`, or until the `timeout` elapses, in
+    /// which case a [TimedOut] error is returned.
+    ///
+    /// Not available when compiled for `wasm32`, as it relies on the `tokio` timer.
+    ///
+    /// ## Returns
+    /// The status of the authorization request.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn wait_for_status(
+        &self,
+        uuid: Uuid,
+        target: Status,
+        timeout: Duration,
+    ) -> Result<Status> {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let status = self.poll_status(uuid).await?;
+                if status >= target || matches!(status, Status::Complete(_) | Status::Expired) {
+                    return Ok(status);
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .map_err(|_| TimedOut)?
+    }
+
+    /// Retrieve the outcome of an authorization request with the `response_code` of the redirect
+    /// returned to the Wallet, see [Verifier::verify_response].
+    ///
+    /// This binds the application frontend that the Wallet is redirected to with the submission
+    /// of the Wallet, for cross-device flows.
+    pub async fn get_outcome_by_code(&self, response_code: &str) -> Result<Outcome> {
+        let session = self
+            .session_store
+            .get_session_by_response_code(response_code)
             .await
-            .map(|session| session.status)
+            .context("failed to retrieve session")?;
+        let Status::Complete(outcome) = session.status else {
+            bail!("the session is not complete")
+        };
+        Ok(outcome)
     }
 
     /// Retrieve an authorization request that was passed by-reference.
@@ -82,6 +195,9 @@ impl Verifier {
             .get_session(reference)
             .await
             .context("failed to retrieve session")?;
+        if session.is_expired() {
+            bail!("the session has expired")
+        }
         if session.status < Status::SentRequest {
             self.session_store
                 .update_status(reference, Status::SentRequest)
@@ -111,35 +227,472 @@ impl Verifier {
     /// If using the `direct_post` response mode, the wallet will submit the authorization response
     /// to `POST https://verifier.example.com/some/sub/path/<reference>`.
     ///
-    /// This will update the presentation status.
+    /// If using the `direct_post.jwt` response mode with an encrypted response, the response is
+    /// decrypted using the key set with [VerifierBuilder::with_response_decryption_key] before it
+    /// is passed to the validator function. Encrypted presentations in the `vp_token` are
+    /// decrypted with the same key. A signed response is verified against the key of the
+    /// holder of its presentations, which requires a DID resolver.
+    ///
+    /// The `state` of the response must match the `state` of the authorization request, if one was
+    /// issued, and each JWT presentation must contain the `nonce` of the authorization request and
+    /// have the `client_id` as its audience. The signature of each JWT presentation is verified
+    /// against the holder's key, resolved with the DID resolver. The issuer-signed JWT of each
+    /// SD-JWT presentation is verified against the key of its issuer, which must be a DID, and it
+    /// must have a key binding JWT, signed with the `cnf` key of the credential, that is bound in
+    /// the same way. The proof of each Data Integrity presentation, and of each credential in it,
+    /// is verified with the DID resolver, and the presentation proof must have the `nonce` as its
+    /// `challenge` and the `client_id` as its `domain`. Presentations in any other format,
+    /// including mdocs (see [DeviceResponse]), cannot be bound to the `nonce` and fail with
+    /// [OutcomeError::NonceMismatch]. If the `response_type` is `vp_token id_token`, the
+    /// response must also contain a self-issued `id_token` bound in the same way, whose signature
+    /// is verified (see [VerifierBuilder::with_did_resolver]), and its subject is set on a
+    /// successful outcome. The response must also satisfy the presentation definition or the DCQL
+    /// query of the session (see [DcqlQuery::validate_vp_token]). Otherwise the outcome is a
+    /// failure, with an [OutcomeError] describing why, and the validator function is not called.
+    ///
+    /// Before the response or any presentation is decrypted or decoded, it is checked against the
+    /// limits set with [VerifierBuilder::with_max_response_size],
+    /// [VerifierBuilder::with_max_presentation_size] and [VerifierBuilder::with_max_credentials],
+    /// and fails with [OutcomeError::LimitExceeded] if it exceeds them. The presentations of a
+    /// `response` JWT, or of an encrypted `vp_token`, are checked again once decoded.
+    ///
+    /// Responses to an expired session are rejected with an error, as are responses to a session
+    /// that is already complete, so that its `nonce` can't be replayed. Once a response has been
+    /// validated, successfully or not, the `nonce` of the session is consumed (see
+    /// [VerifierBuilder::with_nonce_store]) before the outcome is stored. Only the response that
+    /// consumes the `nonce` stores an outcome: any other response bound to the same `nonce`,
+    /// whether a concurrent response to the same session or a response to another session that
+    /// was issued the same `nonce`, is rejected with an [OutcomeError::Replay] error and leaves the
+    /// status of its session unchanged.
+    ///
+    /// This will update the presentation status, unless an error is returned.
+    ///
+    /// ## Returns
+    /// The redirect set with [VerifierBuilder::with_redirect_uri], if any, when the outcome is a
+    /// success. It should be returned to the Wallet as the JSON body of the response. The redirect
+    /// carries a random `response_code`, with which the application frontend can retrieve the
+    /// outcome, see [Verifier::get_outcome_by_code].
+    ///
+    /// [DcqlQuery::validate_vp_token]: crate::core::dcql_query::DcqlQuery::validate_vp_token
+    /// [DeviceResponse]: crate::core::credential_format::mso_mdoc::DeviceResponse
     pub async fn verify_response<F, Fut>(
         &self,
         reference: Uuid,
         authorization_response: AuthorizationResponse,
         validator_function: F,
-    ) -> Result<()>
+    ) -> Result<Option<RedirectResponse>>
     where
         F: FnOnce(Session, AuthorizationResponse) -> Pin<Box<Fut>>,
         Fut: Future<Output = Outcome>,
     {
         let session = self.session_store.get_session(reference).await?;
+        if session.is_expired() {
+            bail!("the session has expired")
+        }
+        if matches!(session.status, Status::Complete(_)) {
+            bail!("the nonce of the session has already been consumed by a response")
+        }
 
-        let outcome = validator_function(session, authorization_response).await;
+        let within_limits = validate_submitted_limits(&authorization_response, self.limits);
+
+        #[cfg(feature = "p256")]
+        let authorization_response = match (authorization_response, &self.response_decryption_key) {
+            (AuthorizationResponse::Jwt(jwt), Some(key))
+                if within_limits.is_ok() && jwt.is_encrypted() =>
+            {
+                AuthorizationResponse::Unencoded(jwt.decrypt(key)?)
+            }
+            (authorization_response, _) => authorization_response,
+        };
+
+        #[cfg(feature = "p256")]
+        let authorization_response = match authorization_response {
+            AuthorizationResponse::Unencoded(mut response)
+                if within_limits.is_ok() && response.vp_token().is_encrypted() =>
+            {
+                let key = self
+                    .response_decryption_key
+                    .as_ref()
+                    .context("a decryption key is required for an encrypted vp_token")?;
+                response.1 = response
+                    .1
+                    .decrypt(key)
+                    .context("unable to decrypt vp_token")?;
+                AuthorizationResponse::Unencoded(response)
+            }
+            authorization_response => authorization_response,
+        };
+
+        let resolver = self
+            .did_resolver
+            .as_ref()
+            .map(|SharedResolver(resolver)| resolver.as_ref() as &dyn DIDResolver);
+        let validation = match within_limits {
+            Ok(()) => {
+                validate_response(&session, &authorization_response, resolver, self.limits).await
+            }
+            Err(e) => Err(e),
+        };
+
+        let Nonce(nonce) = session.authorization_request_object.nonce();
+        if !self
+            .nonce_store
+            .consume(nonce)
+            .await
+            .context("failed to consume the nonce")?
+        {
+            bail!(OutcomeError::Replay)
+        }
+
+        let outcome = match validation {
+            Ok(subject) => match validator_function(session, authorization_response).await {
+                Outcome::Success {
+                    presentations,
+                    presentation_submission,
+                    ..
+                } => Outcome::Success {
+                    presentations,
+                    presentation_submission,
+                    subject,
+                },
+                outcome => outcome,
+            },
+            Err(e) => Outcome::Failure {
+                reason: format!("{e:#}"),
+                error: OutcomeError::from(&e),
+            },
+        };
+
+        let redirect = match (&outcome, &self.redirect_uri) {
+            (Outcome::Success { .. }, Some(redirect_uri)) => {
+                let response_code = BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+                self.session_store
+                    .set_response_code(reference, response_code.clone())
+                    .await
+                    .context("failed to store the response code")?;
+                Some(RedirectResponse::new(
+                    redirect_uri.clone(),
+                    Some(response_code),
+                ))
+            }
+            _ => None,
+        };
 
         self.session_store
             .update_status(reference, Status::Complete(outcome))
+            .await?;
+
+        Ok(redirect)
+    }
+}
+
+/// Checks that an authorization response belongs to the session, returning the subject of the
+/// self-issued `id_token`, if one was requested.
+async fn validate_response(
+    session: &Session,
+    authorization_response: &AuthorizationResponse,
+    resolver: Option<&dyn DIDResolver>,
+    limits: ResponseLimits,
+) -> Result<Option<String>> {
+    let response = match authorization_response {
+        AuthorizationResponse::Unencoded(response) => response.clone(),
+        AuthorizationResponse::Jwt(jwt) if jwt.is_signed() => {
+            let resolver = resolver.context(OutcomeError::InvalidResponseSignature)?;
+            jwt.decode_verified(resolver)
+                .await
+                .context(OutcomeError::InvalidResponseSignature)?
+        }
+        AuthorizationResponse::Jwt(jwt) => jwt.decode_unverified()?,
+    };
+
+    validate_limits(&response, limits)?;
+
+    if let Some(State(expected)) = session
+        .authorization_request_object
+        .get::<State>()
+        .transpose()?
+    {
+        let State(received) = response.0.get().parsing_error()?;
+        if received != expected {
+            bail!(OutcomeError::StateMismatch)
+        }
+    }
+
+    let Nonce(expected) = session.authorization_request_object.nonce();
+    let ClientId(client_id) = session.authorization_request_object.client_id();
+
+    for presentation in response.vp_token().presentations() {
+        // Data Integrity presentations are bound to the nonce and client by the `challenge` and
+        // `domain` of their proof.
+        if presentation.starts_with('{') {
+            let presentation: Json = serde_json::from_str(presentation)
+                .context("unable to parse Data Integrity presentation")?;
+            let proof = presentation
+                .get("proof")
+                .context("Data Integrity presentation has no proof")
+                .context(OutcomeError::InvalidPresentationSignature)?;
+            if proof.get("challenge").and_then(Json::as_str) != Some(expected) {
+                bail!(OutcomeError::NonceMismatch)
+            }
+            if proof.get("domain").and_then(Json::as_str) != Some(client_id) {
+                bail!(OutcomeError::AudienceMismatch)
+            }
+            let resolver = resolver
+                .context("a DID resolver is required to verify Data Integrity presentations")
+                .context(OutcomeError::InvalidPresentationSignature)?;
+            ldp::verify_presentation(&presentation, expected, client_id, resolver)
+                .await
+                .context(OutcomeError::InvalidPresentationSignature)?;
+            continue;
+        }
+
+        // SD-JWT presentations are bound to the nonce by their key binding JWT.
+        if presentation.contains('~') {
+            let resolver = resolver
+                .context("a DID resolver is required to verify SD-JWT presentations")
+                .context(OutcomeError::InvalidPresentationSignature)?;
+            let claims = SdJwtPresentation::parse(presentation)?
+                .verify(resolver)
+                .await
+                .map_err(|e| {
+                    let error = if e.is::<sd_jwt::InvalidSignature>() {
+                        OutcomeError::InvalidPresentationSignature
+                    } else {
+                        OutcomeError::NonceMismatch
+                    };
+                    e.context(error)
+                })?;
+            validate_binding(&claims, expected, client_id)?;
+            continue;
+        }
+
+        // Other presentations, such as mdocs whose issuer and device signatures are not verified,
+        // cannot be bound to the nonce, so they are rejected.
+        if presentation.split('.').count() != 3 {
+            return Err(anyhow::anyhow!(
+                "the nonce of a presentation that is not a JWT, SD-JWT or Data Integrity presentation cannot be checked"
+            )
+            .context(OutcomeError::NonceMismatch));
+        }
+
+        let claims = verify_jwt_presentation(presentation, resolver)
             .await
+            .context(OutcomeError::InvalidPresentationSignature)?;
+        validate_binding(&claims, expected, client_id)?;
+    }
+
+    let mut subject = None;
+    if session.authorization_request_object.response_type() == &ResponseType::VpTokenIdToken {
+        let IdToken(id_token) = response
+            .0
+            .get()
+            .parsing_error()
+            .context("the 'vp_token id_token' response type requires an id_token")?;
+
+        let claims: Json =
+            ssi::jwt::decode_unverified(&id_token).context("unable to decode id_token")?;
+        validate_binding(&claims, expected, client_id)?;
+
+        subject = Some(
+            id_token::verify_self_issued(&id_token, resolver)
+                .await
+                .context(OutcomeError::InvalidIdToken)?,
+        );
+    }
+
+    if let Some(dcql_query) = &session.dcql_query {
+        dcql_query
+            .validate_vp_token(response.vp_token())
+            .context(OutcomeError::DcqlQueryNotSatisfied)?;
+    }
+
+    let Some(presentation_definition) = &session.presentation_definition else {
+        return Ok(subject);
+    };
+
+    presentation_definition
+        .validate_authorization_response(authorization_response)
+        .map_err(|e| {
+            let error = if matches!(e, Error::ConstraintsNotSatisfied(_)) {
+                OutcomeError::ConstraintsNotSatisfied
+            } else {
+                OutcomeError::PresentationDefinitionNotSatisfied
+            };
+            anyhow::Error::from(e).context(error)
+        })?;
+
+    Ok(subject)
+}
+
+/// Verifies the signature of a JWT presentation, returning its claims.
+///
+/// The signing key is resolved with the `resolver` from the `kid` header, which must be a
+/// verification method of the holder, the `iss` of the presentation.
+async fn verify_jwt_presentation(
+    presentation: &str,
+    resolver: Option<&dyn DIDResolver>,
+) -> Result<Json> {
+    let claims: Json =
+        ssi::jwt::decode_unverified(presentation).context("unable to decode presentation JWT")?;
+    let Some(Json::String(holder)) = claims.get("iss") else {
+        bail!("presentation 'iss' was missing or not a string")
+    };
+
+    let kid = id_token::header_kid(presentation)?;
+    let (did, _f) = kid.split_once('#').context(format!(
+        "expected a DID verification method in 'kid' header, received '{kid}'"
+    ))?;
+    if did != holder {
+        bail!("DID from 'kid' ({did}) is not the holder ({holder})")
+    }
+
+    let resolver = resolver.context("a DID resolver is required to verify JWT presentations")?;
+    let key = resolve_key(&kid, resolver)
+        .await
+        .context("unable to resolve verification method from 'kid' header")?;
+
+    ssi::jwt::decode_verify(presentation, &key)
+        .context("presentation signature could not be verified")
+}
+
+/// Checks that a response, as submitted by the Wallet, does not exceed the [ResponseLimits], so
+/// that an oversized response is rejected before it is decrypted or decoded.
+fn validate_submitted_limits(
+    authorization_response: &AuthorizationResponse,
+    limits: ResponseLimits,
+) -> Result<()> {
+    match authorization_response {
+        AuthorizationResponse::Unencoded(response) => validate_limits(response, limits),
+        AuthorizationResponse::Jwt(jwt) if jwt.response.len() > limits.max_response_size => {
+            Err(anyhow::anyhow!(
+                "the response is {} bytes, more than the maximum of {}",
+                jwt.response.len(),
+                limits.max_response_size
+            )
+            .context(OutcomeError::LimitExceeded))
+        }
+        AuthorizationResponse::Jwt(_) => Ok(()),
+    }
+}
+
+/// Checks that the presentations of a response do not exceed the [ResponseLimits], so that
+/// oversized presentations are rejected before they are verified.
+fn validate_limits(
+    response: &UnencodedAuthorizationResponse,
+    limits: ResponseLimits,
+) -> Result<()> {
+    let presentations = response.vp_token().presentations();
+    let descriptors = response
+        .presentation_submission()
+        .map_or(0, |presentation_submission| {
+            presentation_submission.parsed().descriptor_map.len()
+        });
+
+    for (i, presentation) in presentations.iter().enumerate() {
+        if presentation.len() > limits.max_presentation_size {
+            return Err(anyhow::anyhow!(
+                "presentation {i} is {} bytes, more than the maximum of {}",
+                presentation.len(),
+                limits.max_presentation_size
+            )
+            .context(OutcomeError::LimitExceeded));
+        }
+    }
+
+    let credentials: usize = presentations
+        .iter()
+        .map(|presentation| credential_count(presentation))
+        .sum();
+    let count = credentials.max(descriptors);
+    if count > limits.max_credentials {
+        return Err(anyhow::anyhow!(
+            "the response contains {count} credentials, more than the maximum of {}",
+            limits.max_credentials
+        )
+        .context(OutcomeError::LimitExceeded));
+    }
+
+    Ok(())
+}
+
+/// The number of credentials in a presentation: the entries of the `verifiableCredential` of a
+/// JWT or Data Integrity presentation, or one for any other presentation.
+fn credential_count(presentation: &str) -> usize {
+    let claims: Option<Json> = if presentation.starts_with('{') {
+        serde_json::from_str(presentation).ok()
+    } else if !presentation.contains('~') && presentation.split('.').count() == 3 {
+        ssi::jwt::decode_unverified::<Json>(presentation)
+            .ok()
+            .and_then(|mut claims| claims.get_mut("vp").map(Json::take))
+    } else {
+        None
+    };
+
+    match claims
+        .as_ref()
+        .and_then(|vp| vp.get("verifiableCredential"))
+    {
+        Some(Json::Array(credentials)) => credentials.len().max(1),
+        _ => 1,
     }
 }
 
+/// Checks that the claims of a JWT bind it to the authorization request, through the `nonce` and
+/// an `aud` of the `client_id`. A missing claim is treated as a mismatch.
+fn validate_binding(claims: &Json, nonce: &str, client_id: &str) -> Result<()> {
+    if claims.get("nonce").and_then(Json::as_str) != Some(nonce) {
+        bail!(OutcomeError::NonceMismatch)
+    }
+
+    let audience_matches = match claims.get("aud") {
+        Some(Json::String(aud)) => aud == client_id,
+        Some(Json::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !audience_matches {
+        bail!(OutcomeError::AudienceMismatch)
+    }
+
+    Ok(())
+}
+
+/// Negotiate the formats of a presentation definition with the formats supported by a wallet.
+///
+/// Returns the formats accepted by both, with their common algorithms, see
+/// [ClaimFormatMap::intersect]. Fails if there are none, as the wallet could not present any
+/// credential that the verifier accepts.
+pub fn negotiate_format(
+    definition_format: &ClaimFormatMap,
+    wallet_metadata: &WalletMetadata,
+) -> Result<ClaimFormatMap> {
+    let supported = wallet_metadata.vp_formats_supported().claim_format_map();
+    let negotiated = definition_format.intersect(&supported);
+    if negotiated.0.is_empty() {
+        bail!(
+            "no common format and algorithm: the verifier accepts {} and the wallet supports {}",
+            serde_json::to_string(definition_format)?,
+            serde_json::to_string(&supported)?
+        )
+    }
+    Ok(negotiated)
+}
+
 /// Builder struct for [Verifier].
 #[derive(Debug, Clone, Default)]
 pub struct VerifierBuilder {
     client: Option<Arc<dyn Client + Send + Sync>>,
     default_request_params: UntypedObject,
     pass_by_reference: ByReference,
+    #[cfg(feature = "p256")]
+    response_decryption_key: Option<p256::SecretKey>,
     session_store: Option<Arc<dyn SessionStore + Send + Sync>>,
+    nonce_store: Option<Arc<dyn NonceStore + Send + Sync>>,
     submission_endpoint: Option<Url>,
+    redirect_uri: Option<Url>,
+    did_resolver: Option<SharedResolver>,
+    max_response_size: Option<usize>,
+    max_presentation_size: Option<usize>,
+    max_credentials: Option<usize>,
 }
 
 impl VerifierBuilder {
@@ -149,14 +702,27 @@ impl VerifierBuilder {
             client,
             default_request_params,
             pass_by_reference,
+            #[cfg(feature = "p256")]
+            response_decryption_key,
             session_store,
+            nonce_store,
             submission_endpoint,
+            redirect_uri,
+            did_resolver,
+            max_response_size,
+            max_presentation_size,
+            max_credentials,
         } = self;
 
         let Some(client) = client else {
             bail!("client is required, see `with_client`")
         };
 
+        client
+            .id()
+            .validate_for_scheme(client.scheme(), None)
+            .context("invalid client_id")?;
+
         let Some(session_store) = session_store else {
             bail!("session store is required, see `with_session_store`")
         };
@@ -169,8 +735,19 @@ impl VerifierBuilder {
             client,
             default_request_params,
             pass_by_reference,
+            #[cfg(feature = "p256")]
+            response_decryption_key,
             session_store,
+            nonce_store: nonce_store.unwrap_or_else(|| Arc::new(MemoryNonceStore::default())),
             submission_endpoint,
+            redirect_uri,
+            did_resolver,
+            limits: ResponseLimits {
+                max_response_size: max_response_size.unwrap_or(DEFAULT_MAX_RESPONSE_SIZE),
+                max_presentation_size: max_presentation_size
+                    .unwrap_or(DEFAULT_MAX_PRESENTATION_SIZE),
+                max_credentials: max_credentials.unwrap_or(DEFAULT_MAX_CREDENTIALS),
+            },
         })
     }
 
@@ -199,13 +776,29 @@ impl VerifierBuilder {
 
     /// Set the [Client](crate::verifier::client::Client) that the [Verifier] will use to identify
     /// itself to the Wallet.
+    ///
+    /// [VerifierBuilder::build] fails if the `client_id` of the client is not valid for its
+    /// `client_id_scheme`, see [ClientId::validate_for_scheme].
     pub fn with_client(mut self, client: Arc<dyn Client + Send + Sync>) -> Self {
         self.client = Some(client);
         self
     }
 
-    /// Set the [SessionStore](crate::verifier::session_store::SessionStore) that the [Verifier]
-    /// will use to maintain session state across transactions.
+    /// Set the key that the [Verifier] will use to decrypt authorization responses that were
+    /// encrypted for the `direct_post.jwt` response mode.
+    ///
+    /// The public key should be advertised to the Wallet in the client metadata `jwks`.
+    #[cfg(feature = "p256")]
+    pub fn with_response_decryption_key(mut self, key: p256::SecretKey) -> Self {
+        self.response_decryption_key = Some(key);
+        self
+    }
+
+    /// Set the [SessionStore] that the [Verifier] will use to maintain session state across
+    /// transactions.
+    ///
+    /// For a deployment with multiple verifier instances, this should be a store shared between
+    /// them, such as a database. [MemoryStore](session::MemoryStore) can be used for testing.
     pub fn with_session_store(
         mut self,
         session_store: Arc<dyn SessionStore + Send + Sync>,
@@ -214,10 +807,133 @@ impl VerifierBuilder {
         self
     }
 
+    /// Set the [NonceStore] that the [Verifier] will use to detect replayed authorization
+    /// responses.
+    ///
+    /// Defaults to a [MemoryNonceStore], which is not shared between verifier instances.
+    pub fn with_nonce_store(mut self, nonce_store: Arc<dyn NonceStore + Send + Sync>) -> Self {
+        self.nonce_store = Some(nonce_store);
+        self
+    }
+
     /// Set the [Url] that the [Verifier] will listen at to receive the presentation submission
     /// from the Wallet.
     pub fn with_submission_endpoint(mut self, endpoint: Url) -> Self {
         self.submission_endpoint = Some(endpoint);
         self
     }
+
+    /// Set the `redirect_uri` that the Wallet is asked to follow after a successful `direct_post`
+    /// submission, see [Verifier::verify_response].
+    pub fn with_redirect_uri(mut self, redirect_uri: Url) -> Self {
+        self.redirect_uri = Some(redirect_uri);
+        self
+    }
+
+    /// Set the DID resolver used to verify self-issued `id_token`s with a DID subject.
+    pub fn with_did_resolver(mut self, resolver: Arc<dyn DIDResolver + Send + Sync>) -> Self {
+        self.did_resolver = Some(SharedResolver(resolver));
+        self
+    }
+
+    /// Set the maximum size in bytes of the `response` JWT of an authorization response, for the
+    /// `direct_post.jwt` response mode, as submitted by the Wallet.
+    ///
+    /// Defaults to [DEFAULT_MAX_RESPONSE_SIZE].
+    pub fn with_max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = Some(max_response_size);
+        self
+    }
+
+    /// Set the maximum size in bytes of each presentation in the `vp_token` of an authorization
+    /// response, as submitted by the Wallet.
+    ///
+    /// Defaults to [DEFAULT_MAX_PRESENTATION_SIZE].
+    pub fn with_max_presentation_size(mut self, max_presentation_size: usize) -> Self {
+        self.max_presentation_size = Some(max_presentation_size);
+        self
+    }
+
+    /// Set the maximum number of credentials in an authorization response, which bounds the
+    /// presentations in the `vp_token`, the credentials nested in their `verifiableCredential`,
+    /// and the entries of the presentation submission.
+    ///
+    /// Defaults to [DEFAULT_MAX_CREDENTIALS].
+    pub fn with_max_credentials(mut self, max_credentials: usize) -> Self {
+        self.max_credentials = Some(max_credentials);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    use crate::core::authorization_request::{
+        parameters::ClientIdScheme, AuthorizationRequestObject,
+    };
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct UnsignedClient(ClientId, ClientIdScheme);
+
+    #[async_trait]
+    impl Client for UnsignedClient {
+        fn id(&self) -> &ClientId {
+            &self.0
+        }
+
+        fn scheme(&self) -> &ClientIdScheme {
+            &self.1
+        }
+
+        async fn generate_request_object_jwt(
+            &self,
+            _body: &AuthorizationRequestObject,
+        ) -> Result<String> {
+            bail!("not implemented")
+        }
+    }
+
+    #[tokio::test]
+    async fn mismatched_client_id_scheme() {
+        let builder = |id: &str| {
+            Verifier::builder()
+                .with_client(Arc::new(UnsignedClient(
+                    ClientId(id.into()),
+                    ClientIdScheme::Did,
+                )))
+                .with_session_store(Arc::new(session::MemoryStore::default()))
+                .with_submission_endpoint("https://verifier.example.com/response".parse().unwrap())
+        };
+
+        builder("did:example:verifier").build().await.unwrap();
+        assert!(builder("https://verifier.example.com")
+            .build()
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn negotiate_format_without_common_algorithm() {
+        let wallet_metadata = WalletMetadata::openid4vp_scheme_static();
+        let format = |alg: &str| -> ClaimFormatMap {
+            serde_json::from_value(json!({ "jwt_vc_json": { "alg": [alg] } })).unwrap()
+        };
+
+        assert_eq!(
+            negotiate_format(&format("ES256"), &wallet_metadata).unwrap(),
+            format("ES256")
+        );
+
+        let error = negotiate_format(&format("ES384"), &wallet_metadata).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("no common format and algorithm"));
+        assert!(error
+            .to_string()
+            .contains(r#"{"jwt_vc_json":{"alg":["ES384"]}}"#));
+    }
 }
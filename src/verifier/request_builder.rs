@@ -1,3 +1,5 @@
+use std::time::{Duration, SystemTime};
+
 use anyhow::{bail, Context, Result};
 use url::Url;
 use uuid::Uuid;
@@ -6,7 +8,10 @@ use crate::{
     core::{
         authorization_request::{
             self,
-            parameters::{ResponseMode, ResponseType, ResponseUri},
+            parameters::{
+                ClientMetadata, ClientMetadataUri, DcqlQuery, Nonce, PresentationDefinitionUri,
+                RedirectUri, ResponseMode, ResponseType, ResponseUri,
+            },
             AuthorizationRequest, AuthorizationRequestObject, RequestIndirection,
         },
         metadata::{
@@ -24,16 +29,22 @@ use super::{session::Session, Verifier};
 #[derive(Debug, Clone)]
 #[must_use]
 pub struct RequestBuilder<'a> {
+    dcql_query: Option<crate::core::dcql_query::DcqlQuery>,
     presentation_definition: Option<PresentationDefinition>,
+    presentation_definition_uri: Option<Url>,
     request_parameters: UntypedObject,
+    ttl: Option<Duration>,
     verifier: &'a Verifier,
 }
 
 impl<'a> RequestBuilder<'a> {
     pub(crate) fn new(verifier: &'a Verifier) -> Self {
         Self {
+            dcql_query: None,
             presentation_definition: None,
+            presentation_definition_uri: None,
             request_parameters: verifier.default_request_params.clone(),
+            ttl: None,
             verifier,
         }
     }
@@ -47,19 +58,61 @@ impl<'a> RequestBuilder<'a> {
         self
     }
 
+    /// Set a DCQL query, requesting credentials without a presentation definition.
+    ///
+    /// A DCQL query and a presentation definition are mutually exclusive.
+    pub fn with_dcql_query(mut self, dcql_query: crate::core::dcql_query::DcqlQuery) -> Self {
+        self.dcql_query = Some(dcql_query);
+        self
+    }
+
+    /// Pass the presentation definition by reference, at a URL where it is hosted.
+    ///
+    /// The presentation definition must still be set with
+    /// [RequestBuilder::with_presentation_definition], so that the [Verifier] can validate the
+    /// response against it.
+    pub fn with_presentation_definition_uri(mut self, uri: Url) -> Self {
+        self.presentation_definition_uri = Some(uri);
+        self
+    }
+
     /// Set or override the default authorization request parameters.
     pub fn with_request_parameter<T: TypedParameter>(mut self, t: T) -> Self {
         self.request_parameters.insert(t);
         self
     }
 
+    /// Set how long the session for this request lasts.
+    ///
+    /// Once the TTL has elapsed, the status of the session is [Status::Expired] unless the
+    /// response has already been processed, and late requests and responses from the wallet are
+    /// rejected. By default, sessions do not expire.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
     /// Build the request.
     ///
+    /// Fails if mutually exclusive parameters are both set, such as `client_metadata` and
+    /// `client_metadata_uri`, or a presentation definition set by value in the request
+    /// parameters and a `presentation_definition_uri`.
+    ///
+    /// If the `response_mode` is `direct_post` or `direct_post.jwt` and no [ResponseUri] has been
+    /// set, the `response_uri` will be `<submission-endpoint>/<uuid>`.
+    ///
+    /// Fails if the `client_id` is not valid for the `client_id_scheme`, see
+    /// [ClientId::validate_for_scheme](authorization_request::parameters::ClientId::validate_for_scheme).
+    ///
+    /// If no [Nonce] has been set, a random one is generated (see [Nonce::random]). A nonce that
+    /// is shorter than [Nonce::MIN_LENGTH] is rejected.
+    ///
     /// ## Returns
     /// - UUID that can be used by the application frontend to poll for the status of this request.
     /// - URL that the application frontend should use to drive the user to their wallet application.
     pub async fn build(mut self, wallet_metadata: WalletMetadata) -> Result<(Uuid, Url)> {
         let uuid = Uuid::new_v4();
+        let expires_at = self.ttl.map(|ttl| SystemTime::now() + ttl);
 
         let client_id = self.verifier.client.id();
         let client_id_scheme = self.verifier.client.scheme();
@@ -67,16 +120,65 @@ impl<'a> RequestBuilder<'a> {
         let _ = self.request_parameters.insert(client_id.clone());
         let _ = self.request_parameters.insert(client_id_scheme.clone());
 
-        let Some(presentation_definition) = self.presentation_definition else {
-            bail!("presentation definition is required, see `with_presentation_definition`")
-        };
+        match (&self.presentation_definition, &self.dcql_query) {
+            (None, None) => bail!(
+                "presentation definition or DCQL query is required, see `with_presentation_definition` or `with_dcql_query`"
+            ),
+            (Some(_), Some(_)) => {
+                bail!("a presentation definition and a DCQL query are mutually exclusive")
+            }
+            (Some(presentation_definition), None) => {
+                if let Some(uri) = self.presentation_definition_uri {
+                    let _ = self
+                        .request_parameters
+                        .insert(PresentationDefinitionUri(uri));
+                } else {
+                    let _ = self.request_parameters.insert(
+                        authorization_request::parameters::PresentationDefinition::try_from(
+                            presentation_definition.clone(),
+                        )
+                        .context("failed to construct PresentationDefinition request parameter")?,
+                    );
+                }
+            }
+            (None, Some(dcql_query)) => {
+                dcql_query
+                    .validate_structure()
+                    .context("invalid DCQL query")?;
+                let _ = self.request_parameters.insert(
+                    DcqlQuery::try_from(dcql_query.clone())
+                        .context("failed to construct DcqlQuery request parameter")?,
+                );
+            }
+        }
 
-        let _ = self.request_parameters.insert(
-            authorization_request::parameters::PresentationDefinition::try_from(
-                presentation_definition.clone(),
-            )
-            .context("failed to construct PresentationDefinition request parameter")?,
-        );
+        for (a, b) in [
+            (
+                authorization_request::parameters::PresentationDefinition::KEY,
+                PresentationDefinitionUri::KEY,
+            ),
+            (DcqlQuery::KEY, PresentationDefinitionUri::KEY),
+            (ClientMetadata::KEY, ClientMetadataUri::KEY),
+        ] {
+            if self.request_parameters.0.contains_key(a)
+                && self.request_parameters.0.contains_key(b)
+            {
+                bail!("request parameters '{a}' and '{b}' are mutually exclusive")
+            }
+        }
+
+        match self.request_parameters.get::<Nonce>().transpose()? {
+            None => {
+                self.request_parameters.insert(Nonce::random());
+            }
+            Some(Nonce(nonce)) if nonce.len() < Nonce::MIN_LENGTH => {
+                bail!(
+                    "the nonce must be at least {} characters long",
+                    Nonce::MIN_LENGTH
+                )
+            }
+            Some(_) => (),
+        }
 
         let _ = self
             .request_parameters
@@ -90,7 +192,9 @@ impl<'a> RequestBuilder<'a> {
             .context("response mode is required, see `with_request_parameter`")?
             .context("error occurred when retrieving response mode")?
         {
-            ResponseMode::DirectPost | ResponseMode::DirectPostJwt => {
+            ResponseMode::DirectPost | ResponseMode::DirectPostJwt
+                if self.request_parameters.get::<ResponseUri>().is_none() =>
+            {
                 let mut uri = self.verifier.submission_endpoint.clone();
                 {
                     let Ok(mut path) = uri.path_segments_mut() else {
@@ -100,9 +204,22 @@ impl<'a> RequestBuilder<'a> {
                 }
                 self.request_parameters.insert(ResponseUri(uri));
             }
+            ResponseMode::DirectPost | ResponseMode::DirectPostJwt => (),
             ResponseMode::Unsupported(r) => bail!("unsupported response_mode: {r}"),
         }
 
+        let return_uri = match self.request_parameters.get::<ResponseUri>() {
+            Some(response_uri) => Some(response_uri.parsing_error()?.0),
+            None => self
+                .request_parameters
+                .get::<RedirectUri>()
+                .transpose()?
+                .map(|RedirectUri(uri)| uri),
+        };
+        client_id
+            .validate_for_scheme(client_id_scheme, return_uri.as_ref())
+            .context("invalid client_id")?;
+
         if !wallet_metadata
             .get_or_default::<ClientIdSchemesSupported>()?
             .0
@@ -155,7 +272,9 @@ impl<'a> RequestBuilder<'a> {
             status: initial_status,
             authorization_request_jwt,
             authorization_request_object,
-            presentation_definition,
+            presentation_definition: self.presentation_definition,
+            dcql_query: self.dcql_query,
+            expires_at,
         };
 
         self.verifier
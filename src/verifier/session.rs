@@ -1,13 +1,22 @@
-use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    sync::Arc,
+    time::SystemTime,
+};
 
-use anyhow::{bail, Error, Ok, Result};
+use anyhow::{bail, Context, Error, Ok, Result};
 use async_trait::async_trait;
+use serde_json::Value as Json;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
 use crate::{
-    core::authorization_request::AuthorizationRequestObject,
-    presentation_exchange::PresentationDefinition,
+    core::{
+        authorization_request::AuthorizationRequestObject, dcql_query::DcqlQuery,
+        response::AuthorizationResponse,
+    },
+    presentation_exchange::{PresentationDefinition, PresentationSubmission},
 };
 
 #[derive(Debug, Clone)]
@@ -16,7 +25,19 @@ pub struct Session {
     pub status: Status,
     pub authorization_request_jwt: String,
     pub authorization_request_object: AuthorizationRequestObject,
-    pub presentation_definition: PresentationDefinition,
+    pub presentation_definition: Option<PresentationDefinition>,
+    pub dcql_query: Option<DcqlQuery>,
+    /// When the session expires, if it was built with a TTL.
+    pub expires_at: Option<SystemTime>,
+}
+
+impl Session {
+    /// Whether the session has expired, see
+    /// [RequestBuilder::with_ttl](super::request_builder::RequestBuilder::with_ttl).
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| SystemTime::now() >= expires_at)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -29,6 +50,8 @@ pub enum Status {
     ReceivedResponse,
     /// Verifier has finished processing the response.
     Complete(Outcome),
+    /// The session expired before the verifier finished processing a response.
+    Expired,
 }
 
 #[derive(Debug, Clone)]
@@ -36,12 +59,86 @@ pub enum Outcome {
     /// An error occurred during response processing.
     Error { cause: Arc<Error> },
     /// The authorization response did not pass verification.
-    Failure { reason: String },
+    Failure {
+        /// A human-readable description of the failure.
+        reason: String,
+        /// Why the authorization response did not pass verification.
+        error: OutcomeError,
+    },
     /// The authorization response is verified.
-    Success,
+    Success {
+        /// The submitted presentations, decoded if they are JWTs.
+        presentations: Vec<Json>,
+        /// The presentation submission describing the presentations, absent for a response to a
+        /// DCQL query.
+        presentation_submission: Option<PresentationSubmission>,
+        /// The subject of the verified self-issued `id_token`, if one was requested.
+        subject: Option<String>,
+    },
+}
+
+/// Why an authorization response did not pass verification.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OutcomeError {
+    /// The authorization response could not be decoded.
+    #[error("the authorization response is invalid")]
+    InvalidResponse,
+    /// The `state` does not match the authorization request.
+    #[error("'state' does not match the authorization request")]
+    StateMismatch,
+    /// A presentation is not bound to the `nonce` of the authorization request.
+    #[error("presentation 'nonce' does not match the authorization request")]
+    NonceMismatch,
+    /// A presentation is not bound to the `client_id` of the authorization request.
+    #[error("presentation 'aud' does not match the client_id")]
+    AudienceMismatch,
+    /// The presentation submission does not match the presentation definition.
+    #[error("the presentation submission does not match the presentation definition")]
+    PresentationDefinitionNotSatisfied,
+    /// A presented credential does not satisfy the constraints of an input descriptor.
+    #[error("the input descriptor constraints are not satisfied")]
+    ConstraintsNotSatisfied,
+    /// The presentations do not satisfy the DCQL query of the authorization request.
+    #[error("the presentations do not satisfy the DCQL query")]
+    DcqlQueryNotSatisfied,
+    /// The signature of a signed authorization response could not be verified.
+    #[error("the authorization response signature could not be verified")]
+    InvalidResponseSignature,
+    /// The signature of a presentation could not be verified.
+    #[error("the presentation signature could not be verified")]
+    InvalidPresentationSignature,
+    /// The self-issued `id_token` could not be verified.
+    #[error("the id_token could not be verified")]
+    InvalidIdToken,
+    /// The `nonce` was already consumed by another authorization response. This is returned as
+    /// an error by [Verifier::verify_response](super::Verifier::verify_response), and is never
+    /// the outcome of a session.
+    #[error("the 'nonce' has already been consumed by another response")]
+    Replay,
+    /// The authorization response exceeds the limits of the verifier, see
+    /// [VerifierBuilder::with_max_presentation_size](super::VerifierBuilder::with_max_presentation_size).
+    #[error("the authorization response exceeds the limits of the verifier")]
+    LimitExceeded,
+    /// The authorization response was rejected by the validator function.
+    #[error("the authorization response was rejected")]
+    Rejected,
+}
+
+impl From<&Error> for OutcomeError {
+    fn from(error: &Error) -> Self {
+        error
+            .downcast_ref::<OutcomeError>()
+            .cloned()
+            .unwrap_or(OutcomeError::InvalidResponse)
+    }
 }
 
 /// Storage interface for session information.
+///
+/// The [Verifier](super::Verifier) records each authorization request as a [Session] and moves
+/// it through the [Status] transitions as the wallet retrieves the request and submits its
+/// response. Implement this trait to keep sessions in an external backend, so that they are
+/// shared between verifier instances.
 #[async_trait]
 pub trait SessionStore: Debug {
     /// Store a new authorization request session.
@@ -55,6 +152,19 @@ pub trait SessionStore: Debug {
 
     /// Remove a session from the store.
     async fn remove_session(&self, uuid: Uuid) -> Result<()>;
+
+    /// Associate a response code with a session, see
+    /// [Verifier::get_outcome_by_code](super::Verifier::get_outcome_by_code).
+    #[allow(unused_variables)]
+    async fn set_response_code(&self, uuid: Uuid, response_code: String) -> Result<()> {
+        bail!("response codes are not supported by this session store")
+    }
+
+    /// Get the session that a response code was issued for.
+    #[allow(unused_variables)]
+    async fn get_session_by_response_code(&self, response_code: &str) -> Result<Session> {
+        bail!("response codes are not supported by this session store")
+    }
 }
 
 /// A local in-memory store. Not for production use!
@@ -65,6 +175,7 @@ pub trait SessionStore: Debug {
 #[derive(Debug, Clone, Default)]
 pub struct MemoryStore {
     store: Arc<Mutex<BTreeMap<Uuid, Session>>>,
+    response_codes: Arc<Mutex<BTreeMap<String, Uuid>>>,
 }
 
 #[async_trait]
@@ -93,11 +204,60 @@ impl SessionStore for MemoryStore {
 
     async fn remove_session(&self, uuid: Uuid) -> Result<()> {
         if self.store.try_lock()?.remove(&uuid).is_some() {
+            self.response_codes
+                .try_lock()?
+                .retain(|_, session| *session != uuid);
             return Ok(());
         }
 
         bail!("session not found")
     }
+
+    async fn set_response_code(&self, uuid: Uuid, response_code: String) -> Result<()> {
+        if !self.store.try_lock()?.contains_key(&uuid) {
+            bail!("session not found")
+        }
+        self.response_codes.try_lock()?.insert(response_code, uuid);
+        Ok(())
+    }
+
+    async fn get_session_by_response_code(&self, response_code: &str) -> Result<Session> {
+        let Some(uuid) = self.response_codes.try_lock()?.get(response_code).copied() else {
+            bail!("response code not found")
+        };
+        self.get_session(uuid).await
+    }
+}
+
+/// Storage interface for the nonces of validated authorization responses.
+///
+/// The [Verifier](super::Verifier) consumes the `nonce` of each authorization response before
+/// storing its outcome, and rejects any other response bound to the same `nonce` as a replay.
+/// [NonceStore::consume] must be atomic, so that only one of several concurrent responses
+/// consumes a `nonce`. Like the [SessionStore], this should be shared between verifier instances.
+#[async_trait]
+pub trait NonceStore: Debug {
+    /// Mark a nonce as consumed.
+    ///
+    /// Returns `false` if the nonce had already been consumed.
+    async fn consume(&self, nonce: &str) -> Result<bool>;
+}
+
+/// A local in-memory nonce store. Not for production use!
+///
+/// # Warning
+/// This in-memory store should only be used for test purposes, it will not work for a distributed
+/// deployment.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNonceStore {
+    consumed: Arc<Mutex<BTreeSet<String>>>,
+}
+
+#[async_trait]
+impl NonceStore for MemoryNonceStore {
+    async fn consume(&self, nonce: &str) -> Result<bool> {
+        Ok(self.consumed.try_lock()?.insert(nonce.to_owned()))
+    }
 }
 
 impl PartialEq for Outcome {
@@ -107,11 +267,77 @@ impl PartialEq for Outcome {
 }
 
 impl Outcome {
+    /// A successful outcome for an authorization response.
+    ///
+    /// JWT presentations are decoded without verifying their signatures, and JSON presentations
+    /// are parsed, so this should only be constructed once the response has been verified.
+    pub fn success(authorization_response: &AuthorizationResponse) -> Result<Self> {
+        let response = match authorization_response {
+            AuthorizationResponse::Unencoded(response) => response.clone(),
+            AuthorizationResponse::Jwt(jwt) => jwt.decode_unverified()?,
+        };
+
+        let presentations = response
+            .vp_token()
+            .presentations()
+            .into_iter()
+            .map(|presentation| {
+                if let Some(presentation) = serde_json::from_str::<Json>(presentation)
+                    .ok()
+                    .filter(Json::is_object)
+                {
+                    return Ok(presentation);
+                }
+                if presentation.split('.').count() != 3 {
+                    return Ok(Json::String(presentation.to_owned()));
+                }
+                ssi::jwt::decode_unverified(presentation)
+                    .context("unable to decode presentation JWT")
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Outcome::Success {
+            presentations,
+            presentation_submission: response
+                .presentation_submission()
+                .map(|presentation_submission| presentation_submission.parsed().clone()),
+            subject: None,
+        })
+    }
+
+    /// The submitted presentations, if the outcome is a success.
+    pub fn presentations(&self) -> Option<&[Json]> {
+        match self {
+            Outcome::Success { presentations, .. } => Some(presentations),
+            _ => None,
+        }
+    }
+
+    /// The presentation submission, if the outcome is a success of a response with one.
+    pub fn presentation_submission(&self) -> Option<&PresentationSubmission> {
+        match self {
+            Outcome::Success {
+                presentation_submission,
+                ..
+            } => presentation_submission.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The subject of the verified self-issued `id_token`, if the outcome is a success and an
+    /// `id_token` was requested.
+    pub fn subject(&self) -> Option<&str> {
+        match self {
+            Outcome::Success { subject, .. } => subject.as_deref(),
+            _ => None,
+        }
+    }
+
     fn ordering(&self) -> u8 {
         match self {
             Outcome::Error { .. } => 0,
             Outcome::Failure { .. } => 1,
-            Outcome::Success => 2,
+            Outcome::Success { .. } => 2,
         }
     }
 }
@@ -1,47 +1,314 @@
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
-use http::header::CONTENT_TYPE;
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderValue};
+use serde_json::{json, Value as Json};
+use ssi::{did_resolve::DIDResolver, jwk::JWK};
 use tracing::warn;
 use url::Url;
 
+#[cfg(feature = "p256")]
+use crate::core::{
+    authorization_request::parameters::ClientMetadata, metadata::parameters::verifier::JWKs,
+};
 use crate::core::{
     authorization_request::{
-        parameters::ResponseMode, verification::RequestVerifier, AuthorizationRequest,
-        AuthorizationRequestObject,
+        parameters::{ResponseMode, State},
+        verification::{did, RequestVerifier},
+        AuthorizationRequest, AuthorizationRequestObject,
+    },
+    credential_format::{
+        sd_jwt::{SdJwtPresentation, KEY_BINDING_JWT_TYP},
+        CredentialFormat, DcSdJwt, VcSdJwt,
     },
     metadata::WalletMetadata,
-    response::{AuthorizationResponse, PostRedirection},
-    util::{base_request, AsyncHttpClient},
+    object::{ParsingErrorContext, UntypedObject},
+    response::{
+        parameters::VpToken, AuthorizationResponse, JwtAuthorizationResponse, RedirectResponse,
+        UnencodedAuthorizationResponse,
+    },
+    util::AsyncHttpClient,
 };
+use crate::presentation_exchange::{
+    ClaimFormatDesignation, ClaimFormatPayload, CredentialMatch, PresentationDefinition,
+    PresentationSubmission,
+};
+use crate::Error;
+
+use holder_signer::HolderSigner;
 
-#[async_trait]
+pub mod holder_signer;
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait Wallet: RequestVerifier + Sync {
     type HttpClient: AsyncHttpClient + Send + Sync;
 
     fn metadata(&self) -> &WalletMetadata;
     fn http_client(&self) -> &Self::HttpClient;
 
-    async fn validate_request(&self, url: Url) -> Result<AuthorizationRequestObject> {
+    /// The signer of the holder, used to sign presentations and responses, see
+    /// [Wallet::present_credentials] and [Wallet::sign_response].
+    fn holder_signer(&self) -> Option<&(dyn HolderSigner + Send + Sync)> {
+        None
+    }
+
+    async fn validate_request(&self, url: Url) -> Result<AuthorizationRequestObject, Error> {
         let ar = AuthorizationRequest::from_url(url, &self.metadata().authorization_endpoint().0)
-            .context("unable to parse authorization request")?;
-        ar.validate(self)
+            .map_err(Error::InvalidRequest)?;
+        ar.validate(self).await
+    }
+
+    /// Verify the signature of a request object JWT that was signed by a DID.
+    ///
+    /// The signing key is resolved from the DID verification method in the `kid` header, which
+    /// must belong to the `client_id`. See [did::verify_with_resolver].
+    async fn verify_request_jwt(
+        &self,
+        request_object: &AuthorizationRequestObject,
+        request_jwt: String,
+        resolver: &dyn DIDResolver,
+    ) -> Result<()> {
+        did::verify_with_resolver(self.metadata(), request_object, request_jwt, None, resolver)
             .await
-            .context("unable to validate authorization request")
     }
 
+    /// The algorithms that the wallet supports for a format, from the `alg_values_supported` of
+    /// its `vp_formats_supported` metadata. Empty if the format is not supported or does not
+    /// list algorithms.
+    fn supported_algorithms(&self, format: &ClaimFormatDesignation) -> Vec<String> {
+        match self
+            .metadata()
+            .vp_formats_supported()
+            .claim_format_map()
+            .0
+            .remove(format)
+        {
+            Some(ClaimFormatPayload::Alg(alg)) => alg,
+            _ => vec![],
+        }
+    }
+
+    /// Find the held credentials that satisfy each input descriptor of a presentation
+    /// definition, e.g. to let the holder choose which credentials to present.
+    ///
+    /// A credential matches an input descriptor if its format is accepted and its claims satisfy
+    /// the constraint fields. Limited disclosure is not considered, as it depends on the claims
+    /// that are disclosed in the presentation.
+    fn match_credentials(
+        &self,
+        definition: &PresentationDefinition,
+        credentials: &[StoredCredential],
+    ) -> Vec<DescriptorMatch> {
+        definition
+            .input_descriptors
+            .iter()
+            .flat_map(|input_descriptor| {
+                credentials.iter().filter_map(move |credential| {
+                    if !input_descriptor
+                        .accepts_format(&credential.format, definition.format.as_ref())
+                    {
+                        return None;
+                    }
+                    let pointers = input_descriptor
+                        .validate_constraints_fields(&credential.claims)
+                        .ok()?;
+                    let fields = pointers
+                        .into_iter()
+                        .filter_map(|pointer| {
+                            let value = credential.claims.pointer(&pointer)?.clone();
+                            Some((pointer, value))
+                        })
+                        .collect();
+                    Some(DescriptorMatch {
+                        input_descriptor_id: input_descriptor.id.clone(),
+                        credential_id: credential.id.clone(),
+                        fields,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Build an authorization response presenting the credentials selected by the holder for the
+    /// presentation definition of the request, with its presentation submission.
+    ///
+    /// Credentials of the `jwt_vc_json` and `jwt_vc` formats are enveloped in a single JWT
+    /// presentation, signed by the [Wallet::holder_signer] over the `nonce` and `client_id` of the
+    /// request (see [Wallet::present_credentials]). SD-JWT credentials are presented with a key
+    /// binding JWT over the same `nonce` and `client_id`, also signed by the
+    /// [Wallet::holder_signer], replacing any key binding JWT they already have. Other
+    /// credentials are presented as they are, so they must already be bound to the request.
+    /// mdocs cannot be presented, as mdoc presentations cannot be validated, see
+    /// [DeviceResponse](crate::core::credential_format::mso_mdoc::DeviceResponse).
+    ///
+    /// The response is validated against the presentation definition before it is returned.
+    async fn build_response(
+        &self,
+        request: &AuthorizationRequestObject,
+        selected_credentials: &[SelectedCredential],
+    ) -> Result<AuthorizationResponse> {
+        let definition = request
+            .resolve_presentation_definition(self.http_client())
+            .await?;
+        let definition = definition.parsed();
+
+        let (enveloped, bare): (Vec<_>, Vec<_>) =
+            selected_credentials.iter().partition(|credential| {
+                ["jwt_vc_json", "jwt_vc"].contains(&credential.format.as_str())
+            });
+
+        let mut presentations = vec![];
+        let mut matches = vec![];
+
+        if !enveloped.is_empty() {
+            let credentials: Vec<String> = enveloped
+                .iter()
+                .map(|credential| credential.credential.clone())
+                .collect();
+            presentations.push(
+                self.present_credentials(&credentials, &request.nonce().0, &request.client_id().0)
+                    .await?,
+            );
+
+            matches.extend(enveloped.iter().enumerate().map(|(index, credential)| {
+                CredentialMatch {
+                    input_descriptor_id: credential.input_descriptor_id.clone(),
+                    format: credential.format.clone(),
+                    presentation_index: 0,
+                    credential_index: Some(index),
+                }
+            }));
+        }
+
+        for credential in bare {
+            matches.push(CredentialMatch {
+                input_descriptor_id: credential.input_descriptor_id.clone(),
+                format: credential.format.clone(),
+                presentation_index: presentations.len(),
+                credential_index: None,
+            });
+
+            let presentation = if [VcSdJwt::ID, DcSdJwt::ID].contains(&credential.format.as_str()) {
+                let mut presentation = SdJwtPresentation::parse(&credential.credential)?;
+                let signer = self
+                    .holder_signer()
+                    .context("a holder signer is required to present SD-JWTs")?;
+                let claims =
+                    presentation.key_binding_claims(&request.nonce().0, &request.client_id().0);
+                presentation.key_binding_jwt = Some(
+                    signer
+                        .sign_jwt(&claims, KEY_BINDING_JWT_TYP)
+                        .await
+                        .context("unable to sign key binding JWT")?,
+                );
+                presentation.to_string()
+            } else {
+                credential.credential.clone()
+            };
+            presentations.push(presentation);
+        }
+
+        let presentation_submission = PresentationSubmission::from_definition(definition, &matches)
+            .context("unable to build the presentation submission")?;
+
+        let vp_token = match <[String; 1]>::try_from(presentations) {
+            Ok([presentation]) => VpToken::Single(presentation),
+            Err(presentations) => VpToken::Multiple(presentations),
+        };
+
+        let response = AuthorizationResponse::Unencoded(UnencodedAuthorizationResponse::new(
+            vp_token,
+            presentation_submission.try_into()?,
+        ));
+
+        definition
+            .validate_authorization_response(&response)
+            .context("the selected credentials do not satisfy the presentation definition")?;
+
+        Ok(response)
+    }
+
+    /// Envelop JWT credentials in a JWT `VerifiablePresentation` for a request, identified by its
+    /// `nonce` and its `audience` (the `client_id` of the verifier), signed by the
+    /// [Wallet::holder_signer] with the algorithm of its public JWK.
+    ///
+    /// The holder, which is the `iss` of the presentation, is the DID of the `key_id` of the
+    /// public JWK.
+    async fn present_credentials(
+        &self,
+        credentials: &[String],
+        nonce: &str,
+        audience: &str,
+    ) -> Result<String> {
+        let signer = self
+            .holder_signer()
+            .context("a holder signer is required to sign presentations")?;
+        let claims = presentation_claims(credentials, nonce, audience, signer.public_jwk())?;
+        signer
+            .sign_jwt(&claims, "JWT")
+            .await
+            .context("unable to sign presentation")
+    }
+
+    /// Sign an authorization response with the [Wallet::holder_signer], for the
+    /// `direct_post.jwt` response mode.
+    ///
+    /// The `state` of the request, if any, is included in the signed response. The `key_id` of the
+    /// holder's public JWK must be the verification method of the holder DID that issued the
+    /// presentations, see [JwtAuthorizationResponse::decode_verified].
+    async fn sign_response(
+        &self,
+        request: &AuthorizationRequestObject,
+        mut response: UnencodedAuthorizationResponse,
+    ) -> Result<AuthorizationResponse> {
+        if let Some(state) = request.get::<State>() {
+            response.0.insert(state.parsing_error()?);
+        }
+
+        let signer = self
+            .holder_signer()
+            .context("a holder signer is required to sign responses")?;
+        let claims = serde_json::to_value(UntypedObject::from(response))?;
+        let response = signer
+            .sign_jwt(&claims, "JWT")
+            .await
+            .context("unable to sign authorization response")?;
+        Ok(AuthorizationResponse::Jwt(JwtAuthorizationResponse {
+            response,
+        }))
+    }
+
+    /// Encrypt the presentations of a `vp_token` for the verifier, using `ECDH-ES` and `A256GCM`
+    /// with the first P-256 encryption key of its `jwks` (see [ClientMetadata::jwks]).
+    #[cfg(feature = "p256")]
+    fn encrypt_vp_token(&self, vp_token: VpToken, jwks: &JWKs) -> Result<VpToken> {
+        vp_token
+            .encrypt(jwks, "A256GCM")
+            .context("unable to encrypt vp_token")
+    }
+
+    /// Submit an authorization response to the verifier.
+    ///
+    /// The `state` of the request, if any, is included in an unencoded response.
+    ///
+    /// When the `response_mode` is `direct_post.jwt`, an unencoded response is encrypted for the
+    /// verifier using its client metadata.
+    ///
+    /// Returns the redirect that the verifier responded with, if any, which the wallet should
+    /// follow.
     async fn submit_response(
         &self,
         request: AuthorizationRequestObject,
-        response: AuthorizationResponse,
-    ) -> Result<Option<Url>> {
-        let mut http_request_builder = base_request().uri(request.return_uri().as_str());
+        mut response: AuthorizationResponse,
+    ) -> Result<Option<RedirectResponse>> {
+        if let (AuthorizationResponse::Unencoded(unencoded), Some(state)) =
+            (&mut response, request.get::<State>())
+        {
+            unencoded.0.insert(state.parsing_error()?);
+        }
 
         let http_request_body = match request.response_mode() {
             ResponseMode::DirectPost => {
-                http_request_builder = http_request_builder
-                    .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
-                    .method("POST");
-
                 let AuthorizationResponse::Unencoded(unencoded) = response else {
                     bail!("unexpected AuthorizationResponse format")
                 };
@@ -49,12 +316,20 @@ pub trait Wallet: RequestVerifier + Sync {
                 unencoded.into_x_www_form_urlencoded()?.into_bytes()
             }
             ResponseMode::DirectPostJwt => {
-                http_request_builder = http_request_builder
-                    .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
-                    .method("POST");
-
-                let AuthorizationResponse::Jwt(jwt) = response else {
-                    bail!("unexpected AuthorizationResponse format")
+                let jwt = match response {
+                    AuthorizationResponse::Jwt(jwt) => jwt,
+                    #[cfg(feature = "p256")]
+                    AuthorizationResponse::Unencoded(unencoded) => {
+                        let client_metadata = ClientMetadata::resolve(&request, self.http_client())
+                            .await
+                            .context("unable to resolve client metadata")?;
+                        JwtAuthorizationResponse::encrypt(unencoded, &client_metadata)
+                            .context("unable to encrypt authorization response")?
+                    }
+                    #[cfg(not(feature = "p256"))]
+                    AuthorizationResponse::Unencoded(_) => {
+                        bail!("encrypting the authorization response requires the 'p256' feature")
+                    }
                 };
 
                 jwt.into_x_www_form_urlencoded()?.into_bytes()
@@ -62,12 +337,13 @@ pub trait Wallet: RequestVerifier + Sync {
             ResponseMode::Unsupported(rm) => bail!("unsupported response_mode {rm}"),
         };
 
-        let http_request = http_request_builder
-            .body(http_request_body)
-            .context("failed to construct presentation submission request")?;
+        let headers = HeaderMap::from_iter([(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        )]);
         let http_response = self
             .http_client()
-            .execute(http_request)
+            .post(request.return_uri(), http_request_body, headers)
             .await
             .context("failed to make authorization response request")?;
 
@@ -82,7 +358,381 @@ pub trait Wallet: RequestVerifier + Sync {
 
         Ok(serde_json::from_str(&body)
             .map_err(|e| warn!("response did not contain a redirect: {e}"))
-            .ok()
-            .map(|PostRedirection { redirect_uri }| redirect_uri))
+            .ok())
+    }
+}
+
+/// A credential held by the wallet, see [Wallet::match_credentials].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredCredential {
+    /// The identifier of the credential in the wallet.
+    pub id: String,
+    /// The claim format designation of the credential, such as `jwt_vc_json` or `vc+sd-jwt`.
+    pub format: String,
+    /// The decoded claims of the credential, which the constraints are evaluated against.
+    pub claims: Json,
+}
+
+/// The claims of a JWT presentation of `credentials`, with the DID of the `key_id` of the holder
+/// `key` as its `iss`.
+fn presentation_claims(
+    credentials: &[String],
+    nonce: &str,
+    audience: &str,
+    key: &JWK,
+) -> Result<Json> {
+    let holder = key
+        .key_id
+        .as_deref()
+        .and_then(|kid| kid.split_once('#'))
+        .map(|(did, _)| did)
+        .context("the key_id of the key must be a DID verification method of the holder")?;
+
+    Ok(json!({
+        "iss": holder,
+        "aud": audience,
+        "nonce": nonce,
+        "vp": {
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiablePresentation"],
+            "holder": holder,
+            "verifiableCredential": credentials
+        }
+    }))
+}
+
+/// A credential selected by the holder to satisfy an input descriptor, see
+/// [Wallet::build_response].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedCredential {
+    /// The `id` of the input descriptor.
+    pub input_descriptor_id: String,
+    /// The claim format designation of the credential, such as `jwt_vc_json` or `vc+sd-jwt`.
+    pub format: String,
+    /// The encoded credential, such as a JWT.
+    pub credential: String,
+}
+
+/// A held credential that satisfies an input descriptor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorMatch {
+    pub input_descriptor_id: String,
+    pub credential_id: String,
+    /// The JSON pointer and value of each constraint field found in the credential.
+    pub fields: Vec<(String, Json)>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use http::{Request, Response, Uri};
+    use serde_json::json;
+
+    use crate::core::{object::UntypedObject, response::UnencodedAuthorizationResponse};
+
+    use super::{holder_signer::JwkSigner, *};
+
+    #[derive(Default)]
+    struct RecordingHttpClient(Mutex<Option<Uri>>);
+
+    #[async_trait]
+    impl AsyncHttpClient for RecordingHttpClient {
+        async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
+            *self.0.lock().unwrap() = Some(request.uri().clone());
+            let body = json!({
+                "redirect_uri": "https://verifier.example.com/cb#response_code=091535f699ea575c7937fa5f0f454aee"
+            });
+            Ok(Response::builder()
+                .status(200)
+                .body(body.to_string().into_bytes())?)
+        }
+    }
+
+    struct TestWallet {
+        http_client: RecordingHttpClient,
+        metadata: WalletMetadata,
+        holder_signer: Option<JwkSigner>,
+    }
+
+    impl RequestVerifier for TestWallet {}
+
+    impl Wallet for TestWallet {
+        type HttpClient = RecordingHttpClient;
+
+        fn metadata(&self) -> &WalletMetadata {
+            &self.metadata
+        }
+
+        fn http_client(&self) -> &Self::HttpClient {
+            &self.http_client
+        }
+
+        fn holder_signer(&self) -> Option<&(dyn HolderSigner + Send + Sync)> {
+            self.holder_signer
+                .as_ref()
+                .map(|signer| signer as &(dyn HolderSigner + Send + Sync))
+        }
+    }
+
+    #[test]
+    fn match_credentials() {
+        let wallet = TestWallet {
+            http_client: RecordingHttpClient::default(),
+            metadata: WalletMetadata::openid4vp_scheme_static(),
+            holder_signer: None,
+        };
+
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "over_18",
+                    "format": { "jwt_vp_json": {} },
+                    "constraints": {
+                        "fields": [
+                            {
+                                "path": ["$.vc.credentialSubject.age_over_18"],
+                                "filter": { "type": "boolean", "const": true }
+                            }
+                        ]
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let credential = |id: &str, age_over_18: bool| StoredCredential {
+            id: id.into(),
+            format: "jwt_vc_json".into(),
+            claims: json!({
+                "vc": { "credentialSubject": { "age_over_18": age_over_18 } }
+            }),
+        };
+
+        assert_eq!(
+            wallet.match_credentials(
+                &definition,
+                &[credential("a", true), credential("b", false)]
+            ),
+            [DescriptorMatch {
+                input_descriptor_id: "over_18".into(),
+                credential_id: "a".into(),
+                fields: vec![("/vc/credentialSubject/age_over_18".into(), json!(true))],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn present_credentials() {
+        let holder = "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2";
+        let mut key: JWK =
+            serde_json::from_str(include_str!("../tests/examples/subject.jwk")).unwrap();
+        key.key_id = Some(format!("{holder}#{}", &holder["did:key:".len()..]));
+        let credential = include_str!("../tests/examples/vc.jwt").to_owned();
+        let mut wallet = TestWallet {
+            http_client: RecordingHttpClient::default(),
+            metadata: WalletMetadata::openid4vp_scheme_static(),
+            holder_signer: None,
+        };
+
+        assert!(wallet
+            .present_credentials(&[], "n-0S6_WzA2Mj8sKq3bT5yA", "did:example:verifier")
+            .await
+            .is_err());
+
+        wallet.holder_signer = Some(JwkSigner::new(key.clone()));
+        let vp = wallet
+            .present_credentials(
+                std::slice::from_ref(&credential),
+                "n-0S6_WzA2Mj8sKq3bT5yA",
+                "did:example:verifier",
+            )
+            .await
+            .unwrap();
+
+        let claims: Json = ssi::jwt::decode_verify(&vp, &key.to_public()).unwrap();
+        assert_eq!(claims["iss"], holder);
+        assert_eq!(claims["nonce"], "n-0S6_WzA2Mj8sKq3bT5yA");
+        assert_eq!(claims["aud"], "did:example:verifier");
+        assert_eq!(claims["vp"]["holder"], holder);
+        assert_eq!(claims["vp"]["verifiableCredential"], json!([credential]));
+        assert_eq!(
+            crate::core::response::id_token::header_kid(&vp).unwrap(),
+            key.key_id.clone().unwrap()
+        );
+
+        key.key_id = None;
+        wallet.holder_signer = Some(JwkSigner::new(key));
+        assert!(wallet
+            .present_credentials(&[], "nonce", "aud")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn present_multiple_credentials() {
+        let holder = "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2";
+        let mut key: JWK =
+            serde_json::from_str(include_str!("../tests/examples/subject.jwk")).unwrap();
+        key.key_id = Some(format!("{holder}#{}", &holder["did:key:".len()..]));
+        let wallet = TestWallet {
+            http_client: RecordingHttpClient::default(),
+            metadata: WalletMetadata::openid4vp_scheme_static(),
+            holder_signer: Some(JwkSigner::new(key)),
+        };
+        let credential = |claim: &str| {
+            ssi::jwt::encode_unsigned(&json!({
+                "iss": "https://issuer.example.com",
+                "vc": {
+                    "type": ["VerifiableCredential"],
+                    "credentialSubject": { "id": holder, claim: true }
+                }
+            }))
+            .unwrap()
+        };
+
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "input_descriptors": [
+                {
+                    "id": "identity",
+                    "constraints": {
+                        "fields": [{ "path": ["$.vc.credentialSubject.identity"] }]
+                    }
+                },
+                {
+                    "id": "diploma",
+                    "constraints": {
+                        "fields": [{ "path": ["$.vc.credentialSubject.diploma"] }]
+                    }
+                }
+            ]
+        }))
+        .unwrap();
+
+        let vp = wallet
+            .present_credentials(
+                &[credential("identity"), credential("diploma")],
+                "n-0S6_WzA2Mj8sKq3bT5yA",
+                "did:example:verifier",
+            )
+            .await
+            .unwrap();
+
+        let matches = ["identity", "diploma"].map(|id| CredentialMatch {
+            input_descriptor_id: id.into(),
+            format: "jwt_vc_json".into(),
+            presentation_index: 0,
+            credential_index: Some(if id == "identity" { 0 } else { 1 }),
+        });
+        let submission = PresentationSubmission::from_definition(&definition, &matches).unwrap();
+        assert_eq!(
+            submission.descriptor_map[1]
+                .path_nested
+                .as_ref()
+                .unwrap()
+                .path,
+            "$.vp.verifiableCredential[1]"
+        );
+
+        let response = AuthorizationResponse::Unencoded(UnencodedAuthorizationResponse::new(
+            VpToken::Single(vp),
+            submission.try_into().unwrap(),
+        ));
+        definition
+            .validate_authorization_response(&response)
+            .unwrap();
+
+        // Swapping the credentials resolves each input descriptor to the wrong credential.
+        let matches = matches.map(|mut credential_match| {
+            credential_match.credential_index = credential_match.credential_index.map(|i| 1 - i);
+            credential_match
+        });
+        let submission = PresentationSubmission::from_definition(&definition, &matches).unwrap();
+        let AuthorizationResponse::Unencoded(response) = response else {
+            unreachable!()
+        };
+        let response = AuthorizationResponse::Unencoded(UnencodedAuthorizationResponse::new(
+            response.vp_token().clone(),
+            submission.try_into().unwrap(),
+        ));
+        assert!(definition
+            .validate_authorization_response(&response)
+            .is_err());
+    }
+
+    #[test]
+    fn supported_algorithms() {
+        let wallet = TestWallet {
+            http_client: RecordingHttpClient::default(),
+            metadata: WalletMetadata::openid4vp_scheme_static(),
+            holder_signer: None,
+        };
+
+        assert_eq!(
+            wallet.supported_algorithms(&ClaimFormatDesignation::JwtVcJson),
+            ["ES256"]
+        );
+        assert!(wallet
+            .supported_algorithms(&ClaimFormatDesignation::MsoMdoc)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn submit_response_to_response_uri() {
+        let wallet = TestWallet {
+            http_client: RecordingHttpClient::default(),
+            metadata: WalletMetadata::openid4vp_scheme_static(),
+            holder_signer: None,
+        };
+
+        let request: AuthorizationRequestObject = serde_json::from_value::<UntypedObject>(json!({
+            "client_id": "did:example:verifier",
+            "client_id_scheme": "did",
+            "response_mode": "direct_post",
+            "response_type": "vp_token",
+            "response_uri": "https://verifier.example.com/response",
+            "nonce": "random123",
+            "presentation_definition": {
+                "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "input_descriptors": [{ "id": "vc" }]
+            }
+        }))
+        .unwrap()
+        .try_into()
+        .unwrap();
+
+        let response: UnencodedAuthorizationResponse =
+            serde_json::from_value::<UntypedObject>(json!({
+                "vp_token": "presentation",
+                "presentation_submission": {
+                    "id": "39881a17-e454-4d98-87ba-e3073d1014d6",
+                    "definition_id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                    "descriptor_map": []
+                }
+            }))
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let redirect = wallet
+            .submit_response(request, AuthorizationResponse::Unencoded(response))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            redirect.redirect_uri.as_str(),
+            "https://verifier.example.com/cb#response_code=091535f699ea575c7937fa5f0f454aee"
+        );
+        assert_eq!(
+            redirect.response_code.as_deref(),
+            Some("091535f699ea575c7937fa5f0f454aee")
+        );
+
+        assert_eq!(
+            wallet.http_client.0.lock().unwrap().as_ref().unwrap(),
+            "https://verifier.example.com/response"
+        );
     }
 }
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::prelude::*;
+use serde_json::{json, Value as Json};
+use ssi::jwk::{Algorithm, JWK};
+
+use std::fmt::Debug;
+
+/// A source of holder signatures, such as an in-memory key or a key held in an HSM or KMS.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait HolderSigner: Debug {
+    /// The public JWK of the holder. Its `key_id` should be a DID verification method of the
+    /// holder.
+    fn public_jwk(&self) -> &JWK;
+    /// Sign a payload with an algorithm, returning the signature.
+    async fn sign(&self, alg: Algorithm, payload: &[u8]) -> Result<Vec<u8>>;
+
+    /// Sign a JWT with the algorithm of the [HolderSigner::public_jwk], whose `key_id` is set as
+    /// the `kid` header.
+    async fn sign_jwt(&self, claims: &Json, typ: &str) -> Result<String> {
+        let jwk = self.public_jwk();
+        let algorithm = jwk
+            .get_algorithm()
+            .context("unable to determine the signing algorithm of the holder key")?;
+
+        let mut header = json!({ "alg": algorithm, "typ": typ });
+        if let Some(kid) = &jwk.key_id {
+            header["kid"] = json!(kid);
+        }
+        let signing_input = format!(
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?)
+        );
+
+        let signature = self.sign(algorithm, signing_input.as_bytes()).await?;
+        Ok(format!(
+            "{signing_input}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(signature)
+        ))
+    }
+}
+
+/// A [HolderSigner] with an in-memory private JWK.
+#[derive(Debug, Clone)]
+pub struct JwkSigner {
+    key: JWK,
+    public_jwk: JWK,
+}
+
+impl JwkSigner {
+    pub fn new(key: JWK) -> Self {
+        let public_jwk = key.to_public();
+        Self { key, public_jwk }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HolderSigner for JwkSigner {
+    fn public_jwk(&self) -> &JWK {
+        &self.public_jwk
+    }
+
+    async fn sign(&self, alg: Algorithm, payload: &[u8]) -> Result<Vec<u8>> {
+        ssi::jws::sign_bytes(alg, payload, &self.key).context("unable to sign payload")
+    }
+}
@@ -1,67 +1,1585 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::prelude::*;
 use oid4vp::{
     core::{
-        authorization_request::parameters::{ClientMetadata, Nonce, ResponseMode, ResponseType},
+        authorization_request::{
+            parameters::{
+                self, ClientMetadata, ClientMetadataUri, Nonce, PresentationDefinitionUri,
+                ResponseMode, ResponseType, State,
+            },
+            AuthorizationRequestObject,
+        },
+        credential_format::sd_jwt::SdJwtPresentation,
+        dcql_query::{DcqlQuery, HeldCredential},
         object::UntypedObject,
-        response::{parameters::VpToken, AuthorizationResponse, UnencodedAuthorizationResponse},
+        response::{
+            parameters::{IdToken, VpToken},
+            AuthorizationResponse, JwtAuthorizationResponse, UnencodedAuthorizationResponse,
+        },
     },
     presentation_exchange::{PresentationDefinition, PresentationSubmission},
-    verifier::session::{Outcome, Status},
-    wallet::Wallet,
+    verifier::{
+        request_builder::RequestBuilder,
+        session::{MemoryStore, Outcome, OutcomeError, Session, SessionStore, Status},
+        TimedOut, Verifier,
+    },
+    wallet::{SelectedCredential, Wallet},
+    Error,
 };
-use serde_json::json;
+use serde_json::{json, Value as Json};
+use ssi::jwk::{Algorithm, JWK};
+use url::Url;
+use uuid::Uuid;
 
 mod jwt_vc;
 
+/// A nonce that meets the minimum length of [Nonce::MIN_LENGTH].
+const NONCE: &str = "n-0S6_WzA2Mj8sKq3bT5yA";
+
 #[tokio::test]
 async fn w3c_vc_did_client_direct_post() {
     let (wallet, verifier) = jwt_vc::wallet_verifier().await;
 
-    let presentation_definition: PresentationDefinition = serde_json::from_value(json!({
-        "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
-        "input_descriptors": [
-            {
-                "id": "vc",
-                "format": {
-                    "jwt_vc_json": {
-                        "proof_type": [
-                            "JsonWebSignature2020"
-                        ]
-                    }
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "format": {
+            "jwt_vp_json": {
+                "alg": ["ES256"]
+            },
+            "jwt_vc_json": {
+                "proof_type": [
+                    "JsonWebSignature2020"
+                ]
+            }
+        },
+        "constraints": {
+            "fields": [
+                {
+                    "path": ["$.vc.credentialSubject.id"],
+                    "name": "Subject identifier",
+                    "purpose": "To identify the holder"
                 }
+            ]
+        }
+    }));
+
+    let (id, request) = validated_request(
+        &wallet,
+        &verifier,
+        presentation_definition.clone(),
+        |builder| builder.with_request_parameter(State("state123".to_owned())),
+    )
+    .await;
+
+    let resolved_definition = request
+        .resolve_presentation_definition(wallet.http_client())
+        .await
+        .unwrap();
+    assert_eq!(&presentation_definition, resolved_definition.parsed());
+
+    let requested_fields = resolved_definition.parsed().requested_fields();
+    assert_eq!(requested_fields.len(), 1);
+    assert_eq!(
+        requested_fields[0].name.as_deref(),
+        Some("Subject identifier")
+    );
+    assert_eq!(
+        requested_fields[0].purpose.as_deref(),
+        Some("To identify the holder")
+    );
+    assert!(requested_fields[0].required);
+
+    assert_eq!(&ResponseType::VpToken, request.response_type());
+
+    assert_eq!(&ResponseMode::DirectPost, request.response_mode());
+
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert_eq!(Status::SentRequest, status);
+
+    let redirect = wallet.submit_response(request, response).await.unwrap();
+
+    assert_eq!(None, redirect);
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+
+    let Status::Complete(outcome) = status else {
+        unreachable!()
+    };
+    assert_eq!(
+        outcome.presentation_submission().unwrap().definition_id,
+        "0b4dd017-efa6-4a05-a269-9790fa3c22c2"
+    );
+    let presentation = &outcome.presentations().unwrap()[0];
+    let credential: Json = ssi::jwt::decode_unverified(
+        presentation["vp"]["verifiableCredential"][0]
+            .as_str()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        credential["vc"]["credentialSubject"]["id"],
+        "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2"
+    );
+}
+
+#[tokio::test]
+async fn build_response_direct_post() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "format": {
+            "jwt_vp_json": {
+                "alg": ["ES256"]
+            },
+            "jwt_vc_json": {
+                "alg": ["ES256"]
+            }
+        },
+        "constraints": {
+            "fields": [
+                {
+                    "path": ["$.vc.credentialSubject.id"]
+                }
+            ]
+        }
+    }));
+
+    let (id, request) = validated_request(&wallet, &verifier, presentation_definition, |builder| {
+        builder
+    })
+    .await;
+
+    let selected_credentials = [SelectedCredential {
+        input_descriptor_id: "vc".into(),
+        format: "jwt_vc_json".into(),
+        credential: include_str!("examples/vc.jwt").into(),
+    }];
+    let response = wallet
+        .build_response(&request, &selected_credentials)
+        .await
+        .unwrap();
+
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+}
+
+#[tokio::test]
+async fn build_response_sd_jwt() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "pid",
+        "constraints": {
+            "fields": [{ "path": ["$.age_over_18"], "filter": { "const": true } }]
+        }
+    }));
+
+    let (id, request) = validated_request(&wallet, &verifier, presentation_definition, |builder| {
+        builder
+    })
+    .await;
+
+    // The held SD-JWT has no key binding JWT, which the wallet adds for the request.
+    let selected_credentials = [SelectedCredential {
+        input_descriptor_id: "pid".into(),
+        format: "vc+sd-jwt".into(),
+        credential: jwt_vc::create_sd_jwt(None),
+    }];
+    let response = wallet
+        .build_response(&request, &selected_credentials)
+        .await
+        .unwrap();
+
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(
+        matches!(status, Status::Complete(Outcome::Success { .. })),
+        "{status:?}"
+    );
+}
+
+#[tokio::test]
+async fn redirect_after_submission() {
+    let (wallet, verifier) =
+        jwt_vc::wallet_verifier_with_redirect("https://verifier.example.com/result").await;
+
+    let mut redirects = vec![];
+    for nonce in [NONCE, "n-0S6_WzA2Mj8sKq3bT5yB"] {
+        let (_, request) = validated_request(
+            &wallet,
+            &verifier,
+            definition(json!({ "id": "vc" })),
+            |builder| builder.with_request_parameter(Nonce(nonce.to_owned())),
+        )
+        .await;
+        // The second presentation is not bound to the nonce of its request.
+        let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+        redirects.push(wallet.submit_response(request, response).await.unwrap());
+    }
+
+    let redirect = redirects[0].as_ref().unwrap();
+    let response_code = redirect.response_code.as_deref().unwrap();
+    assert_eq!(
+        redirect.redirect_uri.as_str(),
+        format!("https://verifier.example.com/result#response_code={response_code}")
+    );
+
+    let outcome = verifier.get_outcome_by_code(response_code).await.unwrap();
+    assert!(matches!(outcome, Outcome::Success { .. }));
+    verifier
+        .get_outcome_by_code("wrong-response-code")
+        .await
+        .unwrap_err();
+
+    // No redirect is returned for a failed verification.
+    assert_eq!(redirects[1], None);
+}
+
+#[tokio::test]
+async fn w3c_vc_did_client_direct_post_jwt() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "format": {
+            "jwt_vp_json": {
+                "alg": ["ES256"]
+            },
+            "jwt_vc_json": {
+                "proof_type": [
+                    "JsonWebSignature2020"
+                ]
             }
-        ]
+        }
+    }));
+
+    let encryption_key: Json = serde_json::from_str(
+        &p256::SecretKey::from_jwk_str(include_str!("examples/encryption.jwk"))
+            .unwrap()
+            .public_key()
+            .to_jwk_string(),
+    )
+    .unwrap();
+
+    let client_metadata = serde_json::from_value(json!({
+        "jwks": {
+            "keys": [encryption_key]
+        },
+        "authorization_encrypted_response_alg": "ECDH-ES",
+        "encrypted_response_enc_values_supported": ["A256GCM"]
     }))
     .unwrap();
 
-    let client_metadata = UntypedObject::default();
+    let (id, request) = validated_request(
+        &wallet,
+        &verifier,
+        presentation_definition.clone(),
+        |builder| {
+            builder
+                .with_request_parameter(ResponseMode::DirectPostJwt)
+                .with_request_parameter(ClientMetadata(client_metadata))
+        },
+    )
+    .await;
 
-    let (id, request) = verifier
-        .build_authorization_request()
+    assert_eq!(&ResponseMode::DirectPostJwt, request.response_mode());
+
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+
+    let redirect = wallet.submit_response(request, response).await.unwrap();
+
+    assert_eq!(None, redirect);
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+}
+
+#[tokio::test]
+async fn encrypted_vp_token() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "format": {
+            "jwt_vp_json": {
+                "alg": ["ES256"]
+            },
+            "jwt_vc_json": {}
+        }
+    }));
+
+    let public_jwk = |jwk: &str| -> Json {
+        serde_json::from_str(
+            &p256::SecretKey::from_jwk_str(jwk)
+                .unwrap()
+                .public_key()
+                .to_jwk_string(),
+        )
+        .unwrap()
+    };
+
+    for verifier_key in [true, false] {
+        let encryption_key = if verifier_key {
+            public_jwk(include_str!("examples/encryption.jwk"))
+        } else {
+            public_jwk(include_str!("examples/verifier.jwk"))
+        };
+
+        let client_metadata = serde_json::from_value(json!({
+            "jwks": {
+                "keys": [encryption_key]
+            }
+        }))
+        .unwrap();
+
+        let (id, request) = validated_request(
+            &wallet,
+            &verifier,
+            presentation_definition.clone(),
+            |builder| builder.with_request_parameter(ClientMetadata(client_metadata)),
+        )
+        .await;
+
+        let jwks = ClientMetadata::resolve(&request, wallet.http_client())
+            .await
+            .unwrap()
+            .jwks()
+            .unwrap()
+            .unwrap();
+
+        let mut response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+        let AuthorizationResponse::Unencoded(unencoded) = &mut response else {
+            unreachable!()
+        };
+        unencoded.1 = wallet.encrypt_vp_token(unencoded.1.clone(), &jwks).unwrap();
+        assert!(unencoded.vp_token().is_encrypted());
+
+        let result = wallet.submit_response(request, response).await;
+
+        let status = verifier.poll_status(id).await.unwrap();
+        if verifier_key {
+            result.unwrap();
+            assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+        } else {
+            // The verifier is unable to decrypt the vp_token.
+            result.unwrap_err();
+            assert!(!matches!(status, Status::Complete(_)));
+        }
+    }
+}
+
+#[tokio::test]
+async fn signed_authorization_response() {
+    let (mut wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "format": {
+            "jwt_vp_json": {
+                "alg": ["ES256"]
+            },
+            "jwt_vc_json": {}
+        }
+    }));
+
+    // Validation of a `direct_post.jwt` request requires encryption parameters, although the
+    // response is only signed.
+    let encryption_key: Json = serde_json::from_str(
+        &p256::SecretKey::from_jwk_str(include_str!("examples/encryption.jwk"))
+            .unwrap()
+            .public_key()
+            .to_jwk_string(),
+    )
+    .unwrap();
+
+    let client_metadata: UntypedObject = serde_json::from_value(json!({
+        "jwks": {
+            "keys": [encryption_key]
+        },
+        "authorization_encrypted_response_alg": "ECDH-ES",
+        "encrypted_response_enc_values_supported": ["A256GCM"]
+    }))
+    .unwrap();
+
+    let mut ids = vec![];
+    for holder_key in [true, false] {
+        let nonce = format!("{NONCE}-{holder_key}");
+        let (id, request) = validated_request(
+            &wallet,
+            &verifier,
+            presentation_definition.clone(),
+            |builder| {
+                builder
+                    .with_request_parameter(ResponseMode::DirectPostJwt)
+                    .with_request_parameter(Nonce(nonce.clone()))
+                    .with_request_parameter(State("state".to_owned()))
+                    .with_request_parameter(ClientMetadata(client_metadata.clone()))
+            },
+        )
+        .await;
+
+        let mut key = jwt_vc::subject_key();
+        if !holder_key {
+            // Sign with another key, keeping the verification method of the holder.
+            let other: JWK = serde_json::from_str(include_str!("examples/verifier.jwk")).unwrap();
+            key = JWK {
+                key_id: key.key_id,
+                ..other
+            };
+        }
+
+        let AuthorizationResponse::Unencoded(unencoded) =
+            vp_response(jwt_vc::create_vp(&nonce, &request.client_id().0))
+        else {
+            unreachable!()
+        };
+        wallet.set_holder_key(key);
+        let response = wallet.sign_response(&request, unencoded).await.unwrap();
+        let AuthorizationResponse::Jwt(jwt) = &response else {
+            panic!("expected a JWT response")
+        };
+        assert!(jwt.is_signed());
+        let State(state) = jwt.decode_unverified().unwrap().0.get().unwrap().unwrap();
+        assert_eq!(state, "state");
+
+        wallet.submit_response(request, response).await.unwrap();
+        ids.push(id);
+    }
+
+    let status = verifier.poll_status(ids[0]).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+
+    let status = verifier.poll_status(ids[1]).await.unwrap();
+    assert!(matches!(
+        status,
+        Status::Complete(Outcome::Failure {
+            error: OutcomeError::InvalidResponseSignature,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn w3c_vc_did_client_direct_post_state_mismatch() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let (id, mut request) = validated_request(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder.with_request_parameter(State("state123".to_owned())),
+    )
+    .await;
+    request.insert(State("tampered".to_owned()));
+
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(
+        status,
+        Status::Complete(Outcome::Failure {
+            error: OutcomeError::StateMismatch,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn w3c_vc_did_client_direct_post_nonce_mismatch() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let (id, request) = validated_request(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder,
+    )
+    .await;
+
+    let response = vp_response(jwt_vc::create_vp("replayed", &request.client_id().0));
+
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(
+        status,
+        Status::Complete(Outcome::Failure {
+            error: OutcomeError::NonceMismatch,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn unverifiable_presentations() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let client_id = verifier.client_id().0.clone();
+    let claims: Json = ssi::jwt::decode_unverified(&jwt_vc::create_vp(NONCE, &client_id)).unwrap();
+    let mut issuer_key: JWK = serde_json::from_str(include_str!("examples/issuer.jwk")).unwrap();
+    issuer_key.key_id = jwt_vc::subject_key().key_id;
+
+    // Each session has its own nonce, as a response consumes the nonce of its session.
+    for (i, (presentation, expected)) in [
+        (
+            ssi::jwt::encode_unsigned(&claims).unwrap(),
+            OutcomeError::InvalidPresentationSignature,
+        ),
+        (
+            ssi::jwt::encode_sign(Algorithm::ES256, &claims, &issuer_key).unwrap(),
+            OutcomeError::InvalidPresentationSignature,
+        ),
+        (
+            BASE64_URL_SAFE_NO_PAD.encode(b"an mdoc DeviceResponse"),
+            OutcomeError::NonceMismatch,
+        ),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let (id, request) = validated_request(
+            &wallet,
+            &verifier,
+            definition(json!({ "id": "vc" })),
+            |builder| builder.with_request_parameter(Nonce(format!("{NONCE}-{i}"))),
+        )
+        .await;
+
+        wallet
+            .submit_response(request, vp_response(presentation))
+            .await
+            .unwrap();
+
+        let status = verifier.poll_status(id).await.unwrap();
+        assert!(
+            matches!(&status, Status::Complete(Outcome::Failure { error, .. }) if *error == expected),
+            "{status:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn w3c_vc_did_client_direct_post_audience_mismatch() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let (id, request) = validated_request(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder,
+    )
+    .await;
+
+    let response = vp_response(jwt_vc::create_vp(NONCE, "did:example:other-verifier"));
+
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(
+        status,
+        Status::Complete(Outcome::Failure {
+            error: OutcomeError::AudienceMismatch,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn w3c_vc_did_client_direct_post_constraints_not_satisfied() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "constraints": {
+            "fields": [
+                {
+                    "path": ["$.vc.credentialSubject.id"],
+                    "filter": {
+                        "type": "string",
+                        "const": "did:example:someone-else"
+                    }
+                }
+            ]
+        }
+    }));
+
+    let (id, request) = validated_request(&wallet, &verifier, presentation_definition, |builder| {
+        builder
+    })
+    .await;
+
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(
+        status,
+        Status::Complete(Outcome::Failure {
+            error: OutcomeError::ConstraintsNotSatisfied,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn vp_token_id_token() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "format": {
+            "jwt_vp_json": {
+                "alg": ["ES256"]
+            },
+            "jwt_vc_json": {}
+        }
+    }));
+
+    let mut ids = vec![];
+    for id_token in [true, false] {
+        let nonce = format!("{NONCE}-{id_token}");
+        let (id, request) = validated_request(
+            &wallet,
+            &verifier,
+            presentation_definition.clone(),
+            |builder| {
+                builder
+                    .with_request_parameter(ResponseType::VpTokenIdToken)
+                    .with_request_parameter(Nonce(nonce.clone()))
+            },
+        )
+        .await;
+        assert_eq!(request.is_id_token_requested(), Some(true));
+
+        let client_id = request.client_id().0.clone();
+        let mut response = vp_response(jwt_vc::create_vp(&nonce, &client_id));
+        if id_token {
+            let AuthorizationResponse::Unencoded(unencoded) = &mut response else {
+                unreachable!()
+            };
+            unencoded
+                .0
+                .insert(IdToken(jwt_vc::create_id_token(&nonce, &client_id)));
+        }
+
+        wallet.submit_response(request, response).await.unwrap();
+        ids.push(id);
+    }
+
+    let status = verifier.poll_status(ids[0]).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+
+    let status = verifier.poll_status(ids[1]).await.unwrap();
+    assert!(matches!(
+        status,
+        Status::Complete(Outcome::Failure {
+            error: OutcomeError::InvalidResponse,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn self_issued_id_token() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "vc",
+        "format": {
+            "jwt_vp_json": {
+                "alg": ["ES256"]
+            },
+            "jwt_vc_json": {}
+        }
+    }));
+
+    let mut ids = vec![];
+    for tampered in [false, true] {
+        let nonce = format!("{NONCE}-{tampered}");
+        let (id, request) = validated_request(
+            &wallet,
+            &verifier,
+            presentation_definition.clone(),
+            |builder| {
+                builder
+                    .with_request_parameter(ResponseType::VpTokenIdToken)
+                    .with_request_parameter(Nonce(nonce.clone()))
+            },
+        )
+        .await;
+
+        let client_id = request.client_id().0.clone();
+        let mut id_token = jwt_vc::create_id_token(&nonce, &client_id);
+        if tampered {
+            // Replace the claims, keeping the original signature.
+            let claims = BASE64_URL_SAFE_NO_PAD.encode(
+                json!({
+                    "iss": "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2",
+                    "sub": "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2",
+                    "aud": client_id,
+                    "nonce": nonce,
+                    "name": "Mallory"
+                })
+                .to_string(),
+            );
+            let parts: Vec<&str> = id_token.split('.').collect();
+            id_token = format!("{}.{claims}.{}", parts[0], parts[2]);
+        }
+
+        let mut response = vp_response(jwt_vc::create_vp(&nonce, &client_id));
+        let AuthorizationResponse::Unencoded(unencoded) = &mut response else {
+            unreachable!()
+        };
+        unencoded.0.insert(IdToken(id_token));
+
+        wallet.submit_response(request, response).await.unwrap();
+        ids.push(id);
+    }
+
+    let Status::Complete(outcome) = verifier.poll_status(ids[0]).await.unwrap() else {
+        panic!("expected the session to be complete")
+    };
+    assert_eq!(
+        outcome.subject(),
+        Some("did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2")
+    );
+
+    let status = verifier.poll_status(ids[1]).await.unwrap();
+    assert!(matches!(
+        status,
+        Status::Complete(Outcome::Failure {
+            error: OutcomeError::InvalidIdToken,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn invalid_request_error() {
+    let (wallet, _verifier) = jwt_vc::wallet_verifier().await;
+
+    let error = wallet
+        .validate_request(
+            "https://wallet.example.com/?client_id=xyz&request=abc"
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::InvalidRequest(_)));
+}
+
+#[tokio::test]
+async fn w3c_vc_did_client_direct_post_by_reference() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier_by_reference().await;
+
+    let (id, request) = start_session(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder,
+    )
+    .await;
+
+    assert!(request.query().unwrap().contains("request_uri="));
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert_eq!(Status::SentRequestByReference, status);
+
+    let request = wallet.validate_request(request).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert_eq!(Status::SentRequest, status);
+
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+}
+
+#[tokio::test]
+async fn presentation_definition_uri() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({ "id": "vc" }));
+
+    let (_, request) = validated_request(&wallet, &verifier, presentation_definition, |builder| {
+        builder.with_presentation_definition_uri(
+            "http://example.com/presentation-definition"
+                .parse()
+                .unwrap(),
+        )
+    })
+    .await;
+
+    let PresentationDefinitionUri(uri) = request.get().unwrap().unwrap();
+    assert_eq!(uri.as_str(), "http://example.com/presentation-definition");
+    assert!(request
+        .get::<parameters::PresentationDefinition>()
+        .is_none());
+}
+
+#[tokio::test]
+async fn mutually_exclusive_request_parameters() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({ "id": "vc" }));
+    let presentation_definition_uri = PresentationDefinitionUri(
+        "http://example.com/presentation-definition"
+            .parse()
+            .unwrap(),
+    );
+
+    let request = || {
+        verifier
+            .build_authorization_request()
+            .with_request_parameter(ResponseMode::DirectPost)
+            .with_request_parameter(ResponseType::VpToken)
+            .with_request_parameter(Nonce(NONCE.to_owned()))
+    };
+
+    let error = request()
         .with_presentation_definition(presentation_definition.clone())
+        .with_request_parameter(presentation_definition_uri.clone())
+        .with_request_parameter(ClientMetadata(UntypedObject::default()))
+        .build(wallet.metadata().clone())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "request parameters 'presentation_definition' and 'presentation_definition_uri' are mutually exclusive"
+    );
+
+    let error = request()
+        .with_dcql_query(
+            DcqlQuery::from_json(
+                &json!({ "credentials": [{ "id": "mdl", "format": "mso_mdoc" }] }).to_string(),
+            )
+            .unwrap(),
+        )
+        .with_request_parameter(presentation_definition_uri)
+        .with_request_parameter(ClientMetadata(UntypedObject::default()))
+        .build(wallet.metadata().clone())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "request parameters 'dcql_query' and 'presentation_definition_uri' are mutually exclusive"
+    );
+
+    let error = request()
+        .with_presentation_definition(presentation_definition)
+        .with_request_parameter(ClientMetadata(UntypedObject::default()))
+        .with_request_parameter(ClientMetadataUri(
+            "http://example.com/client-metadata".parse().unwrap(),
+        ))
+        .build(wallet.metadata().clone())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "request parameters 'client_metadata' and 'client_metadata_uri' are mutually exclusive"
+    );
+}
+
+#[tokio::test]
+async fn wait_for_status() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let (id, request) = start_session(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder,
+    )
+    .await;
+
+    let error = verifier
+        .wait_for_status(id, Status::ReceivedResponse, Duration::from_millis(100))
+        .await
+        .unwrap_err();
+    assert!(error.is::<TimedOut>());
+
+    let request = wallet.validate_request(request).await.unwrap();
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+
+    let (status, submission) = tokio::join!(
+        verifier.wait_for_status(id, Status::ReceivedResponse, Duration::from_secs(5)),
+        wallet.submit_response(request, response),
+    );
+    submission.unwrap();
+    assert!(matches!(
+        status.unwrap(),
+        Status::Complete(Outcome::Success { .. })
+    ));
+}
+
+#[tokio::test]
+async fn expired_session() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let (id, request) = validated_request(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder.with_ttl(Duration::from_millis(100)),
+    )
+    .await;
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+
+    assert_eq!(verifier.poll_status(id).await.unwrap(), Status::SentRequest);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    assert_eq!(verifier.poll_status(id).await.unwrap(), Status::Expired);
+    wallet.submit_response(request, response).await.unwrap_err();
+    assert_eq!(verifier.poll_status(id).await.unwrap(), Status::Expired);
+}
+
+#[tokio::test]
+async fn nonce_issuance_and_replay() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({ "id": "vc" }));
+
+    let builder = || {
+        verifier
+            .build_authorization_request()
+            .with_presentation_definition(presentation_definition.clone())
+            .with_request_parameter(ResponseMode::DirectPost)
+            .with_request_parameter(ResponseType::VpToken)
+            .with_request_parameter(ClientMetadata(UntypedObject::default()))
+    };
+
+    let error = builder()
+        .with_request_parameter(Nonce("random123".to_owned()))
+        .build(wallet.metadata().clone())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("at least 22 characters"));
+
+    let mut requests = vec![];
+    for _ in 0..2 {
+        let (id, request) = builder().build(wallet.metadata().clone()).await.unwrap();
+        requests.push((id, wallet.validate_request(request).await.unwrap()));
+    }
+    let Nonce(nonce) = requests[0].1.nonce().clone();
+    assert!(nonce.len() >= Nonce::MIN_LENGTH);
+    assert_ne!(nonce, requests[1].1.nonce().0);
+
+    let (id, request) = requests.remove(0);
+    let response = vp_response(jwt_vc::create_vp(&nonce, &request.client_id().0));
+    wallet
+        .submit_response(request.clone(), response.clone())
+        .await
+        .unwrap();
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+
+    // The nonce of the session has been consumed.
+    wallet.submit_response(request, response).await.unwrap_err();
+}
+
+#[tokio::test]
+async fn replayed_nonce() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    // Two sessions that were issued the same nonce.
+    let mut sessions = vec![];
+    for _ in 0..2 {
+        sessions.push(
+            validated_request(
+                &wallet,
+                &verifier,
+                definition(json!({ "id": "vc" })),
+                |builder| builder,
+            )
+            .await,
+        );
+    }
+
+    let response = vp_response(jwt_vc::create_vp(NONCE, &sessions[0].1.client_id().0));
+    wallet
+        .submit_response(sessions[0].1.clone(), response.clone())
+        .await
+        .unwrap();
+    let status = verifier.poll_status(sessions[0].0).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+
+    // The replayed response is rejected without completing the second session.
+    wallet
+        .submit_response(sessions[1].1.clone(), response)
+        .await
+        .unwrap_err();
+    let status = verifier.poll_status(sessions[1].0).await.unwrap();
+    assert!(!matches!(status, Status::Complete(_)), "{status:?}");
+}
+
+#[tokio::test]
+async fn concurrent_responses() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let (id, request) = validated_request(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder,
+    )
+    .await;
+
+    // A valid and an invalid response race for the same session.
+    let valid = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+    let invalid = vp_response(jwt_vc::create_vp(NONCE, "did:example:other-verifier"));
+    let (valid, invalid) = tokio::join!(
+        wallet.submit_response(request.clone(), valid),
+        wallet.submit_response(request, invalid),
+    );
+
+    // Only the response that consumed the nonce set the outcome.
+    let status = verifier.poll_status(id).await.unwrap();
+    match (valid, invalid) {
+        (Ok(_), Err(_)) => assert!(
+            matches!(status, Status::Complete(Outcome::Success { .. })),
+            "{status:?}"
+        ),
+        (Err(_), Ok(_)) => assert!(
+            matches!(
+                status,
+                Status::Complete(Outcome::Failure {
+                    error: OutcomeError::AudienceMismatch,
+                    ..
+                })
+            ),
+            "{status:?}"
+        ),
+        results => panic!("expected exactly one response to be accepted: {results:?}"),
+    }
+}
+
+#[tokio::test]
+async fn sd_jwt_key_binding() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "pid",
+        "constraints": {
+            "fields": [{ "path": ["$.age_over_18"], "filter": { "const": true } }]
+        }
+    }));
+
+    // Each session has its own nonce, as a response consumes the nonce of its session.
+    let submit = |nonce: String, sd_jwt: fn(&str, &str) -> String| {
+        let verifier = verifier.clone();
+        let presentation_definition = presentation_definition.clone();
+        let wallet = &wallet;
+        async move {
+            let (id, request) =
+                validated_request(wallet, &verifier, presentation_definition, |builder| {
+                    builder.with_request_parameter(Nonce(nonce.clone()))
+                })
+                .await;
+
+            let presentation_submission: PresentationSubmission = serde_json::from_value(json!({
+                "id": "39881a17-e454-4d98-87ba-e3073d1014d6",
+                "definition_id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+                "descriptor_map": [{ "id": "pid", "path": "$", "format": "vc+sd-jwt" }]
+            }))
+            .unwrap();
+            let response = AuthorizationResponse::Unencoded(UnencodedAuthorizationResponse::new(
+                VpToken::Single(sd_jwt(&nonce, &request.client_id().0)),
+                presentation_submission.try_into().unwrap(),
+            ));
+            wallet.submit_response(request, response).await.unwrap();
+            verifier.poll_status(id).await.unwrap()
+        }
+    };
+
+    let status = submit(NONCE.to_owned(), |nonce, audience| {
+        jwt_vc::create_sd_jwt(Some((nonce, audience)))
+    })
+    .await;
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+
+    for (i, (sd_jwt, expected)) in [
+        (
+            (|_, audience| jwt_vc::create_sd_jwt(Some((NONCE, audience))))
+                as fn(&str, &str) -> String,
+            OutcomeError::NonceMismatch,
+        ),
+        (
+            |_, _| jwt_vc::create_sd_jwt(None),
+            OutcomeError::NonceMismatch,
+        ),
+        (
+            // The key binding JWT is not signed with the `cnf` key.
+            |nonce, audience| {
+                let mut presentation =
+                    SdJwtPresentation::parse(&jwt_vc::create_sd_jwt(None)).unwrap();
+                let other: JWK =
+                    serde_json::from_str(include_str!("examples/verifier.jwk")).unwrap();
+                presentation.key_binding_jwt = Some(jwt_vc::sign_key_binding(
+                    &presentation,
+                    nonce,
+                    audience,
+                    &other,
+                ));
+                presentation.to_string()
+            },
+            OutcomeError::InvalidPresentationSignature,
+        ),
+        (
+            // The issuer-signed JWT is not signed by the issuer.
+            |nonce, audience| {
+                let mut presentation =
+                    SdJwtPresentation::parse(&jwt_vc::create_sd_jwt(None)).unwrap();
+                let mut claims: Json =
+                    ssi::jwt::decode_unverified(&presentation.issuer_jwt).unwrap();
+                claims["vct"] = json!("https://credentials.example.com/other_credential");
+                let subject: JWK =
+                    serde_json::from_str(include_str!("examples/subject.jwk")).unwrap();
+                presentation.issuer_jwt = ssi::jwt::encode_sign(
+                    Algorithm::ES256,
+                    &claims,
+                    &JWK {
+                        key_id: jwt_vc::issuer_key().key_id,
+                        ..subject.clone()
+                    },
+                )
+                .unwrap();
+                presentation.key_binding_jwt = Some(jwt_vc::sign_key_binding(
+                    &presentation,
+                    nonce,
+                    audience,
+                    &subject,
+                ));
+                presentation.to_string()
+            },
+            OutcomeError::InvalidPresentationSignature,
+        ),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let status = submit(format!("{NONCE}-{i}"), sd_jwt).await;
+        assert!(
+            matches!(&status, Status::Complete(Outcome::Failure { error, .. }) if *error == expected),
+            "{status:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn ldp_vp_proof() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let presentation_definition = definition(json!({
+        "id": "degree",
+        "constraints": {
+            "fields": [{
+                "path": ["$.credentialSubject.degree.type"],
+                "filter": { "type": "string", "const": "BachelorDegree" }
+            }]
+        }
+    }));
+
+    let client_id = verifier.client_id().0.clone();
+    // Each session has its own nonce, as a response consumes the nonce of its session.
+    let nonces: Vec<String> = (0..4).map(|i| format!("{NONCE}-{i}")).collect();
+    for (nonce, vp, expected) in [
+        (
+            &nonces[0],
+            jwt_vc::create_ldp_vp(&nonces[0], &client_id).await,
+            None,
+        ),
+        (
+            &nonces[1],
+            jwt_vc::create_ldp_vp(NONCE, &client_id).await,
+            Some(OutcomeError::NonceMismatch),
+        ),
+        (
+            &nonces[2],
+            jwt_vc::create_ldp_vp(&nonces[2], "did:example:other-verifier").await,
+            Some(OutcomeError::AudienceMismatch),
+        ),
+        (
+            &nonces[3],
+            {
+                let mut vp = jwt_vc::create_ldp_vp(&nonces[3], &client_id).await;
+                vp["verifiableCredential"][0]["credentialSubject"]["degree"]["type"] =
+                    json!("MasterDegree");
+                vp
+            },
+            Some(OutcomeError::InvalidPresentationSignature),
+        ),
+    ] {
+        let (id, request) = validated_request(
+            &wallet,
+            &verifier,
+            presentation_definition.clone(),
+            |builder| builder.with_request_parameter(Nonce(nonce.clone())),
+        )
+        .await;
+
+        let presentation_submission: PresentationSubmission = serde_json::from_value(json!({
+            "id": "39881a17-e454-4d98-87ba-e3073d1014d6",
+            "definition_id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+            "descriptor_map": [{
+                "id": "degree",
+                "path": "$",
+                "format": "ldp_vp",
+                "path_nested": {
+                    "id": "degree",
+                    "path": "$.verifiableCredential[0]",
+                    "format": "ldp_vc"
+                }
+            }]
+        }))
+        .unwrap();
+        let response = AuthorizationResponse::Unencoded(UnencodedAuthorizationResponse::new(
+            VpToken::Single(vp.to_string()),
+            presentation_submission.try_into().unwrap(),
+        ));
+        wallet.submit_response(request, response).await.unwrap();
+
+        let status = verifier.poll_status(id).await.unwrap();
+        match expected {
+            None => assert!(
+                matches!(status, Status::Complete(Outcome::Success { .. })),
+                "{status:?}"
+            ),
+            Some(expected) => assert!(
+                matches!(&status, Status::Complete(Outcome::Failure { error, .. }) if *error == expected),
+                "{status:?}"
+            ),
+        }
+    }
+}
+
+#[tokio::test]
+async fn response_limits() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier_with_limits(8192, 4096, 1).await;
+
+    // An oversized presentation that is not even a JWT, which would otherwise fail to decode.
+    let oversized = vp_response("x".repeat(4097));
+
+    let vp = jwt_vc::create_vp(
+        NONCE,
+        "did:key:zDnaeaDj3YpPR4JXos2kCCNPS86hdELeN5PZh97KGkoFzUtGn",
+    );
+    let AuthorizationResponse::Unencoded(mut too_many) = vp_response(vp.clone()) else {
+        unreachable!()
+    };
+    too_many.1 = VpToken::Multiple(vec![vp.clone(), vp.clone()]);
+
+    // A single presentation that nests more credentials than the maximum.
+    let mut claims: Json = ssi::jwt::decode_unverified(&vp).unwrap();
+    let credential = claims["vp"]["verifiableCredential"][0].clone();
+    claims["vp"]["verifiableCredential"] = json!([credential.clone(), credential]);
+    let too_many_nested = vp_response(
+        ssi::jwt::encode_sign(Algorithm::ES256, &claims, &jwt_vc::subject_key()).unwrap(),
+    );
+
+    // An oversized response JWT, which would otherwise fail to decrypt. Validation of a
+    // `direct_post.jwt` request requires encryption parameters.
+    let encryption_key: Json = serde_json::from_str(
+        &p256::SecretKey::from_jwk_str(include_str!("examples/encryption.jwk"))
+            .unwrap()
+            .public_key()
+            .to_jwk_string(),
+    )
+    .unwrap();
+    let client_metadata: UntypedObject = serde_json::from_value(json!({
+        "jwks": {
+            "keys": [encryption_key]
+        },
+        "authorization_encrypted_response_alg": "ECDH-ES",
+        "encrypted_response_enc_values_supported": ["A256GCM"]
+    }))
+    .unwrap();
+    let oversized_jwt = AuthorizationResponse::Jwt(JwtAuthorizationResponse {
+        response: "x".repeat(8193),
+    });
+
+    for (i, (response_mode, response)) in [
+        (ResponseMode::DirectPost, oversized),
+        (
+            ResponseMode::DirectPost,
+            AuthorizationResponse::Unencoded(too_many),
+        ),
+        (ResponseMode::DirectPost, too_many_nested),
+        (ResponseMode::DirectPostJwt, oversized_jwt),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let (id, request) = validated_request(
+            &wallet,
+            &verifier,
+            definition(json!({ "id": "vc" })),
+            |builder| {
+                builder
+                    .with_request_parameter(response_mode)
+                    .with_request_parameter(Nonce(format!("{NONCE}-{i}")))
+                    .with_request_parameter(ClientMetadata(client_metadata.clone()))
+            },
+        )
+        .await;
+        wallet.submit_response(request, response).await.unwrap();
+
+        let status = verifier.poll_status(id).await.unwrap();
+        assert!(matches!(
+            status,
+            Status::Complete(Outcome::Failure {
+                error: OutcomeError::LimitExceeded,
+                ..
+            })
+        ));
+    }
+}
+
+#[tokio::test]
+async fn dcql_query() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    let dcql_query = DcqlQuery::from_json(
+        &json!({
+            "credentials": [
+                {
+                    "id": "mdl",
+                    "format": "mso_mdoc",
+                    "meta": {
+                        "doctype_value": "org.iso.18013.5.1.mDL"
+                    }
+                }
+            ]
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let (_, request) = verifier
+        .build_authorization_request()
+        .with_dcql_query(dcql_query)
         .with_request_parameter(ResponseMode::DirectPost)
         .with_request_parameter(ResponseType::VpToken)
-        .with_request_parameter(Nonce("random123".to_owned()))
-        .with_request_parameter(ClientMetadata(client_metadata))
+        .with_request_parameter(Nonce(NONCE.to_owned()))
+        .with_request_parameter(ClientMetadata(UntypedObject::default()))
         .build(wallet.metadata().clone())
         .await
         .unwrap();
 
     let request = wallet.validate_request(request).await.unwrap();
 
-    assert_eq!(
-        &presentation_definition,
-        request
-            .resolve_presentation_definition(wallet.http_client())
-            .await
-            .unwrap()
-            .parsed()
-    );
+    let dcql_query = request
+        .get::<parameters::DcqlQuery>()
+        .unwrap()
+        .unwrap()
+        .into_parsed();
 
-    assert_eq!(&ResponseType::VpToken, request.response_type());
+    let mdl = HeldCredential {
+        format: "mso_mdoc".into(),
+        doctype: Some("org.iso.18013.5.1.mDL".into()),
+        vct: None,
+        claims: json!({}),
+    };
+    let pid = HeldCredential {
+        format: "mso_mdoc".into(),
+        doctype: Some("eu.europa.ec.eudi.pid.1".into()),
+        vct: None,
+        claims: json!({}),
+    };
+    let credentials = [pid, mdl.clone()];
 
-    assert_eq!(&ResponseMode::DirectPost, request.response_mode());
+    let selected = dcql_query.select(&credentials);
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected["mdl"], [&mdl]);
+}
+
+#[tokio::test]
+async fn dcql_response() {
+    let (wallet, verifier) = jwt_vc::wallet_verifier().await;
+
+    // Each session has its own nonce, as a response consumes the nonce of its session.
+    let submit = |nonce: String, age_over_18: bool, query_id: &'static str| {
+        let verifier = verifier.clone();
+        let wallet = &wallet;
+        async move {
+            let dcql_query = DcqlQuery::from_json(
+                &json!({
+                    "credentials": [{
+                        "id": "pid",
+                        "format": "vc+sd-jwt",
+                        "meta": {
+                            "vct_values": ["https://credentials.example.com/identity_credential"]
+                        },
+                        "claims": [{ "path": ["age_over_18"], "values": [age_over_18] }]
+                    }]
+                })
+                .to_string(),
+            )
+            .unwrap();
+
+            let (id, request) = verifier
+                .build_authorization_request()
+                .with_dcql_query(dcql_query)
+                .with_request_parameter(ResponseMode::DirectPost)
+                .with_request_parameter(ResponseType::VpToken)
+                .with_request_parameter(Nonce(nonce.clone()))
+                .with_request_parameter(ClientMetadata(UntypedObject::default()))
+                .build(wallet.metadata().clone())
+                .await
+                .unwrap();
+            let request = wallet.validate_request(request).await.unwrap();
+
+            let sd_jwt = jwt_vc::create_sd_jwt(Some((&nonce, &request.client_id().0)));
+            let response = AuthorizationResponse::Unencoded(
+                UnencodedAuthorizationResponse::by_query_id([(query_id.into(), sd_jwt)].into()),
+            );
+            wallet.submit_response(request, response).await.unwrap();
+            verifier.poll_status(id).await.unwrap()
+        }
+    };
+
+    let Status::Complete(outcome) = submit(format!("{NONCE}-0"), true, "pid").await else {
+        panic!("expected a complete session")
+    };
+    assert!(matches!(outcome, Outcome::Success { .. }), "{outcome:?}");
+    assert!(outcome.presentation_submission().is_none());
+
+    // The claim does not have the requested value, and the presentation is not for the
+    // requested credential query.
+    for (i, (age_over_18, query_id)) in [(false, "pid"), (true, "mdl")].into_iter().enumerate() {
+        let status = submit(format!("{NONCE}-{}", i + 1), age_over_18, query_id).await;
+        assert!(
+            matches!(
+                &status,
+                Status::Complete(Outcome::Failure {
+                    error: OutcomeError::DcqlQueryNotSatisfied,
+                    ..
+                })
+            ),
+            "{status:?}"
+        );
+    }
+}
+
+/// A session store that records each status it is given.
+#[derive(Debug, Default)]
+struct RecordingStore {
+    store: MemoryStore,
+    statuses: Mutex<Vec<Status>>,
+}
+
+#[async_trait]
+impl SessionStore for RecordingStore {
+    async fn initiate(&self, session: Session) -> Result<()> {
+        self.statuses.lock().unwrap().push(session.status.clone());
+        self.store.initiate(session).await
+    }
+
+    async fn update_status(&self, uuid: Uuid, status: Status) -> Result<()> {
+        self.statuses.lock().unwrap().push(status.clone());
+        self.store.update_status(uuid, status).await
+    }
+
+    async fn get_session(&self, uuid: Uuid) -> Result<Session> {
+        self.store.get_session(uuid).await
+    }
+
+    async fn remove_session(&self, uuid: Uuid) -> Result<()> {
+        self.store.remove_session(uuid).await
+    }
+}
+
+#[tokio::test]
+async fn custom_session_store() {
+    let store = Arc::new(RecordingStore::default());
+    let (wallet, verifier) = jwt_vc::wallet_verifier_with_store(store.clone()).await;
+
+    let (id, request) = validated_request(
+        &wallet,
+        &verifier,
+        definition(json!({ "id": "vc" })),
+        |builder| builder,
+    )
+    .await;
+    let response = vp_response(jwt_vc::create_vp(NONCE, &request.client_id().0));
+    wallet.submit_response(request, response).await.unwrap();
+
+    let status = verifier.poll_status(id).await.unwrap();
+    assert!(matches!(status, Status::Complete(Outcome::Success { .. })));
+
+    let statuses = store.statuses.lock().unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses[0], Status::SentRequest);
+    assert!(matches!(
+        statuses[1],
+        Status::Complete(Outcome::Success { .. })
+    ));
+}
+
+/// A presentation definition with the single `input_descriptor`.
+fn definition(input_descriptor: Json) -> PresentationDefinition {
+    serde_json::from_value(json!({
+        "id": "0b4dd017-efa6-4a05-a269-9790fa3c22c2",
+        "input_descriptors": [input_descriptor]
+    }))
+    .unwrap()
+}
+
+/// Start a session for `presentation_definition`, returning its id and the request URL.
+///
+/// The request has a `direct_post` response mode, a `vp_token` response type, the [NONCE] and
+/// empty client metadata, unless `customize` overrides them.
+async fn start_session(
+    wallet: &jwt_vc::JwtVcWallet,
+    verifier: &Verifier,
+    presentation_definition: PresentationDefinition,
+    customize: impl FnOnce(RequestBuilder<'_>) -> RequestBuilder<'_>,
+) -> (Uuid, Url) {
+    let builder = verifier
+        .build_authorization_request()
+        .with_presentation_definition(presentation_definition)
+        .with_request_parameter(ResponseMode::DirectPost)
+        .with_request_parameter(ResponseType::VpToken)
+        .with_request_parameter(Nonce(NONCE.to_owned()))
+        .with_request_parameter(ClientMetadata(UntypedObject::default()));
+    customize(builder)
+        .build(wallet.metadata().clone())
+        .await
+        .unwrap()
+}
+
+/// Start a session as [start_session] does, returning the request validated by the wallet.
+async fn validated_request(
+    wallet: &jwt_vc::JwtVcWallet,
+    verifier: &Verifier,
+    presentation_definition: PresentationDefinition,
+    customize: impl FnOnce(RequestBuilder<'_>) -> RequestBuilder<'_>,
+) -> (Uuid, AuthorizationRequestObject) {
+    let (id, request) = start_session(wallet, verifier, presentation_definition, customize).await;
+    (id, wallet.validate_request(request).await.unwrap())
+}
 
-    // TODO: Response with a VP.
+fn vp_response(vp: String) -> AuthorizationResponse {
     let presentation_submission: PresentationSubmission = serde_json::from_value(json!(
         {
             "id": "39881a17-e454-4d98-87ba-e3073d1014d6",
@@ -70,27 +1588,20 @@ async fn w3c_vc_did_client_direct_post() {
                 {
                     "id": "vc",
                     "path": "$",
-                    "format": "jwt_vc_json"
+                    "format": "jwt_vp_json",
+                    "path_nested": {
+                        "id": "vc",
+                        "path": "$.vp.verifiableCredential[0]",
+                        "format": "jwt_vc_json"
+                    }
                 }
             ]
         }
-
     ))
     .unwrap();
 
-    let response = AuthorizationResponse::Unencoded(UnencodedAuthorizationResponse(
-        Default::default(),
-        VpToken(include_str!("examples/vc.jwt").to_owned()),
+    AuthorizationResponse::Unencoded(UnencodedAuthorizationResponse::new(
+        VpToken::Single(vp),
         presentation_submission.try_into().unwrap(),
-    ));
-
-    let status = verifier.poll_status(id).await.unwrap();
-    assert_eq!(Status::SentRequest, status);
-
-    let redirect = wallet.submit_response(request, response).await.unwrap();
-
-    assert_eq!(None, redirect);
-
-    let status = verifier.poll_status(id).await.unwrap();
-    assert_eq!(Status::Complete(Outcome::Success), status);
+    ))
 }
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use base64::prelude::*;
 use did_method_key::DIDKey;
 use http::{Request, Response};
 use oid4vp::{
@@ -10,21 +11,242 @@ use oid4vp::{
             verification::{did, RequestVerifier},
             AuthorizationRequestObject,
         },
+        credential_format::sd_jwt::{disclosure_digest, SdJwtPresentation, KEY_BINDING_JWT_TYP},
         metadata::WalletMetadata,
         response::AuthorizationResponse,
         util::AsyncHttpClient,
     },
     verifier::{
         request_signer::P256Signer,
-        session::{MemoryStore, Outcome},
-        Verifier,
+        session::{MemoryStore, Outcome, OutcomeError, SessionStore},
+        Verifier, VerifierBuilder,
     },
-    wallet::Wallet,
+    wallet::{
+        holder_signer::{HolderSigner, JwkSigner},
+        Wallet,
+    },
+};
+use serde_json::{json, Value as Json};
+use ssi::{
+    did::DIDMethod,
+    jsonld::ContextLoader,
+    jwk::{Algorithm, JWK},
+    vc::{Credential, LinkedDataProofOptions, Presentation, ProofPurpose, URI},
 };
-use serde_json::json;
-use ssi::did::DIDMethod;
+
+/// Create a verifiable presentation of the example credential, signed by its subject.
+pub fn create_vp(nonce: &str, audience: &str) -> String {
+    let holder = "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2";
+    let key = subject_key();
+    let claims = json!({
+        "iss": holder,
+        "aud": audience,
+        "nonce": nonce,
+        "vp": {
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiablePresentation"],
+            "holder": holder,
+            "verifiableCredential": [include_str!("examples/vc.jwt")]
+        }
+    });
+    ssi::jwt::encode_sign(Algorithm::ES256, &claims, &key).unwrap()
+}
+
+/// Create a Data Integrity presentation of a degree credential signed by its issuer, signed by
+/// its subject with the `challenge` and `domain` of an authorization request.
+pub async fn create_ldp_vp(challenge: &str, domain: &str) -> Json {
+    let holder = "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2";
+    let issuer_key = issuer_key();
+    let mut credential: Credential = serde_json::from_value(json!({
+        "@context": ["https://www.w3.org/2018/credentials/v1"],
+        "type": ["VerifiableCredential", "UniversityDegreeCredential"],
+        "issuer": ISSUER,
+        "issuanceDate": "2024-01-01T00:00:00Z",
+        "credentialSubject": {
+            "id": holder,
+            "degree": { "type": "BachelorDegree" }
+        }
+    }))
+    .unwrap();
+
+    let options = LinkedDataProofOptions {
+        verification_method: issuer_key.key_id.clone().map(URI::String),
+        proof_purpose: Some(ProofPurpose::AssertionMethod),
+        ..Default::default()
+    };
+    let proof = credential
+        .generate_proof(
+            &issuer_key,
+            &options,
+            DIDKey.to_resolver(),
+            &mut ContextLoader::default(),
+        )
+        .await
+        .unwrap();
+    credential.add_proof(proof);
+
+    let key = subject_key();
+    let mut presentation: Presentation = serde_json::from_value(json!({
+        "@context": ["https://www.w3.org/2018/credentials/v1"],
+        "type": ["VerifiablePresentation"],
+        "holder": holder,
+        "verifiableCredential": [credential]
+    }))
+    .unwrap();
+
+    let options = LinkedDataProofOptions {
+        verification_method: key.key_id.clone().map(URI::String),
+        proof_purpose: Some(ProofPurpose::Authentication),
+        challenge: Some(challenge.to_owned()),
+        domain: Some(domain.to_owned()),
+        ..Default::default()
+    };
+    let proof = presentation
+        .generate_proof(
+            &key,
+            &options,
+            DIDKey.to_resolver(),
+            &mut ContextLoader::default(),
+        )
+        .await
+        .unwrap();
+    presentation.add_proof(proof);
+    serde_json::to_value(presentation).unwrap()
+}
+
+/// Create an SD-JWT presentation of a credential bound to the key of its subject, with a key
+/// binding JWT for the `nonce` and `audience`, if given.
+pub fn create_sd_jwt(key_binding: Option<(&str, &str)>) -> String {
+    let key: JWK = serde_json::from_str(include_str!("examples/subject.jwk")).unwrap();
+    let disclosure =
+        BASE64_URL_SAFE_NO_PAD.encode(json!(["salt", "age_over_18", true]).to_string());
+    let issuer_jwt = ssi::jwt::encode_sign(
+        Algorithm::ES256,
+        &json!({
+            "iss": ISSUER,
+            "vct": "https://credentials.example.com/identity_credential",
+            "_sd": [disclosure_digest(&disclosure)],
+            "cnf": { "jwk": key.to_public() }
+        }),
+        &issuer_key(),
+    )
+    .unwrap();
+
+    let mut presentation =
+        SdJwtPresentation::parse(&format!("{issuer_jwt}~{disclosure}~")).unwrap();
+    if let Some((nonce, audience)) = key_binding {
+        presentation.key_binding_jwt = Some(sign_key_binding(&presentation, nonce, audience, &key));
+    }
+    presentation.to_string()
+}
+
+/// Sign a key binding JWT over an SD-JWT presentation with `key`.
+pub fn sign_key_binding(
+    presentation: &SdJwtPresentation,
+    nonce: &str,
+    audience: &str,
+    key: &JWK,
+) -> String {
+    let header = json!({ "alg": "ES256", "typ": KEY_BINDING_JWT_TYP });
+    let claims = presentation.key_binding_claims(nonce, audience);
+    let signing_input = format!(
+        "{}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(header.to_string()),
+        BASE64_URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature = ssi::jws::sign_bytes(Algorithm::ES256, signing_input.as_bytes(), key).unwrap();
+    format!(
+        "{signing_input}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(signature)
+    )
+}
+
+/// The DID of the issuer of the example SD-JWT.
+pub const ISSUER: &str = "did:key:zDnaeeex9MAVbhoWeDcbbGZdzM1zxqZqpC387jWoLhUr1BdST";
+
+/// The key of the issuer of the example SD-JWT, identified by its DID verification method.
+pub fn issuer_key() -> JWK {
+    let mut key: JWK = serde_json::from_str(include_str!("examples/issuer.jwk")).unwrap();
+    key.key_id = Some(format!(
+        "{ISSUER}#{}",
+        ISSUER.strip_prefix("did:key:").unwrap()
+    ));
+    key
+}
+
+/// The key of the subject of the example credential, identified by its DID verification method.
+pub fn subject_key() -> JWK {
+    let subject = "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2";
+    let mut key: JWK = serde_json::from_str(include_str!("examples/subject.jwk")).unwrap();
+    key.key_id = Some(format!(
+        "{subject}#{}",
+        subject.strip_prefix("did:key:").unwrap()
+    ));
+    key
+}
+
+/// Create a self-issued ID Token, signed by the subject of the example credential.
+pub fn create_id_token(nonce: &str, audience: &str) -> String {
+    let subject = "did:key:zDnaefqT1BrGGsJEZGwAiueouqMh6MqsZhaL1md5hkHgtfzb2";
+    let key = subject_key();
+    let claims = json!({
+        "iss": subject,
+        "sub": subject,
+        "aud": audience,
+        "nonce": nonce,
+    });
+    ssi::jwt::encode_sign(Algorithm::ES256, &claims, &key).unwrap()
+}
 
 pub async fn wallet_verifier() -> (JwtVcWallet, Arc<Verifier>) {
+    wallet_verifier_with_store(Arc::new(MemoryStore::default())).await
+}
+
+/// A wallet and a verifier that keeps its sessions in `session_store`.
+pub async fn wallet_verifier_with_store(
+    session_store: Arc<dyn SessionStore + Send + Sync>,
+) -> (JwtVcWallet, Arc<Verifier>) {
+    wallet_verifier_with(Verifier::builder().with_session_store(session_store)).await
+}
+
+/// A wallet and a verifier that asks the wallet to follow `redirect_uri` after a submission.
+pub async fn wallet_verifier_with_redirect(redirect_uri: &str) -> (JwtVcWallet, Arc<Verifier>) {
+    wallet_verifier_with(
+        Verifier::builder()
+            .with_redirect_uri(redirect_uri.parse().unwrap())
+            .with_session_store(Arc::new(MemoryStore::default())),
+    )
+    .await
+}
+
+/// A wallet and a verifier that limits the size of submitted responses, and the size and number
+/// of their presentations.
+pub async fn wallet_verifier_with_limits(
+    max_response_size: usize,
+    max_presentation_size: usize,
+    max_credentials: usize,
+) -> (JwtVcWallet, Arc<Verifier>) {
+    wallet_verifier_with(
+        Verifier::builder()
+            .with_max_response_size(max_response_size)
+            .with_max_presentation_size(max_presentation_size)
+            .with_max_credentials(max_credentials)
+            .with_session_store(Arc::new(MemoryStore::default())),
+    )
+    .await
+}
+
+/// A wallet and a verifier that passes the authorization request by reference.
+pub async fn wallet_verifier_by_reference() -> (JwtVcWallet, Arc<Verifier>) {
+    wallet_verifier_with(
+        Verifier::builder()
+            .by_reference("http://example.com/request".parse().unwrap())
+            .with_session_store(Arc::new(MemoryStore::default())),
+    )
+    .await
+}
+
+async fn wallet_verifier_with(builder: VerifierBuilder) -> (JwtVcWallet, Arc<Verifier>) {
     let verifier_did = "did:key:zDnaeaDj3YpPR4JXos2kCCNPS86hdELeN5PZh97KGkoFzUtGn".to_owned();
     let verifier_did_vm =
         "did:key:zDnaeaDj3YpPR4JXos2kCCNPS86hdELeN5PZh97KGkoFzUtGn#zDnaeaDj3YpPR4JXos2kCCNPS86hdELeN5PZh97KGkoFzUtGn".to_owned();
@@ -46,10 +268,13 @@ pub async fn wallet_verifier() -> (JwtVcWallet, Arc<Verifier>) {
         .unwrap(),
     );
     let verifier = Arc::new(
-        Verifier::builder()
+        builder
             .with_client(client)
             .with_submission_endpoint("http://example.com/submission".parse().unwrap())
-            .with_session_store(Arc::new(MemoryStore::default()))
+            .with_did_resolver(Arc::new(DIDKey))
+            .with_response_decryption_key(
+                p256::SecretKey::from_jwk_str(include_str!("examples/encryption.jwk")).unwrap(),
+            )
             .build()
             .await
             .unwrap(),
@@ -85,6 +310,7 @@ pub async fn wallet_verifier() -> (JwtVcWallet, Arc<Verifier>) {
             http_client,
             metadata,
             trusted_dids: vec![verifier_did],
+            holder_signer: JwkSigner::new(subject_key()),
         },
         verifier,
     )
@@ -94,6 +320,7 @@ pub struct JwtVcWallet {
     http_client: MockHttpClient,
     metadata: WalletMetadata,
     trusted_dids: Vec<String>,
+    holder_signer: JwkSigner,
 }
 
 pub struct MockHttpClient {
@@ -101,6 +328,11 @@ pub struct MockHttpClient {
 }
 
 impl JwtVcWallet {
+    /// Sign presentations and responses with `key` instead of the key of the subject.
+    pub fn set_holder_key(&mut self, key: JWK) {
+        self.holder_signer = JwkSigner::new(key);
+    }
+
     fn trusted_dids(&self) -> &[String] {
         &self.trusted_dids
     }
@@ -116,6 +348,9 @@ impl Wallet for JwtVcWallet {
     fn metadata(&self) -> &WalletMetadata {
         &self.metadata
     }
+    fn holder_signer(&self) -> Option<&(dyn HolderSigner + Send + Sync)> {
+        Some(&self.holder_signer)
+    }
 }
 
 #[async_trait]
@@ -139,26 +374,59 @@ impl RequestVerifier for JwtVcWallet {
 #[async_trait]
 impl AsyncHttpClient for MockHttpClient {
     async fn execute(&self, request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>> {
-        // Only expect submission.
         let body = request.body();
         let uri = request.uri();
+
+        if let Some(id) = uri.path().strip_prefix("/request/") {
+            let request_object = self
+                .verifier
+                .retrieve_authorization_request(id.parse().context("failed to parse id")?)
+                .await?;
+
+            return Response::builder()
+                .status(200)
+                .body(request_object.into_bytes())
+                .context("failed to build response");
+        }
+
         let id = uri
             .path()
             .strip_prefix("/submission/")
             .context("failed to extract id from path")?;
 
-        self.verifier
+        let redirect = self
+            .verifier
             .verify_response(
                 id.parse().context("failed to parse id")?,
                 AuthorizationResponse::from_x_www_form_urlencoded(body)
                     .context("failed to parse authorization response request")?,
-                |_, _| Box::pin(async { Outcome::Success }),
+                |_, response| {
+                    Box::pin(async move {
+                        match response {
+                            AuthorizationResponse::Unencoded(_) => Outcome::success(&response)
+                                .unwrap_or_else(|e| Outcome::Error { cause: Arc::new(e) }),
+                            AuthorizationResponse::Jwt(ref jwt) if jwt.is_signed() => {
+                                Outcome::success(&response)
+                                    .unwrap_or_else(|e| Outcome::Error { cause: Arc::new(e) })
+                            }
+                            AuthorizationResponse::Jwt(_) => Outcome::Failure {
+                                reason: "response was not decrypted".into(),
+                                error: OutcomeError::Rejected,
+                            },
+                        }
+                    })
+                },
             )
             .await?;
 
+        let body = match redirect {
+            Some(redirect) => serde_json::to_vec(&redirect)?,
+            None => vec![],
+        };
+
         Response::builder()
             .status(200)
-            .body(vec![])
+            .body(body)
             .context("failed to build response")
     }
 }